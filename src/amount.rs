@@ -0,0 +1,253 @@
+//! Exact asset amounts
+//!
+//! Balances, thresholds and transfer values are held as 256-bit integers in the
+//! asset's base units (wei, USDC micro-units, ...) together with the number of
+//! decimals needed to display them.
+use anyhow::{Result, anyhow, bail, ensure};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+pub use ruint::aliases::U256;
+
+/// Largest number of decimals whose scale factor fits in 256 bits
+pub const MAX_DECIMALS: u8 = 77;
+
+/// Decimals of native EVM assets (ETH)
+pub const NATIVE_DECIMALS: u8 = 18;
+
+/// Amount in base units with per-asset decimals
+#[derive(Debug, Clone, Copy)]
+pub struct Amount {
+    units: U256,
+    decimals: u8,
+}
+
+/// `10^decimals`
+pub fn scale_factor(decimals: u8) -> U256 {
+    U256::from(10u8).pow(U256::from(decimals))
+}
+
+impl Amount {
+    pub fn from_units(units: U256, decimals: u8) -> Self {
+        Self { units, decimals }
+    }
+
+    pub fn zero(decimals: u8) -> Self {
+        Self::from_units(U256::ZERO, decimals)
+    }
+
+    /// Base units, e.g. wei for ETH
+    pub fn units(&self) -> U256 {
+        self.units
+    }
+
+    pub fn decimals(&self) -> u8 {
+        self.decimals
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.units.is_zero()
+    }
+
+    /// Parse a human-readable decimal ("0.1", "250") into base units.
+    ///
+    /// Only plain digits with an optional fractional part are accepted; signs,
+    /// exponents, whitespace and more fractional digits than `decimals` are
+    /// rejected instead of being rounded.
+    pub fn parse(value: &str, decimals: u8) -> Result<Self> {
+        Self::parse_decimal(value)?.rescale(decimals)
+    }
+
+    /// Parse a human-readable decimal keeping exactly the digits written
+    fn parse_decimal(value: &str) -> Result<Self> {
+        let (whole, fraction) = match value.split_once('.') {
+            Some((whole, fraction)) => (whole, fraction),
+            None => (value, ""),
+        };
+        ensure!(
+            !whole.is_empty(),
+            "invalid amount {:?}: missing integer part",
+            value
+        );
+        ensure!(
+            value.len() == whole.len() || !fraction.is_empty(),
+            "invalid amount {:?}: missing digits after the decimal point",
+            value
+        );
+        ensure!(
+            whole
+                .bytes()
+                .chain(fraction.bytes())
+                .all(|b| b.is_ascii_digit()),
+            "invalid amount {:?}: expected digits with an optional decimal point",
+            value
+        );
+        ensure!(
+            fraction.len() <= MAX_DECIMALS as usize,
+            "invalid amount {:?}: more than {} decimal places",
+            value,
+            MAX_DECIMALS
+        );
+
+        let digits = format!("{}{}", whole, fraction);
+        let units = U256::from_str_radix(&digits, 10)
+            .map_err(|_| anyhow!("invalid amount {:?}: too large", value))?;
+
+        Ok(Self::from_units(units, fraction.len() as u8))
+    }
+
+    /// Express the same value with a different number of decimals.
+    ///
+    /// Fails instead of rounding when precision would be lost, or when the
+    /// value does not fit at the new scale.
+    pub fn rescale(&self, decimals: u8) -> Result<Self> {
+        ensure!(
+            decimals <= MAX_DECIMALS,
+            "{} decimals is above the supported maximum of {}",
+            decimals,
+            MAX_DECIMALS
+        );
+        let units = match decimals.cmp(&self.decimals) {
+            Ordering::Equal => self.units,
+            Ordering::Greater => self
+                .units
+                .checked_mul(scale_factor(decimals - self.decimals))
+                .ok_or_else(|| anyhow!("amount {} is too large for {} decimals", self, decimals))?,
+            Ordering::Less => {
+                let factor = scale_factor(self.decimals - decimals);
+                if !(self.units % factor).is_zero() {
+                    bail!("amount {} has more than {} decimal places", self, decimals);
+                }
+                self.units / factor
+            }
+        };
+        Ok(Self::from_units(units, decimals))
+    }
+
+    pub fn checked_add(&self, other: &Amount) -> Option<Amount> {
+        let other = other.rescale(self.decimals).ok()?;
+        Some(Self::from_units(
+            self.units.checked_add(other.units)?,
+            self.decimals,
+        ))
+    }
+
+    pub fn checked_sub(&self, other: &Amount) -> Option<Amount> {
+        let other = other.rescale(self.decimals).ok()?;
+        Some(Self::from_units(
+            self.units.checked_sub(other.units)?,
+            self.decimals,
+        ))
+    }
+
+    /// All `decimals` fractional digits, trailing zeros included, so that
+    /// parsing the text back gives the same base units and decimals
+    fn to_exact_string(self) -> String {
+        if self.decimals == 0 {
+            return self.units.to_string();
+        }
+        let factor = scale_factor(self.decimals);
+        format!(
+            "{}.{:0>width$}",
+            self.units / factor,
+            (self.units % factor).to_string(),
+            width = self.decimals as usize
+        )
+    }
+}
+
+impl PartialEq for Amount {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Amount {}
+
+impl PartialOrd for Amount {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Amount {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Bring the side with more decimals down to the other's scale, which
+        // cannot overflow; a remainder dropped on the way breaks a tie.
+        match self.decimals.cmp(&other.decimals) {
+            Ordering::Equal => self.units.cmp(&other.units),
+            Ordering::Greater => {
+                compare_scaled_down(self.units, self.decimals - other.decimals, other.units)
+            }
+            Ordering::Less => {
+                compare_scaled_down(other.units, other.decimals - self.decimals, self.units)
+                    .reverse()
+            }
+        }
+    }
+}
+
+/// Compare `units / 10^shift` with `other`, exactly
+fn compare_scaled_down(mut units: U256, mut shift: u8, other: U256) -> Ordering {
+    let mut remainder = false;
+    while shift > 0 {
+        // Divide in steps whose factor fits in 256 bits
+        let step = shift.min(MAX_DECIMALS);
+        let factor = scale_factor(step);
+        remainder |= !(units % factor).is_zero();
+        units /= factor;
+        shift -= step;
+    }
+    units.cmp(&other).then(match remainder {
+        true => Ordering::Greater,
+        false => Ordering::Equal,
+    })
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.decimals == 0 {
+            return write!(f, "{}", self.units);
+        }
+        let factor = scale_factor(self.decimals);
+        let whole = self.units / factor;
+        let fraction = self.units % factor;
+        if fraction.is_zero() {
+            return write!(f, "{}", whole);
+        }
+        let fraction = format!(
+            "{:0>width$}",
+            fraction.to_string(),
+            width = self.decimals as usize
+        );
+        write!(f, "{}.{}", whole, fraction.trim_end_matches('0'))
+    }
+}
+
+impl FromStr for Amount {
+    type Err = anyhow::Error;
+
+    /// Parse keeping the written precision; use [`Amount::rescale`] or
+    /// [`Amount::parse`] to bind the value to an asset's decimals.
+    fn from_str(value: &str) -> Result<Self> {
+        Self::parse_decimal(value)
+    }
+}
+
+/// Serialized with every decimal place ("1.500000000000000000" for 1.5 ETH)
+/// so persisted amounts keep their scale: deserializing keeps the digits as
+/// written.
+impl Serialize for Amount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_exact_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
@@ -0,0 +1,132 @@
+//! Approval Queue
+//!
+//! Holds sweeps above the configured amounts (or held by a sweep cap) in a
+//! queue persisted in the state file until an operator approves or rejects
+//! them with the `approvals` command, which records its decisions in a file
+//! of its own. Approved sweeps run on the next cycle. A split sweep is queued
+//! as one entry holding all of its legs.
+use crate::state_manager::StateManager;
+use crate::types::{Address, ApprovalConfig, ApprovalStatus, PendingApproval, SweepDecision};
+use anyhow::Result;
+use chrono::Utc;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// Approval policy on top of the queue kept by the state manager
+pub struct ApprovalQueue {
+    config: ApprovalConfig,
+    state_manager: Arc<StateManager>,
+}
+
+impl ApprovalQueue {
+    pub fn new(config: ApprovalConfig, state_manager: Arc<StateManager>) -> Self {
+        Self {
+            config,
+            state_manager,
+        }
+    }
+
+    /// Why `decision` needs approval, `None` when it can go out automatically.
+    /// A leg of a split sweep is judged by the whole sweep.
+    pub fn needs_approval(&self, decision: &SweepDecision) -> Result<Option<String>> {
+        let Some(limit) = self.config.above.get(&decision.asset) else {
+            return Ok(None);
+        };
+        let amount = decision.split.map_or(decision.amount, |split| split.total);
+        let limit = limit.rescale(amount.decimals())?;
+        Ok((amount > limit).then(|| {
+            format!(
+                "{} {} is above the approval amount {}",
+                amount, decision.asset, limit
+            )
+        }))
+    }
+
+    /// Queue a sweep made of `legs`, unless one for the same wallet, asset
+    /// and destinations is already waiting
+    pub async fn hold(
+        &self,
+        wallet: &Address,
+        legs: Vec<SweepDecision>,
+        reason: String,
+    ) -> Result<()> {
+        let destinations = |legs: &[SweepDecision]| {
+            legs.iter()
+                .map(|leg| leg.destination.clone())
+                .collect::<Vec<_>>()
+        };
+        if let Some(queued) = self
+            .state_manager
+            .approvals()
+            .await
+            .into_iter()
+            .find(|approval| {
+                approval.wallet == *wallet
+                    && approval.legs.first().map(|leg| &leg.asset)
+                        == legs.first().map(|leg| &leg.asset)
+                    && destinations(&approval.legs) == destinations(&legs)
+            })
+        {
+            info!(
+                "{} sweep on {} is already queued for approval as #{}",
+                queued.asset(),
+                wallet,
+                queued.id
+            );
+            return Ok(());
+        }
+
+        let approval = self
+            .state_manager
+            .queue_approval(wallet, legs, reason, self.config.ttl_seconds)
+            .await?;
+        warn!(
+            "SWEEP HELD FOR APPROVAL #{}: {} {} in {} transaction(s) on {}: {}",
+            approval.id,
+            approval.amount(),
+            approval.asset(),
+            approval.legs.len(),
+            wallet,
+            approval.reason
+        );
+        Ok(())
+    }
+
+    /// Pick up sweeps approved or rejected with the `approvals` command since
+    /// the last cycle
+    pub async fn reload(&self) {
+        self.state_manager.reload_approvals().await;
+    }
+
+    /// Drop queued sweeps whose TTL passed
+    pub async fn expire(&self) -> Result<()> {
+        for approval in self.state_manager.expire_approvals(Utc::now()).await? {
+            warn!(
+                "APPROVAL EXPIRED #{}: {} {} on {} was not executed by {}",
+                approval.id,
+                approval.amount(),
+                approval.asset(),
+                approval.wallet,
+                approval.expires_at
+            );
+        }
+        Ok(())
+    }
+
+    /// Approved sweeps for `wallet`, oldest first
+    pub async fn approved(&self, wallet: &Address) -> Vec<PendingApproval> {
+        self.state_manager
+            .approvals()
+            .await
+            .into_iter()
+            .filter(|approval| {
+                approval.wallet == *wallet && approval.status == ApprovalStatus::Approved
+            })
+            .collect()
+    }
+
+    /// Remove an approved sweep from the queue once it has run or been dropped
+    pub async fn remove(&self, id: u64) -> Result<PendingApproval> {
+        self.state_manager.remove_approval(id).await
+    }
+}
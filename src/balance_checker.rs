@@ -1,8 +1,216 @@
-//! Dummy Balance Checker
-use crate::types::Address;
-use anyhow::Result;
+//! Balance Checker
+//!
+//! Defines the `BalanceSource` abstraction used by the rules engine and the
+//! implementations that can be selected from config, and tells transient
+//! query failures, which are retried, from permanent ones.
+use crate::amount::U256;
+use crate::rpc::{self, BlockTag, HttpStatusError, JsonRpcClient, RpcErrorObject};
+use crate::timeouts::TimedOut;
+use crate::types::{Address, BalanceSourceConfig, RetryPolicy};
+use anyhow::{Context, Result, anyhow};
+use async_trait::async_trait;
 use rand::Rng;
+use serde_json::json;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::sync::Arc;
+use tokio::time::{sleep, timeout};
+use tracing::warn;
 
+/// Source of wallet balances queried by the rules engine
+#[async_trait]
+pub trait BalanceSource: Send + Sync {
+    /// Short name used to identify the source in logs and errors
+    fn name(&self) -> &str;
+
+    /// Native asset balance (ETH, ...) held by `address`, in base units (wei)
+    async fn check_native_balance(&self, address: &Address) -> Result<U256>;
+
+    /// Token balance held by `address` for the token contract at `token_address`,
+    /// in the token's base units
+    async fn check_token_balance(&self, address: &Address, token_address: &Address)
+    -> Result<U256>;
+}
+
+/// Build the balance source selected in config
+pub fn from_config(config: &BalanceSourceConfig) -> Result<Arc<dyn BalanceSource>> {
+    match config {
+        BalanceSourceConfig::Dummy { min, max } => {
+            anyhow::ensure!(
+                min < max,
+                "Invalid dummy balance range: min ({}) must be below max ({})",
+                min,
+                max
+            );
+            Ok(Arc::new(DummyBalanceChecker::new(*min, *max)))
+        }
+        BalanceSourceConfig::Rpc { url, block, retry } => {
+            // Wrapped even without retries, for the per-attempt timeout
+            let checker = Arc::new(RpcBalanceChecker::new(url.clone(), *block)?);
+            Ok(Arc::new(RetryingBalanceSource::new(checker, *retry)))
+        }
+    }
+}
+
+/// A failed balance query, by whether trying again can help
+#[derive(Debug)]
+pub enum BalanceError {
+    /// Timeouts, rate limits, dropped connections and server errors
+    Transient(anyhow::Error),
+    /// Invalid addresses, reverted calls, malformed responses and anything
+    /// not known to be transient
+    Permanent(anyhow::Error),
+}
+
+impl BalanceError {
+    /// Classify `error` by the first cause in its chain that tells either way
+    pub fn classify(error: anyhow::Error) -> Self {
+        let error = match error.downcast::<BalanceError>() {
+            Ok(classified) => return classified,
+            Err(error) => error,
+        };
+        match error.chain().find_map(transient_cause) {
+            Some(true) => BalanceError::Transient(error),
+            _ => BalanceError::Permanent(error),
+        }
+    }
+
+    pub fn is_transient(&self) -> bool {
+        matches!(self, BalanceError::Transient(_))
+    }
+}
+
+impl fmt::Display for BalanceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BalanceError::Transient(error) | BalanceError::Permanent(error) => {
+                write!(f, "{:#}", error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BalanceError {}
+
+/// Whether one cause of an error is transient, `None` when it does not say
+fn transient_cause(cause: &(dyn std::error::Error + 'static)) -> Option<bool> {
+    if let Some(error) = cause.downcast_ref::<BalanceError>() {
+        return Some(error.is_transient());
+    }
+    if let Some(error) = cause.downcast_ref::<RpcErrorObject>() {
+        return Some(error.is_transient());
+    }
+    if let Some(error) = cause.downcast_ref::<HttpStatusError>() {
+        return Some(error.is_transient());
+    }
+    if let Some(error) = cause.downcast_ref::<reqwest::Error>() {
+        if error.is_timeout() || error.is_connect() {
+            return Some(true);
+        }
+        // A connection reset mid-request or a body cut off mid-read says so
+        // in the I/O error beneath it
+        if error.is_request() || error.is_body() || error.is_decode() {
+            return None;
+        }
+        return Some(false);
+    }
+    if let Some(error) = cause.downcast_ref::<io::Error>() {
+        return Some(matches!(
+            error.kind(),
+            io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::ConnectionRefused
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::TimedOut
+                | io::ErrorKind::UnexpectedEof
+        ));
+    }
+    cause.downcast_ref::<TimedOut>().map(|_| true)
+}
+
+/// Retries transient failures of another balance source with capped
+/// exponential backoff and jitter; permanent failures are returned at once
+pub struct RetryingBalanceSource {
+    inner: Arc<dyn BalanceSource>,
+    policy: RetryPolicy,
+}
+
+impl RetryingBalanceSource {
+    pub fn new(inner: Arc<dyn BalanceSource>, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    async fn retry<F, Fut>(&self, address: &Address, query: F) -> Result<U256>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<U256>>,
+    {
+        let max_attempts = self.policy.max_attempts.max(1);
+        let mut attempt = 1;
+        loop {
+            let error = match timeout(self.policy.attempt_timeout(), query()).await {
+                Ok(Ok(balance)) => return Ok(balance),
+                Ok(Err(error)) => BalanceError::classify(error),
+                Err(_) => BalanceError::Transient(anyhow!(
+                    "attempt timed out after {}ms",
+                    self.policy.attempt_timeout_ms
+                )),
+            };
+            if !error.is_transient() {
+                return Err(error.into());
+            }
+            if attempt >= max_attempts {
+                return Err(anyhow!(error).context(format!(
+                    "{} balance query for {} failed {} times",
+                    self.inner.name(),
+                    address,
+                    attempt
+                )));
+            }
+
+            // Equal jitter: half the backoff, plus up to the other half
+            let backoff = self.policy.backoff(attempt);
+            let delay = backoff / 2 + backoff.mul_f64(rand::rng().random_range(0.0..=0.5));
+            warn!(
+                "Transient {} balance error for {} (attempt {} of {}), retrying in {}ms: {}",
+                self.inner.name(),
+                address,
+                attempt,
+                max_attempts,
+                delay.as_millis(),
+                error
+            );
+            sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[async_trait]
+impl BalanceSource for RetryingBalanceSource {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn check_native_balance(&self, address: &Address) -> Result<U256> {
+        self.retry(address, || self.inner.check_native_balance(address))
+            .await
+    }
+
+    async fn check_token_balance(
+        &self,
+        address: &Address,
+        token_address: &Address,
+    ) -> Result<U256> {
+        self.retry(address, || {
+            self.inner.check_token_balance(address, token_address)
+        })
+        .await
+    }
+}
+
+/// Dummy balance checker returning random balances
 pub struct DummyBalanceChecker {
     min: f64,
     max: f64,
@@ -12,19 +220,82 @@ impl DummyBalanceChecker {
     pub fn new(min: f64, max: f64) -> Self {
         Self { min, max }
     }
-    pub async fn check_native_balance(&self, _address: &Address) -> Result<f64> {
+}
+
+#[async_trait]
+impl BalanceSource for DummyBalanceChecker {
+    fn name(&self) -> &str {
+        "dummy"
+    }
+
+    async fn check_native_balance(&self, _address: &Address) -> Result<U256> {
         let mut rng = rand::rng();
         let balance = rng.random_range(self.min..self.max);
-        Ok(balance)
+        Ok(U256::from((balance * 1e18) as u128))
     }
 
-    pub async fn check_token_balance(
+    async fn check_token_balance(
         &self,
         _address: &Address,
         _token_address: &Address,
-    ) -> Result<u64> {
+    ) -> Result<U256> {
         let mut rng = rand::rng();
         let balance = rng.random_range(100_u64..200_u64);
+        Ok(U256::from(balance))
+    }
+}
+
+/// Balance checker backed by an Ethereum JSON-RPC endpoint
+pub struct RpcBalanceChecker {
+    client: JsonRpcClient,
+    block: BlockTag,
+}
+
+impl RpcBalanceChecker {
+    pub fn new(url: String, block: BlockTag) -> Result<Self> {
+        Ok(Self {
+            client: JsonRpcClient::new(url)?,
+            block,
+        })
+    }
+}
+
+#[async_trait]
+impl BalanceSource for RpcBalanceChecker {
+    fn name(&self) -> &str {
+        "rpc"
+    }
+
+    /// `eth_getBalance` at the configured block tag
+    async fn check_native_balance(&self, address: &Address) -> Result<U256> {
+        rpc::parse_address(address).map_err(BalanceError::Permanent)?;
+        let balance = self
+            .client
+            .get_balance(address, self.block)
+            .await
+            .map_err(BalanceError::classify)?;
         Ok(balance)
     }
+
+    /// `balanceOf(address)` via `eth_call` at the configured block tag
+    async fn check_token_balance(
+        &self,
+        address: &Address,
+        token_address: &Address,
+    ) -> Result<U256> {
+        rpc::parse_address(token_address).map_err(BalanceError::Permanent)?;
+        let call = json!({
+            "to": token_address,
+            "data": rpc::encode_balance_of(address).map_err(BalanceError::Permanent)?,
+        });
+        let result = self
+            .client
+            .call("eth_call", json!([call, self.block.as_str()]))
+            .await
+            .map_err(BalanceError::classify)?;
+        let word = result
+            .as_str()
+            .with_context(|| format!("eth_call returned a non-string result: {}", result))?;
+        rpc::decode_uint_word(word)
+    }
 }
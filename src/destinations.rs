@@ -0,0 +1,103 @@
+//! Sweep Destinations
+//!
+//! Chooses where each sweep is sent (the rule's destination, the asset's
+//! route, or the treasury) and refuses destinations outside the allowlist.
+//! Also divides split sweeps between their destinations.
+use crate::amount::{Amount, U256};
+use crate::types::{Address, Config, SplitDestination, SweepDecision, TOTAL_BPS};
+use anyhow::{Result, bail};
+use std::collections::HashMap;
+
+/// Destination routing and allowlist shared by the emitters
+#[derive(Debug, Clone)]
+pub struct Destinations {
+    treasury_address: Address,
+    routes: HashMap<String, Address>,
+    allowlist: Vec<Address>,
+}
+
+impl Destinations {
+    /// Send everything to `treasury_address`, the only allowed destination
+    pub fn new(treasury_address: Address) -> Self {
+        Self {
+            allowlist: vec![treasury_address.clone()],
+            treasury_address,
+            routes: HashMap::new(),
+        }
+    }
+
+    /// Route assets per `routes` and allow only `allowed_destinations`
+    /// (the treasury when that is empty)
+    pub fn from_config(config: &Config) -> Self {
+        let mut destinations = Self::new(config.treasury_address.clone());
+        destinations.routes = config.routes.clone();
+        if !config.allowed_destinations.is_empty() {
+            destinations.allowlist = config.allowed_destinations.clone();
+        }
+        destinations
+    }
+
+    /// Whether sweeps may be sent to `address` (compared case-insensitively)
+    pub fn is_allowed(&self, address: &str) -> bool {
+        self.allowlist
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(address))
+    }
+
+    /// Where `decision` goes, checked against the allowlist
+    pub fn resolve(&self, decision: &SweepDecision) -> Result<Address> {
+        let destination = decision
+            .destination
+            .as_ref()
+            .or_else(|| self.routes.get(&decision.asset))
+            .unwrap_or(&self.treasury_address);
+        if !self.is_allowed(destination) {
+            bail!(
+                "Destination {} for {} is not in the allowlist",
+                destination,
+                decision.asset
+            );
+        }
+        Ok(destination.clone())
+    }
+}
+
+/// Divide `amount` between `legs` by their bps, rounding each share down and
+/// giving the dust to the primary leg (the first when none is marked)
+pub fn split_amount(amount: Amount, legs: &[SplitDestination]) -> Vec<Amount> {
+    let total_bps = U256::from(TOTAL_BPS);
+    let (whole, rest) = amount.units().div_rem(total_bps);
+    let mut shares: Vec<U256> = legs
+        .iter()
+        .map(|leg| {
+            // Split before multiplying so large amounts cannot overflow
+            let bps = U256::from(leg.bps);
+            whole * bps + rest * bps / total_bps
+        })
+        .collect();
+
+    let assigned = shares
+        .iter()
+        .fold(U256::ZERO, |sum, share| sum.saturating_add(*share));
+    let primary = legs.iter().position(|leg| leg.primary).unwrap_or(0);
+    if let Some(share) = shares.get_mut(primary) {
+        *share += amount.units().saturating_sub(assigned);
+    }
+    shares
+        .into_iter()
+        .map(|units| Amount::from_units(units, amount.decimals()))
+        .collect()
+}
+
+/// Group sweep decisions into sweeps: the legs of a split together, every
+/// other decision on its own
+pub fn group_sweeps(decisions: Vec<SweepDecision>) -> Vec<Vec<SweepDecision>> {
+    let mut sweeps: Vec<Vec<SweepDecision>> = Vec::new();
+    for decision in decisions {
+        match (decision.split, sweeps.last_mut()) {
+            (Some(split), Some(sweep)) if !split.first => sweep.push(decision),
+            _ => sweeps.push(vec![decision]),
+        }
+    }
+    sweeps
+}
@@ -0,0 +1,312 @@
+//! EVM Transaction Builder
+//!
+//! Builds EIP-1559 (type 2) and legacy EIP-155 transactions for sweeps,
+//! RLP-encodes them and signs them with local secp256k1 keys.
+use crate::amount::U256;
+use crate::rlp::RlpItem;
+use crate::rpc::parse_address;
+use crate::types::{Address, GasConfig, PendingTx, SweepDecision, TxType};
+use anyhow::{Context, Result};
+use k256::ecdsa::{SigningKey, VerifyingKey};
+use sha3::{Digest, Keccak256};
+
+/// Selector of ERC-20 `transfer(address,uint256)`
+const TRANSFER_SELECTOR: [u8; 4] = [0xa9, 0x05, 0x9c, 0xbb];
+
+/// Unsigned EVM transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvmTransaction {
+    pub tx_type: TxType,
+    pub chain_id: u64,
+    pub nonce: u64,
+    pub gas_limit: u64,
+    /// Gas price for legacy transactions
+    pub max_fee_per_gas: U256,
+    /// Ignored for legacy transactions
+    pub max_priority_fee_per_gas: U256,
+    pub to: [u8; 20],
+    pub value: U256,
+    pub data: Vec<u8>,
+}
+
+/// Signed transaction ready for `eth_sendRawTransaction`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedTransaction {
+    pub raw: Vec<u8>,
+    pub hash: [u8; 32],
+}
+
+impl SignedTransaction {
+    pub fn raw_hex(&self) -> String {
+        format!("0x{}", hex::encode(&self.raw))
+    }
+
+    pub fn hash_hex(&self) -> String {
+        format!("0x{}", hex::encode(self.hash))
+    }
+}
+
+impl EvmTransaction {
+    /// Upper bound on the fee this transaction can pay, in wei
+    pub fn max_fee_cost(&self) -> U256 {
+        U256::from(self.gas_limit) * self.max_fee_per_gas
+    }
+
+    /// Copy of this transaction with both fees raised by `bump_percent`
+    /// (rounded up), or `None` when the new fee would exceed `max_fee_per_gas`.
+    ///
+    /// With `fee_from_value`, a native transfer pays the extra fee out of its
+    /// value, as a native sweep already spends the whole balance at the
+    /// original fee; `None` as well when the value cannot cover it. Transfers
+    /// that must deliver their full value, such as gas top-ups, leave it off.
+    pub fn bumped(
+        &self,
+        bump_percent: u64,
+        max_fee_per_gas: U256,
+        fee_from_value: bool,
+    ) -> Option<EvmTransaction> {
+        let bump = |fee: U256| {
+            let raised = fee.checked_mul(U256::from(100 + bump_percent))?;
+            Some(raised.div_ceil(U256::from(100u8)))
+        };
+        let new_max_fee = bump(self.max_fee_per_gas)?;
+        if new_max_fee > max_fee_per_gas {
+            return None;
+        }
+        let value = match fee_from_value && self.data.is_empty() && !self.value.is_zero() {
+            true => {
+                let extra_fee = U256::from(self.gas_limit)
+                    .checked_mul(new_max_fee - self.max_fee_per_gas)?;
+                self.value.checked_sub(extra_fee)?
+            }
+            false => self.value,
+        };
+        Some(EvmTransaction {
+            max_fee_per_gas: new_max_fee,
+            max_priority_fee_per_gas: bump(self.max_priority_fee_per_gas)?,
+            value,
+            ..self.clone()
+        })
+    }
+
+    /// Tracking entry for this transaction after broadcasting it as `hash`
+    pub fn to_pending(&self, asset: &str, hash: String) -> PendingTx {
+        PendingTx {
+            nonce: self.nonce,
+            asset: asset.to_string(),
+            tx_type: self.tx_type,
+            chain_id: self.chain_id,
+            to: format_address(&self.to),
+            value: self.value,
+            data: format!("0x{}", hex::encode(&self.data)),
+            gas_limit: self.gas_limit,
+            max_fee_per_gas: self.max_fee_per_gas,
+            max_priority_fee_per_gas: self.max_priority_fee_per_gas,
+            hashes: vec![hash],
+            broadcast_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// Rebuild the latest transaction broadcast for a pending entry
+    pub fn from_pending(pending: &PendingTx) -> Result<EvmTransaction> {
+        let data = hex::decode(pending.data.trim_start_matches("0x"))
+            .context("Invalid calldata in pending transaction")?;
+        Ok(EvmTransaction {
+            tx_type: pending.tx_type,
+            chain_id: pending.chain_id,
+            nonce: pending.nonce,
+            gas_limit: pending.gas_limit,
+            max_fee_per_gas: pending.max_fee_per_gas,
+            max_priority_fee_per_gas: pending.max_priority_fee_per_gas,
+            to: parse_address(&pending.to)?,
+            value: pending.value,
+            data,
+        })
+    }
+
+    /// Hash signed by the sender
+    pub fn signing_hash(&self) -> [u8; 32] {
+        match self.tx_type {
+            TxType::Eip1559 => {
+                let mut payload = vec![0x02];
+                payload.extend(RlpItem::List(self.eip1559_fields()).encode());
+                keccak256(&payload)
+            }
+            TxType::Legacy => {
+                let mut fields = self.legacy_fields();
+                fields.push(RlpItem::u64(self.chain_id));
+                fields.push(RlpItem::u64(0));
+                fields.push(RlpItem::u64(0));
+                keccak256(&RlpItem::List(fields).encode())
+            }
+        }
+    }
+
+    /// Sign with `key` and produce the raw transaction bytes
+    pub fn sign(&self, key: &SigningKey) -> Result<SignedTransaction> {
+        let (signature, recovery_id) = key
+            .sign_prehash_recoverable(&self.signing_hash())
+            .context("Failed to sign transaction")?;
+        let r = U256::from_be_slice(&signature.r().to_bytes());
+        let s = U256::from_be_slice(&signature.s().to_bytes());
+        let parity = recovery_id.is_y_odd() as u64;
+
+        let raw = match self.tx_type {
+            TxType::Eip1559 => {
+                let mut fields = self.eip1559_fields();
+                fields.push(RlpItem::u64(parity));
+                fields.push(RlpItem::uint(r));
+                fields.push(RlpItem::uint(s));
+                let mut raw = vec![0x02];
+                raw.extend(RlpItem::List(fields).encode());
+                raw
+            }
+            TxType::Legacy => {
+                let v = self
+                    .chain_id
+                    .checked_mul(2)
+                    .and_then(|v| v.checked_add(35 + parity))
+                    .context("Chain id too large for EIP-155")?;
+                let mut fields = self.legacy_fields();
+                fields.push(RlpItem::u64(v));
+                fields.push(RlpItem::uint(r));
+                fields.push(RlpItem::uint(s));
+                RlpItem::List(fields).encode()
+            }
+        };
+
+        Ok(SignedTransaction {
+            hash: keccak256(&raw),
+            raw,
+        })
+    }
+
+    fn eip1559_fields(&self) -> Vec<RlpItem> {
+        vec![
+            RlpItem::u64(self.chain_id),
+            RlpItem::u64(self.nonce),
+            RlpItem::uint(self.max_priority_fee_per_gas),
+            RlpItem::uint(self.max_fee_per_gas),
+            RlpItem::u64(self.gas_limit),
+            RlpItem::bytes(&self.to),
+            RlpItem::uint(self.value),
+            RlpItem::bytes(&self.data),
+            // Empty access list
+            RlpItem::List(Vec::new()),
+        ]
+    }
+
+    fn legacy_fields(&self) -> Vec<RlpItem> {
+        vec![
+            RlpItem::u64(self.nonce),
+            RlpItem::uint(self.max_fee_per_gas),
+            RlpItem::u64(self.gas_limit),
+            RlpItem::bytes(&self.to),
+            RlpItem::uint(self.value),
+            RlpItem::bytes(&self.data),
+        ]
+    }
+}
+
+/// Builds sweep transactions for one chain
+pub struct EvmTxBuilder {
+    chain_id: u64,
+    gas: GasConfig,
+}
+
+impl EvmTxBuilder {
+    pub fn new(chain_id: u64, gas: GasConfig) -> Self {
+        Self { chain_id, gas }
+    }
+
+    pub fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    pub fn gas(&self) -> &GasConfig {
+        &self.gas
+    }
+
+    /// Build the transaction moving `decision.amount` to `destination`.
+    ///
+    /// Native sweeps transfer value directly; token sweeps call
+    /// `transfer(destination, amount)` on `decision.token_address`.
+    pub fn build_sweep(
+        &self,
+        nonce: u64,
+        destination: &Address,
+        decision: &SweepDecision,
+    ) -> Result<EvmTransaction> {
+        let destination = parse_address(destination).context("Invalid sweep destination")?;
+        match &decision.token_address {
+            None => self.build(
+                nonce,
+                destination,
+                decision.amount.units(),
+                Vec::new(),
+                self.gas.native_gas_limit,
+            ),
+            Some(token_address) => {
+                let token = parse_address(token_address)
+                    .with_context(|| format!("Invalid {} token address", decision.asset))?;
+                let data = encode_erc20_transfer(&destination, decision.amount.units());
+                self.build(nonce, token, U256::ZERO, data, self.gas.token_gas_limit)
+            }
+        }
+    }
+
+    /// Build a plain native transfer
+    pub fn build_transfer(&self, nonce: u64, to: &Address, value: U256) -> Result<EvmTransaction> {
+        let to = parse_address(to).context("Invalid transfer recipient")?;
+        self.build(nonce, to, value, Vec::new(), self.gas.native_gas_limit)
+    }
+
+    fn build(
+        &self,
+        nonce: u64,
+        to: [u8; 20],
+        value: U256,
+        data: Vec<u8>,
+        gas_limit: u64,
+    ) -> Result<EvmTransaction> {
+        Ok(EvmTransaction {
+            tx_type: self.gas.tx_type,
+            chain_id: self.chain_id,
+            nonce,
+            gas_limit,
+            max_fee_per_gas: self.gas.max_fee_per_gas_wei()?,
+            max_priority_fee_per_gas: self.gas.max_priority_fee_per_gas_wei()?,
+            to,
+            value,
+            data,
+        })
+    }
+}
+
+/// Calldata for ERC-20 `transfer(to, amount)`
+pub fn encode_erc20_transfer(to: &[u8; 20], amount: U256) -> Vec<u8> {
+    let mut data = Vec::with_capacity(4 + 32 + 32);
+    data.extend_from_slice(&TRANSFER_SELECTOR);
+    data.extend_from_slice(&[0u8; 12]);
+    data.extend_from_slice(to);
+    data.extend_from_slice(&amount.to_be_bytes::<32>());
+    data
+}
+
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    Keccak256::digest(data).into()
+}
+
+/// Ethereum address controlled by a public key
+pub fn address_of(key: &VerifyingKey) -> [u8; 20] {
+    let point = key.to_encoded_point(false);
+    let hash = keccak256(&point.as_bytes()[1..]);
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&hash[12..]);
+    address
+}
+
+/// Lowercase `0x`-prefixed hex form of an address
+pub fn format_address(address: &[u8; 20]) -> Address {
+    format!("0x{}", hex::encode(address))
+}
@@ -0,0 +1,70 @@
+//! Local Keystore
+//!
+//! Loads secp256k1 signing keys for the hot wallets from a JSON file mapping
+//! each address to its hex private key:
+//!
+//! ```json
+//! { "0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f": "0x4646...4646" }
+//! ```
+//!
+//! Keys are stored unencrypted, so the file must be readable only by the
+//! service account.
+use crate::evm_tx::{address_of, format_address};
+use crate::rpc::parse_address;
+use crate::types::Address;
+use anyhow::{Context, Result, anyhow, ensure};
+use k256::ecdsa::SigningKey;
+use std::collections::HashMap;
+use std::path::Path;
+use tokio::fs;
+
+/// Signing keys indexed by address
+pub struct Keystore {
+    keys: HashMap<[u8; 20], SigningKey>,
+}
+
+impl Keystore {
+    /// Load the keystore file
+    pub async fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .await
+            .with_context(|| format!("Failed to read keystore {}", path.display()))?;
+        Self::from_json(&content)
+            .with_context(|| format!("Failed to parse keystore {}", path.display()))
+    }
+
+    /// Parse keystore JSON, checking that every key matches its address
+    pub fn from_json(content: &str) -> Result<Self> {
+        let entries: HashMap<Address, String> = serde_json::from_str(content)?;
+
+        let mut keys = HashMap::new();
+        for (address, private_key) in entries {
+            let expected = parse_address(&address)?;
+            let key_bytes = hex::decode(private_key.trim_start_matches("0x"))
+                .map_err(|_| anyhow!("Private key for {} is not hex", address))?;
+            let key = SigningKey::from_slice(&key_bytes)
+                .map_err(|_| anyhow!("Private key for {} is not a valid secp256k1 key", address))?;
+            let derived = address_of(key.verifying_key());
+            ensure!(
+                derived == expected,
+                "Private key listed for {} belongs to {}",
+                address,
+                format_address(&derived)
+            );
+            keys.insert(expected, key);
+        }
+
+        Ok(Self { keys })
+    }
+
+    /// Signing key for `address`
+    pub fn signer(&self, address: &Address) -> Result<&SigningKey> {
+        self.keys
+            .get(&parse_address(address)?)
+            .ok_or_else(|| anyhow!("No key for {} in keystore", address))
+    }
+
+    pub fn addresses(&self) -> Vec<Address> {
+        self.keys.keys().map(format_address).collect()
+    }
+}
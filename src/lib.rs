@@ -1,7 +1,20 @@
+pub mod amount;
+pub mod approvals;
 pub mod balance_checker;
+pub mod destinations;
+pub mod evm_tx;
+pub mod keystore;
 pub mod monitor;
+pub mod nonce_reconciler;
+pub mod planner;
+pub mod price_oracle;
+pub mod report;
+pub mod rlp;
+pub mod rpc;
 pub mod rules_engine;
 pub mod scheduler;
 pub mod state_manager;
+pub mod sweep_caps;
+pub mod timeouts;
 pub mod tx_emitter;
 pub mod types;
@@ -1,25 +1,26 @@
 //! Treasury Sweeper
-mod balance_checker;
-mod monitor;
-mod rules_engine;
-mod scheduler;
-mod state_manager;
-mod tx_emitter;
-mod types;
-use crate::balance_checker::DummyBalanceChecker;
-use crate::monitor::*;
-use crate::rules_engine::RulesEngine;
-use crate::scheduler::*;
-use crate::state_manager::StateManager;
-use crate::tx_emitter::MockTxEmitter;
-use crate::types::*;
 use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::signal;
-use tracing::info;
+use tracing::{info, warn};
 use tracing_subscriber::prelude::*;
+use treasury_sweeper::amount::{Amount, NATIVE_DECIMALS};
+use treasury_sweeper::approvals::ApprovalQueue;
+use treasury_sweeper::balance_checker;
+use treasury_sweeper::destinations::Destinations;
+use treasury_sweeper::monitor::*;
+use treasury_sweeper::nonce_reconciler::NonceReconciler;
+use treasury_sweeper::planner::{PlannedAction, Planner};
+use treasury_sweeper::price_oracle;
+use treasury_sweeper::rules_engine::RulesEngine;
+use treasury_sweeper::scheduler::*;
+use treasury_sweeper::state_manager::StateManager;
+use treasury_sweeper::sweep_caps::SweepCaps;
+use treasury_sweeper::tx_emitter::{self, EvmTxEmitter};
+use treasury_sweeper::types::*;
 
 #[derive(Parser)]
 #[command(name = "treasury_sweeper")]
@@ -36,10 +37,52 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Commands {
-    Once,
+    Once {
+        /// Print the cycle report as JSON on stdout
+        #[arg(long)]
+        json: bool,
+    },
 
     Continuous,
 
+    /// Show the sweeps the next cycle would perform without sending them,
+    /// reserving nonces or writing state
+    Plan {
+        /// Print the plan as JSON on stdout
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Replace a stuck transaction with a zero-value self-transfer at its nonce
+    Cancel {
+        /// Hot wallet that sent the stuck transaction
+        #[arg(long)]
+        address: String,
+
+        /// Nonce of the tracked transaction to cancel, defaults to the
+        /// wallet's oldest pending one
+        #[arg(long)]
+        nonce: Option<u64>,
+    },
+
+    /// Find nonces that block a wallet's later transactions
+    FillGaps {
+        /// Hot wallet to check, defaults to every configured wallet
+        #[arg(long)]
+        address: Option<String>,
+
+        /// Send a zero-value self-transfer into each gap instead of only
+        /// listing them
+        #[arg(long)]
+        confirm: bool,
+    },
+
+    /// Review sweeps held for manual approval
+    Approvals {
+        #[command(subcommand)]
+        command: ApprovalCommands,
+    },
+
     InitState {
         #[arg(long, default_value = "3")]
         num_wallets: usize,
@@ -49,10 +92,22 @@ enum Commands {
 
         /// ETH threshold for native balance rule
         #[arg(long, default_value = "0.1")]
-        eth_threshold: String,
+        eth_threshold: Amount,
     },
 }
 
+#[derive(Subcommand)]
+enum ApprovalCommands {
+    /// List queued sweeps
+    List,
+
+    /// Approve a queued sweep; it runs on the next cycle
+    Approve { id: u64 },
+
+    /// Remove a queued sweep without executing it
+    Reject { id: u64 },
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
  
@@ -62,7 +117,8 @@ async fn main() -> Result<()> {
             tracing_subscriber::EnvFilter::try_from_default_env()
                 .unwrap_or_else(|_| "treasury_sweeper=info".into()),
         )
-        .with(tracing_subscriber::fmt::layer())
+        // Logs go to stderr so stdout carries only command output
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
         .init();
 
     let cli = Cli::parse();
@@ -103,25 +159,46 @@ async fn main() -> Result<()> {
                 label: format!("Hot Wallet {}", i + 1),
                 rules: vec![
                     SweepRule::NativeBalance {
-                        threshold: eth_threshold.clone(),
+                        threshold: (*eth_threshold).into(),
                         asset: "ETH".to_string(),
+                        decimals: NATIVE_DECIMALS,
+                        cooldown_seconds: None,
+                        destination: None,
+                        destinations: vec![],
                     },
                 
                     SweepRule::TokenBalance {
-                        threshold: "100".to_string(),
+                        threshold: "100".parse()?,
                         token_address: "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48".to_string(),
                         asset: "USDC".to_string(),
+                        decimals: 0,
+                        cooldown_seconds: None,
+                        destination: None,
+                        destinations: vec![],
                     },
                 ],
+                cooldown_seconds: None,
             };
             hot_wallets.push(wallet_config);
         }
 
         let config = Config {
-            treasury_address: treasury_address,
+            treasury_address,
             hot_wallets,
             sweep_interval_seconds: *interval,
+            balance_source: BalanceSourceConfig::default(),
+            emitter: EmitterConfig::default(),
+            gas: GasConfig::default(),
+            nonce_reconciliation: None,
+            price_oracle: None,
+            caps: vec![],
+            approval: None,
+            routes: HashMap::new(),
+            allowed_destinations: vec![],
+            max_parallel_wallets: 8,
+            timeouts: TimeoutConfig::default(),
         };
+        config.validate()?;
         let config_json =
             serde_json::to_string_pretty(&config).context("Failed to serialize configuration")?;
 
@@ -143,13 +220,53 @@ async fn main() -> Result<()> {
     }
 
 
+    // The approval queue lives in the state file and needs no config
+    if let Commands::Approvals { command } = &cli.command {
+        match command {
+            ApprovalCommands::List => {
+                let approvals = state_manager.approvals().await;
+                if approvals.is_empty() {
+                    info!("No sweeps are waiting for approval");
+                }
+                for approval in approvals {
+                    info!(
+                        "#{} [{:?}] {} {} from {} in {} transaction(s) ({}), expires {}",
+                        approval.id,
+                        approval.status,
+                        approval.amount(),
+                        approval.asset(),
+                        approval.wallet,
+                        approval.legs.len(),
+                        approval.reason,
+                        approval.expires_at
+                    );
+                }
+            }
+            ApprovalCommands::Approve { id } => {
+                let approval = state_manager.approve(*id).await?;
+                info!(
+                    "Approved #{}: {} {} from {} runs on the next cycle",
+                    approval.id, approval.amount(), approval.asset(), approval.wallet
+                );
+            }
+            ApprovalCommands::Reject { id } => {
+                let approval = state_manager.reject(*id).await?;
+                info!(
+                    "Rejected #{}: {} {} from {}",
+                    approval.id, approval.amount(), approval.asset(), approval.wallet
+                );
+            }
+        }
+        return Ok(());
+    }
+
     info!("Loading configuration from {}", cli.config.display());
     let config_content = tokio::fs::read_to_string(&cli.config)
         .await
         .context("Failed to read config file")?;
 
-    let config: Config =
-        serde_json::from_str(&config_content).context("Failed to parse config file")?;
+    let config =
+        Config::from_json(&config_content).context("Failed to parse config file")?;
 
     info!("Configuration loaded:");
     info!(
@@ -159,23 +276,168 @@ async fn main() -> Result<()> {
     info!("  Hot wallets: {}", config.hot_wallets.len());
     info!("  Sweep interval: {}s", config.sweep_interval_seconds);
 
-   
-    let balance_checker = DummyBalanceChecker::new(0.0, 4.0);
-    let rules_engine = Arc::new(RulesEngine::new(balance_checker));
+    if let Commands::Cancel { address, nonce } = &cli.command {
+        let emitter = EvmTxEmitter::from_config(&config, state_manager.clone())
+            .await
+            .context("cancel needs the evm emitter")?;
+        let outcome = emitter.cancel(address, *nonce).await?;
+        info!(
+            "Cancellation finished: {} is {:?}",
+            outcome.hash, outcome.status
+        );
+        return Ok(());
+    }
 
-    let tx_emitter = Arc::new(MockTxEmitter::new(
+    if let Commands::FillGaps { address, confirm } = &cli.command {
+        let emitter = EvmTxEmitter::from_config(&config, state_manager.clone())
+            .await
+            .context("fill-gaps needs the evm emitter")?;
+        let addresses = match address {
+            Some(address) => vec![address.clone()],
+            None => config
+                .hot_wallets
+                .iter()
+                .map(|wallet| wallet.address.clone())
+                .collect(),
+        };
+
+        for address in &addresses {
+            let gaps = emitter.find_gaps(address).await?;
+            if gaps.is_empty() {
+                info!("No nonce gaps for {}", address);
+                continue;
+            }
+            warn!("Nonce gaps for {}: {:?}", address, gaps);
+            if !*confirm {
+                info!("Re-run with --confirm to fill them");
+                continue;
+            }
+            for nonce in gaps {
+                let outcome = emitter.fill_gap(address, nonce).await?;
+                info!(
+                    "Filled nonce {} for {}: {} is {:?}",
+                    nonce, address, outcome.hash, outcome.status
+                );
+            }
+        }
+        return Ok(());
+    }
+
+    let balance_checker = balance_checker::from_config(&config.balance_source)
+        .context("Failed to build balance source")?;
+    info!("  Balance source: {}", balance_checker.name());
+    let mut rules_engine = RulesEngine::new(balance_checker)
+        .with_state_manager(state_manager.clone())
+        .with_balance_timeout(config.timeouts.balance_seconds);
+    if let EmitterConfig::Evm { .. } = config.emitter {
+        // Real transactions pay for gas out of the swept native balance
+        rules_engine = rules_engine.with_native_fee(
+            config.gas.native_sweep_fee()?,
+            config.gas.max_native_fee_percent,
+        );
+    }
+    if let Some(oracle_config) = &config.price_oracle {
+        let oracle =
+            price_oracle::from_config(oracle_config).context("Failed to build price oracle")?;
+        info!("  Price oracle: {}", oracle.name());
+        rules_engine = rules_engine.with_price_oracle(oracle, oracle_config.max_age_seconds());
+    }
+    let rules_engine = Arc::new(rules_engine);
+
+    // Planning only reads state, so it runs before the emitter and the
+    // nonce reconciler are built
+    if let Commands::Plan { json } = &cli.command {
+        let mut planner = Planner::new(rules_engine, Destinations::from_config(&config));
+        if let EmitterConfig::Evm { .. } = config.emitter {
+            planner = planner.with_fees(
+                config.gas.native_sweep_fee()?,
+                config.gas.token_sweep_fee()?,
+            );
+        }
+        if !config.caps.is_empty() {
+            let sweep_caps = SweepCaps::new(config.caps.clone(), state_manager.clone());
+            planner = planner.with_sweep_caps(sweep_caps);
+        }
+        let approvals = ApprovalQueue::new(
+            config.approval.clone().unwrap_or_default(),
+            state_manager.clone(),
+        );
+        let plan = planner.with_approvals(approvals).plan(&config).await;
+
+        if *json {
+            let plan_json =
+                serde_json::to_string_pretty(&plan).context("Failed to serialize plan")?;
+            println!("{}", plan_json);
+            return Ok(());
+        }
+        for wallet in &plan.wallets {
+            println!("{} ({})", wallet.address, wallet.label);
+            if let Some(error) = &wallet.error {
+                println!("  error: {}", error);
+            }
+            for sweep in &wallet.sweeps {
+                let fee = match &sweep.estimated_fee {
+                    Some(fee) => format!(", fee up to {} ETH", fee),
+                    None => String::new(),
+                };
+                let action = match &sweep.action {
+                    PlannedAction::Send => "send".to_string(),
+                    PlannedAction::Hold { reason } => format!("hold: {}", reason),
+                    PlannedAction::Skip { reason } => format!("skip: {}", reason),
+                };
+                println!(
+                    "  {} {} to {}{} [{}]",
+                    sweep.amount, sweep.asset, sweep.destination, fee, action
+                );
+            }
+            for skipped in &wallet.skipped {
+                println!(
+                    "  {} ({}) skipped: {}",
+                    skipped.asset, skipped.rule_type, skipped.reason
+                );
+            }
+        }
+        println!("{} sweeps would be sent", plan.sweep_count);
+        return Ok(());
+    }
+
+    let tx_emitter = tx_emitter::from_config(&config, state_manager.clone())
+        .await
+        .context("Failed to build transaction emitter")?;
+    info!("  Emitter: {}", tx_emitter.name());
+
+    let nonce_reconciler = NonceReconciler::from_config(&config, state_manager.clone())
+        .context("Failed to build nonce reconciler")?
+        .map(Arc::new);
+
+    let mut monitor = WalletMonitor::new(rules_engine, tx_emitter);
+    if !config.caps.is_empty() {
+        info!("  Sweep caps: {}", config.caps.len());
+        let sweep_caps = SweepCaps::new(config.caps.clone(), state_manager.clone());
+        monitor = monitor.with_sweep_caps(sweep_caps);
+    }
+    let approvals = ApprovalQueue::new(
+        config.approval.clone().unwrap_or_default(),
         state_manager.clone(),
-        config.treasury_address.clone(),
-    ));
+    );
+    let monitor = Arc::new(monitor.with_approvals(approvals));
+    let mut scheduler = Scheduler::new(monitor, config.clone());
 
-    let monitor = Arc::new(WalletMonitor::new(rules_engine, tx_emitter));
-    let scheduler = Scheduler::new(monitor, config);
+    // The scheduler reconciles before every cycle, the first one included
+    if let Some(reconciler) = nonce_reconciler {
+        info!("  Nonce reconciliation: {:?}", reconciler.policy());
+        scheduler = scheduler.with_nonce_reconciler(reconciler);
+    }
 
     // Execute sweep command
     match cli.command {
-        Commands::Once => {
-            let count = scheduler.run_once().await?;
-            info!("Sweep cycle complete: {} sweeps executed", count);
+        Commands::Once { json } => {
+            let report = scheduler.run_once().await?;
+            if json {
+                let report_json = serde_json::to_string_pretty(&report)
+                    .context("Failed to serialize cycle report")?;
+                println!("{}", report_json);
+            }
         }
         Commands::Continuous => {
             let ctrl_c = signal::ctrl_c();
@@ -189,8 +451,12 @@ async fn main() -> Result<()> {
                 }
             }
         }
-        Commands::InitState { .. } => {
-            unreachable!("InitState handled above");
+        Commands::InitState { .. }
+        | Commands::Plan { .. }
+        | Commands::Approvals { .. }
+        | Commands::Cancel { .. }
+        | Commands::FillGaps { .. } => {
+            unreachable!("handled above");
         }
     }
 
@@ -1,70 +1,343 @@
 //! Wallet Monitor
 //!
 //! Orchestrates the sweep process: checks balances, evaluates rules,and triggers sweeps when conditions are met.
-use crate::rules_engine::RulesEngine;
-use crate::tx_emitter::MockTxEmitter;
-use crate::types::{Config, HotWalletConfig};
-use anyhow::Result;
+use crate::amount::U256;
+use crate::approvals::ApprovalQueue;
+use crate::destinations::group_sweeps;
+use crate::report::{CycleReport, WalletFailure, WalletReport};
+use crate::rules_engine::{Evaluation, RulesEngine};
+use crate::sweep_caps::{CapOutcome, SweepCaps};
+use crate::timeouts::{Deadline, TimeoutKind};
+use crate::tx_emitter::TxEmitter;
+use crate::types::{
+    Config, HotWalletConfig, PendingApproval, SkippedSweep, SweepDecision, TimeoutConfig,
+};
+use anyhow::{Context, Result};
+use chrono::Utc;
+use futures_util::{StreamExt, stream};
 use std::sync::Arc;
 use tracing::{info, warn};
 
 /// Wallet monitor that orchestrates the sweep process
 pub struct WalletMonitor {
     rules_engine: Arc<RulesEngine>,
-    tx_emitter: Arc<MockTxEmitter>,
+    tx_emitter: Arc<dyn TxEmitter>,
+    sweep_caps: Option<SweepCaps>,
+    approvals: Option<ApprovalQueue>,
 }
 
 impl WalletMonitor {
-    pub fn new(rules_engine: Arc<RulesEngine>, tx_emitter: Arc<MockTxEmitter>) -> Self {
+    pub fn new(rules_engine: Arc<RulesEngine>, tx_emitter: Arc<dyn TxEmitter>) -> Self {
         Self {
             rules_engine,
             tx_emitter,
+            sweep_caps: None,
+            approvals: None,
         }
     }
 
-    async fn check_and_sweep(&self, wallet_config: &HotWalletConfig) -> Result<usize> {
+    /// Check every sweep against `sweep_caps` before it is emitted
+    pub fn with_sweep_caps(mut self, sweep_caps: SweepCaps) -> Self {
+        self.sweep_caps = Some(sweep_caps);
+        self
+    }
+
+    /// Queue sweeps that need approval in `approvals` and run the approved ones
+    pub fn with_approvals(mut self, approvals: ApprovalQueue) -> Self {
+        self.approvals = Some(approvals);
+        self
+    }
+
+    /// Queue a held sweep, made of `legs`, for approval as one entry, or only
+    /// log it without a queue
+    async fn hold(
+        &self,
+        wallet_config: &HotWalletConfig,
+        legs: Vec<SweepDecision>,
+        reason: String,
+        report: &mut WalletReport,
+    ) -> Result<()> {
+        for decision in &legs {
+            report.held.push(SkippedSweep {
+                asset: decision.asset.clone(),
+                rule_type: decision.rule_type.clone(),
+                reason: reason.clone(),
+            });
+        }
+        match &self.approvals {
+            Some(approvals) => approvals.hold(&wallet_config.address, legs, reason).await,
+            None => {
+                warn!(
+                    "SWEEP HELD: {} on {}: {}",
+                    legs[0].asset, wallet_config.address, reason
+                );
+                Ok(())
+            }
+        }
+    }
+
+    /// Execute an approved sweep if the wallet still holds the approved amount
+    /// for every leg, plus the fee each native leg pays.
+    ///
+    /// The sweep leaves the queue once it has been emitted or dropped; one
+    /// that fails before any leg went out stays approved and is tried again
+    /// on the next cycle.
+    async fn run_approved(
+        &self,
+        approvals: &ApprovalQueue,
+        wallet_config: &HotWalletConfig,
+        approval: PendingApproval,
+        report: &mut WalletReport,
+    ) -> Result<()> {
+        let Some(first) = approval.legs.first() else {
+            approvals.remove(approval.id).await?;
+            return Ok(());
+        };
+        // A native sweep's amount already has its fee taken off
+        let native_fee = self.rules_engine.native_sweep_fee();
+        let mut needed = U256::ZERO;
+        for leg in &approval.legs {
+            let fee = match leg.token_address {
+                Some(_) => U256::ZERO,
+                None => native_fee,
+            };
+            needed = needed
+                .checked_add(leg.amount.units())
+                .and_then(|needed| needed.checked_add(fee))
+                .context("Approved sweep total overflowed")?;
+        }
+        let balance = self
+            .rules_engine
+            .current_balance(
+                &wallet_config.address,
+                first.token_address.as_ref(),
+                &first.asset,
+            )
+            .await?;
+        if balance < needed {
+            let reason = format!(
+                "balance is below the approved {} of queued sweep #{} and its fees",
+                approval.amount(),
+                approval.id
+            );
+            warn!(
+                "APPROVED SWEEP DROPPED: {} on {}: {}",
+                first.asset, wallet_config.address, reason
+            );
+            report.skipped.push(SkippedSweep {
+                asset: first.asset.clone(),
+                rule_type: first.rule_type.clone(),
+                reason,
+            });
+            approvals.remove(approval.id).await?;
+            return Ok(());
+        }
+
+        info!(
+            "Executing approved sweep #{}: {} {}",
+            approval.id,
+            approval.amount(),
+            first.asset
+        );
+        for (sent, decision) in approval.legs.iter().enumerate() {
+            match self
+                .tx_emitter
+                .emit_sweep(&wallet_config.address, decision)
+                .await
+            {
+                Ok(submitted) => report.transactions.push(submitted),
+                // Legs already sent must not go out again on a retry
+                Err(e) if sent > 0 => {
+                    approvals.remove(approval.id).await?;
+                    return Err(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        approvals.remove(approval.id).await?;
+        Ok(())
+    }
+
+    /// Check one wallet, recording what it does in `report` as it goes.
+    ///
+    /// Evaluation is cut off at `deadline`, but a sweep already started is
+    /// always seen through: once it has passed, only no new sweep starts.
+    async fn check_and_sweep(
+        &self,
+        wallet_config: &HotWalletConfig,
+        deadline: Option<Deadline>,
+        report: &mut WalletReport,
+    ) -> Result<()> {
         info!(
             "Checking wallet {} ({})",
             wallet_config.address,
             wallet_config.label
         );
 
-        // Evaluate all rules
-        let decisions = self.rules_engine.evaluate(wallet_config).await?;
+        // Sweeps approved since the last cycle go first
+        if let Some(approvals) = &self.approvals {
+            for approval in approvals.approved(&wallet_config.address).await {
+                Deadline::check(deadline)?;
+                self.run_approved(approvals, wallet_config, approval, report)
+                    .await?;
+            }
+        }
+
+        // Evaluate all rules; the ones evaluated before a failure or the
+        // deadline stay in the report
+        let mut evaluation = Evaluation::default();
+        let result = Deadline::run(
+            deadline,
+            self.rules_engine
+                .evaluate_into(wallet_config, &mut evaluation),
+        )
+        .await;
+        for skipped in &evaluation.skipped {
+            warn!(
+                "SWEEP SKIPPED: {} ({}) on {}: {}",
+                skipped.asset, skipped.rule_type, wallet_config.address, skipped.reason
+            );
+        }
+        report.rules.append(&mut evaluation.rules);
+        report.skipped.append(&mut evaluation.skipped);
+        result?;
+        let decisions = evaluation.decisions;
 
-        let sweep_count = decisions.len();
-        
-        // Execute all triggered sweeps
-        for decision in decisions {
-            self.tx_emitter
-                .emit_sweep(&wallet_config.address, &decision)
-                .await?;
+        // Execute all triggered sweeps; the legs of a split go together
+        for legs in group_sweeps(decisions) {
+            let (asset, rule_type) = (legs[0].asset.clone(), legs[0].rule_type.clone());
+            // Waiting for another wallet's sweep under a shared cap is bounded
+            let _shared_cap = match &self.sweep_caps {
+                Some(sweep_caps) => {
+                    Deadline::run(deadline, async { Ok(sweep_caps.lock_shared(&asset).await) })
+                        .await?
+                }
+                None => None,
+            };
+            let legs = match &self.sweep_caps {
+                Some(sweep_caps) => {
+                    match sweep_caps
+                        .check(&wallet_config.address, legs.clone())
+                        .await?
+                    {
+                        CapOutcome::Allow(legs) => legs,
+                        CapOutcome::Hold(reason) => {
+                            self.hold(wallet_config, legs, reason, report).await?;
+                            continue;
+                        }
+                        CapOutcome::Skip(reason) => {
+                            warn!(
+                                "SWEEP SKIPPED: {} on {}: {}",
+                                asset, wallet_config.address, reason
+                            );
+                            report.skipped.push(SkippedSweep {
+                                asset,
+                                rule_type,
+                                reason,
+                            });
+                            continue;
+                        }
+                    }
+                }
+                None => legs,
+            };
+            if let Some(approvals) = &self.approvals
+                && let Some(reason) = approvals.needs_approval(&legs[0])?
+            {
+                self.hold(wallet_config, legs, reason, report).await?;
+                continue;
+            }
+            Deadline::check(deadline)?;
+            for decision in &legs {
+                let submitted = self
+                    .tx_emitter
+                    .emit_sweep(&wallet_config.address, decision)
+                    .await?;
+                report.transactions.push(submitted);
+            }
         }
 
-        Ok(sweep_count)
+        Ok(())
     }
 
+    /// Check one wallet within the wallet timeout and what is left of the
+    /// cycle (`cycle_deadline`)
+    async fn check_within(
+        &self,
+        wallet_config: &HotWalletConfig,
+        timeouts: &TimeoutConfig,
+        cycle_deadline: Option<Deadline>,
+    ) -> WalletReport {
+        let mut report =
+            WalletReport::new(wallet_config.address.clone(), wallet_config.label.clone());
+        let result = async {
+            // Wallets still waiting when the cycle runs out are not started
+            Deadline::check(cycle_deadline)?;
+            let deadline = Deadline::earliest(
+                Deadline::after(TimeoutKind::Wallet, timeouts.wallet_seconds),
+                cycle_deadline,
+            );
+            self.check_and_sweep(wallet_config, deadline, &mut report)
+                .await
+        }
+        .await;
 
-    /// Returns the total number of sweeps executed across all wallets
-    pub async fn check_all_wallets(&self, config: &Config) -> Result<usize> {
-        let mut total_sweep_count = 0;
-
-        for wallet_config in &config.hot_wallets {
-            match self.check_and_sweep(wallet_config).await {
-                Ok(count) => {
-                    total_sweep_count += count;
+        if let Err(e) = result {
+            let failure = WalletFailure::from_error(&e);
+            match &failure {
+                WalletFailure::TimedOut(timed_out) => {
+                    warn!("WALLET TIMED OUT: {}: {}", wallet_config.address, timed_out)
                 }
-                Err(e) => {
-                    warn!(
-                        "Error checking wallet {}: {}",
-                        wallet_config.address,
-                        e
-                    );
-                    // Continue with other wallets even if one fails
+                WalletFailure::Error(_) => {
+                    warn!("Error checking wallet {}: {}", wallet_config.address, e)
                 }
             }
+            report.error = Some(failure);
         }
+        report
+    }
 
-        Ok(total_sweep_count)
+    /// Check every wallet, up to `max_parallel_wallets` at the same time, and
+    /// return each wallet's report in config order
+    ///
+    /// A wallet that fails or times out does not stop the others.
+    pub async fn check_wallets(&self, config: &Config) -> Result<Vec<WalletReport>> {
+        if let Some(approvals) = &self.approvals {
+            approvals.reload().await;
+            approvals.expire().await?;
+        }
+
+        let timeouts = &config.timeouts;
+        let deadline = Deadline::after(TimeoutKind::Cycle, timeouts.cycle_seconds);
+        let mut reports: Vec<_> = stream::iter(config.hot_wallets.iter().enumerate())
+            .map(|(index, wallet_config)| async move {
+                let report = self.check_within(wallet_config, timeouts, deadline).await;
+                (index, report)
+            })
+            .buffer_unordered(config.max_parallel_wallets.max(1))
+            .collect()
+            .await;
+        reports.sort_by_key(|(index, _)| *index);
+
+        Ok(reports.into_iter().map(|(_, report)| report).collect())
+    }
+
+    /// Check every wallet and report what the cycle did
+    pub async fn run_cycle(&self, config: &Config) -> Result<CycleReport> {
+        let started_at = Utc::now();
+        let report = CycleReport::new(started_at, self.check_wallets(config).await?);
+        if report.failed_wallets > 0 {
+            warn!(
+                "{} of {} wallets could not be checked",
+                report.failed_wallets,
+                report.wallets.len()
+            );
+        }
+        Ok(report)
+    }
+
+    /// Returns the total number of sweeps executed across all wallets
+    pub async fn check_all_wallets(&self, config: &Config) -> Result<usize> {
+        Ok(self.run_cycle(config).await?.sweep_count)
     }
 }
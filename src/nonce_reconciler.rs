@@ -0,0 +1,175 @@
+//! Nonce Reconciler
+//!
+//! Compares each wallet's stored nonce with the node's pending transaction
+//! count and applies the configured policy when they disagree.
+use crate::rpc::{BlockTag, JsonRpcClient, parse_address};
+use crate::state_manager::StateManager;
+use crate::types::{Address, Config, EmitterConfig, NoncePolicy};
+use anyhow::{Context, Result, anyhow};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// What reconciliation did about a discrepancy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReconcileAction {
+    /// The stored nonce was replaced with the chain's
+    Adopted,
+    /// The wallet was halted
+    Halted,
+    /// The discrepancy was only logged
+    Alerted,
+}
+
+/// A wallet whose stored nonce did not match the chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NonceDiscrepancy {
+    pub address: Address,
+    pub local_nonce: u64,
+    pub chain_nonce: u64,
+    pub action: ReconcileAction,
+}
+
+/// Reconciles stored nonces with `eth_getTransactionCount(addr, "pending")`
+pub struct NonceReconciler {
+    rpc: Arc<JsonRpcClient>,
+    state_manager: Arc<StateManager>,
+    policy: NoncePolicy,
+}
+
+impl NonceReconciler {
+    pub fn new(
+        rpc: Arc<JsonRpcClient>,
+        state_manager: Arc<StateManager>,
+        policy: NoncePolicy,
+    ) -> Self {
+        Self {
+            rpc,
+            state_manager,
+            policy,
+        }
+    }
+
+    /// Build the reconciler described by `config`, `None` when it is disabled
+    pub fn from_config(config: &Config, state_manager: Arc<StateManager>) -> Result<Option<Self>> {
+        let Some(reconciliation) = &config.nonce_reconciliation else {
+            return Ok(None);
+        };
+
+        let rpc_url = match (&reconciliation.rpc_url, &config.emitter) {
+            (Some(url), _) => url.clone(),
+            (None, EmitterConfig::Evm { rpc_url, .. }) => rpc_url.clone(),
+            (None, _) => {
+                return Err(anyhow!(
+                    "nonce_reconciliation needs an rpc_url unless the evm emitter is used"
+                ));
+            }
+        };
+
+        let rpc = Arc::new(JsonRpcClient::new(rpc_url)?);
+        Ok(Some(Self::new(rpc, state_manager, reconciliation.policy)))
+    }
+
+    pub fn policy(&self) -> NoncePolicy {
+        self.policy
+    }
+
+    /// Reconcile every configured hot wallet and the gas station, which
+    /// sends from its own nonce sequence.
+    ///
+    /// A wallet that cannot be checked is logged and skipped, so one bad
+    /// address or RPC failure does not block the others.
+    pub async fn reconcile_all(&self, config: &Config) -> Vec<NonceDiscrepancy> {
+        let mut discrepancies = Vec::new();
+
+        let mut addresses: Vec<&Address> = config
+            .hot_wallets
+            .iter()
+            .map(|wallet_config| &wallet_config.address)
+            .collect();
+        if let EmitterConfig::Evm {
+            gas_station: Some(station),
+            ..
+        } = &config.emitter
+            && !addresses.contains(&&station.address)
+        {
+            addresses.push(&station.address);
+        }
+
+        for address in addresses {
+            match self.reconcile_wallet(address).await {
+                Ok(Some(discrepancy)) => discrepancies.push(discrepancy),
+                Ok(None) => {}
+                Err(e) => {
+                    warn!("Nonce reconciliation failed for {}: {:#}", address, e);
+                }
+            }
+        }
+
+        discrepancies
+    }
+
+    /// Reconcile one wallet, returning the discrepancy if there was one.
+    ///
+    /// A wallet halted by an earlier discrepancy resumes once the nonces agree.
+    pub async fn reconcile_wallet(&self, address: &Address) -> Result<Option<NonceDiscrepancy>> {
+        parse_address(address)?;
+        let chain_nonce = self
+            .rpc
+            .get_transaction_count(address, BlockTag::Pending)
+            .await
+            .with_context(|| format!("Failed to fetch transaction count for {}", address))?;
+        let local_nonce = self.state_manager.next_nonce(address).await;
+
+        if chain_nonce == local_nonce {
+            if self.state_manager.halted_reason(address).await.is_some() {
+                info!(
+                    event = "nonce_reconciled",
+                    wallet = %address,
+                    nonce = local_nonce,
+                    "Nonce for {} matches the chain again, resuming sweeps",
+                    address
+                );
+                self.state_manager.set_halted(address, None).await?;
+            }
+            return Ok(None);
+        }
+
+        let action = match self.policy {
+            NoncePolicy::Adopt => {
+                self.state_manager
+                    .set_next_nonce(address, chain_nonce)
+                    .await?;
+                ReconcileAction::Adopted
+            }
+            NoncePolicy::Halt => {
+                let reason = format!(
+                    "stored nonce {} does not match chain nonce {}",
+                    local_nonce, chain_nonce
+                );
+                self.state_manager.set_halted(address, Some(reason)).await?;
+                ReconcileAction::Halted
+            }
+            NoncePolicy::Alert => ReconcileAction::Alerted,
+        };
+
+        warn!(
+            event = "nonce_discrepancy",
+            wallet = %address,
+            local_nonce,
+            chain_nonce,
+            policy = ?self.policy,
+            action = ?action,
+            "Nonce discrepancy for {}: stored {}, chain {}",
+            address,
+            local_nonce,
+            chain_nonce
+        );
+
+        Ok(Some(NonceDiscrepancy {
+            address: address.clone(),
+            local_nonce,
+            chain_nonce,
+            action,
+        }))
+    }
+}
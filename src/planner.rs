@@ -0,0 +1,218 @@
+//! Sweep Planner
+//!
+//! Works out what a sweep cycle would do without doing it: evaluates every
+//! wallet's rules, applies the caps and approval limits, and resolves each
+//! sweep's destination and estimated fee. Never reserves a nonce, queues an
+//! approval or writes state, so it is safe to run next to a live sweeper.
+use crate::amount::{Amount, NATIVE_DECIMALS, U256};
+use crate::approvals::ApprovalQueue;
+use crate::destinations::{Destinations, group_sweeps};
+use crate::report::WalletFailure;
+use crate::rules_engine::RulesEngine;
+use crate::sweep_caps::{CapOutcome, SweepCaps};
+use crate::timeouts::{self, TimeoutKind};
+use crate::types::{
+    Address, Config, HotWalletConfig, RuleReport, SkippedSweep, SweepDecision, SweepSplit,
+};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// What the cycle would do with a planned sweep
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum PlannedAction {
+    /// Emit it
+    Send,
+    /// Hold it for approval, because of a cap or an approval limit
+    Hold { reason: String },
+    /// Drop it, because `clip` caps leave nothing to sweep
+    Skip { reason: String },
+}
+
+/// A sweep the next cycle would consider, after caps have been applied
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedSweep {
+    pub asset: String,
+    pub rule_type: String,
+    pub amount: Amount,
+    pub token_address: Option<Address>,
+    pub destination: Address,
+    pub split: Option<SweepSplit>,
+    /// Most the sweep can pay in gas, in native units; `None` when the
+    /// emitter sends nothing on chain
+    pub estimated_fee: Option<Amount>,
+    #[serde(flatten)]
+    pub action: PlannedAction,
+}
+
+/// What the next cycle would do for one wallet
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WalletPlan {
+    pub address: Address,
+    pub label: String,
+    /// Every rule evaluated, in config order
+    pub rules: Vec<RuleReport>,
+    /// Triggered rules that would not produce a sweep
+    pub skipped: Vec<SkippedSweep>,
+    pub sweeps: Vec<PlannedSweep>,
+    pub error: Option<WalletFailure>,
+}
+
+/// What the next cycle would do, wallet by wallet in config order
+#[derive(Debug, Clone, Serialize)]
+pub struct Plan {
+    pub created_at: DateTime<Utc>,
+    /// Sweeps that would be sent
+    pub sweep_count: usize,
+    pub wallets: Vec<WalletPlan>,
+}
+
+/// Plans sweep cycles from the same rules, caps and approval limits as the
+/// monitor, reading state but never changing it
+pub struct Planner {
+    rules_engine: Arc<RulesEngine>,
+    destinations: Destinations,
+    sweep_caps: Option<SweepCaps>,
+    approvals: Option<ApprovalQueue>,
+    /// Gas cost ceilings of native and token sweeps, in wei
+    fees: Option<(U256, U256)>,
+}
+
+impl Planner {
+    pub fn new(rules_engine: Arc<RulesEngine>, destinations: Destinations) -> Self {
+        Self {
+            rules_engine,
+            destinations,
+            sweep_caps: None,
+            approvals: None,
+            fees: None,
+        }
+    }
+
+    /// Check planned sweeps against `sweep_caps`
+    ///
+    /// Caps count the recorded sweep history only, not sweeps planned
+    /// earlier in the same plan.
+    pub fn with_sweep_caps(mut self, sweep_caps: SweepCaps) -> Self {
+        self.sweep_caps = Some(sweep_caps);
+        self
+    }
+
+    /// Mark planned sweeps above the approval limits as held
+    pub fn with_approvals(mut self, approvals: ApprovalQueue) -> Self {
+        self.approvals = Some(approvals);
+        self
+    }
+
+    /// Estimate each sweep's fee from the most a native or token sweep can
+    /// pay in gas, in wei
+    pub fn with_fees(mut self, native_fee: U256, token_fee: U256) -> Self {
+        self.fees = Some((native_fee, token_fee));
+        self
+    }
+
+    fn estimated_fee(&self, decision: &SweepDecision) -> Option<Amount> {
+        let (native_fee, token_fee) = self.fees?;
+        let fee = match decision.token_address {
+            Some(_) => token_fee,
+            None => native_fee,
+        };
+        Some(Amount::from_units(fee, NATIVE_DECIMALS))
+    }
+
+    /// What the caps and approval limits would do with the sweep made of
+    /// `legs`, judged as a whole
+    async fn action(
+        &self,
+        wallet: &Address,
+        legs: Vec<SweepDecision>,
+    ) -> Result<(Vec<SweepDecision>, PlannedAction)> {
+        let legs = match &self.sweep_caps {
+            Some(sweep_caps) => match sweep_caps.check(wallet, legs.clone()).await? {
+                CapOutcome::Allow(legs) => legs,
+                CapOutcome::Hold(reason) => return Ok((legs, PlannedAction::Hold { reason })),
+                CapOutcome::Skip(reason) => return Ok((legs, PlannedAction::Skip { reason })),
+            },
+            None => legs,
+        };
+        if let Some(approvals) = &self.approvals
+            && let Some(reason) = approvals.needs_approval(&legs[0])?
+        {
+            return Ok((legs, PlannedAction::Hold { reason }));
+        }
+        Ok((legs, PlannedAction::Send))
+    }
+
+    /// Plan one wallet, recording what it would do in `plan` as it goes
+    async fn plan_wallet(
+        &self,
+        wallet_config: &HotWalletConfig,
+        plan: &mut WalletPlan,
+    ) -> Result<()> {
+        let evaluation = self.rules_engine.evaluate_detailed(wallet_config).await?;
+        plan.rules = evaluation.rules;
+        plan.skipped = evaluation.skipped;
+
+        for legs in group_sweeps(evaluation.decisions) {
+            let (legs, action) = self.action(&wallet_config.address, legs).await?;
+            for decision in legs {
+                let destination = self.destinations.resolve(&decision)?;
+                let estimated_fee = self.estimated_fee(&decision);
+                plan.sweeps.push(PlannedSweep {
+                    asset: decision.asset,
+                    rule_type: decision.rule_type,
+                    amount: decision.amount,
+                    token_address: decision.token_address,
+                    destination,
+                    split: decision.split,
+                    estimated_fee,
+                    action: action.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Plan every wallet in config order. A wallet that fails or times out
+    /// does not stop the others.
+    pub async fn plan(&self, config: &Config) -> Plan {
+        let mut wallets = Vec::with_capacity(config.hot_wallets.len());
+        for wallet_config in &config.hot_wallets {
+            let mut plan = WalletPlan {
+                address: wallet_config.address.clone(),
+                label: wallet_config.label.clone(),
+                ..WalletPlan::default()
+            };
+            let result = timeouts::run(
+                TimeoutKind::Wallet,
+                config.timeouts.wallet_seconds,
+                self.plan_wallet(wallet_config, &mut plan),
+            )
+            .await;
+            if let Err(e) = result {
+                warn!("Error planning wallet {}: {}", wallet_config.address, e);
+                plan.error = Some(WalletFailure::from_error(&e));
+            }
+            wallets.push(plan);
+        }
+
+        let sweep_count = wallets
+            .iter()
+            .flat_map(|wallet| &wallet.sweeps)
+            .filter(|sweep| sweep.action == PlannedAction::Send)
+            .count();
+        info!(
+            "Plan complete: {} sweeps would be sent from {} wallets",
+            sweep_count,
+            wallets.len()
+        );
+        Plan {
+            created_at: Utc::now(),
+            sweep_count,
+            wallets,
+        }
+    }
+}
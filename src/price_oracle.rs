@@ -0,0 +1,132 @@
+//! Price Oracle
+//!
+//! Defines the `PriceOracle` abstraction used to convert USD thresholds into
+//! asset amounts, and the implementations that can be selected from config.
+//!
+//! Both implementations read prices in the same shape, keyed by asset symbol:
+//!
+//! ```json
+//! { "ETH": { "usd": "3012.55", "updated_at": "2026-10-17T09:00:00Z" } }
+//! ```
+use crate::amount::Amount;
+use crate::types::PriceOracleConfig;
+use anyhow::{Context, Result, anyhow};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::fs;
+
+/// USD price of one whole unit of an asset
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Price {
+    pub usd: Amount,
+    /// When the price was observed
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Price {
+    /// Whether the price is older than `max_age_seconds` at `now`
+    pub fn is_stale(&self, max_age_seconds: u64, now: DateTime<Utc>) -> bool {
+        u64::try_from(now.signed_duration_since(self.updated_at).num_seconds())
+            .is_ok_and(|age| age > max_age_seconds)
+    }
+}
+
+/// Source of USD prices queried by the rules engine
+#[async_trait]
+pub trait PriceOracle: Send + Sync {
+    /// Short name used to identify the oracle in logs and errors
+    fn name(&self) -> &str;
+
+    /// Latest known price of `asset` (the rule's asset symbol, e.g. "ETH")
+    async fn price(&self, asset: &str) -> Result<Price>;
+}
+
+/// Build the price oracle selected in config
+pub fn from_config(config: &PriceOracleConfig) -> Result<Arc<dyn PriceOracle>> {
+    match config {
+        PriceOracleConfig::Static { path, .. } => {
+            Ok(Arc::new(StaticPriceOracle::new(path.clone())))
+        }
+        PriceOracleConfig::Http { url, .. } => Ok(Arc::new(HttpPriceOracle::new(url.clone())?)),
+    }
+}
+
+/// Prices read from a JSON file.
+///
+/// The file is re-read on every lookup, so it can be updated while the
+/// service runs.
+pub struct StaticPriceOracle {
+    path: PathBuf,
+}
+
+impl StaticPriceOracle {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+#[async_trait]
+impl PriceOracle for StaticPriceOracle {
+    fn name(&self) -> &str {
+        "static"
+    }
+
+    async fn price(&self, asset: &str) -> Result<Price> {
+        let content = fs::read_to_string(&self.path)
+            .await
+            .with_context(|| format!("Failed to read price file {}", self.path.display()))?;
+        let prices: HashMap<String, Price> = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse price file {}", self.path.display()))?;
+        prices
+            .get(asset)
+            .copied()
+            .ok_or_else(|| anyhow!("No price for {} in {}", asset, self.path.display()))
+    }
+}
+
+/// Prices served over HTTP: `GET {url}/{asset}` returns one price object
+pub struct HttpPriceOracle {
+    url: String,
+    http: reqwest::Client,
+}
+
+impl HttpPriceOracle {
+    pub fn new(url: impl Into<String>) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .context("Failed to build HTTP client")?;
+        Ok(Self {
+            url: url.into(),
+            http,
+        })
+    }
+}
+
+#[async_trait]
+impl PriceOracle for HttpPriceOracle {
+    fn name(&self) -> &str {
+        "http"
+    }
+
+    async fn price(&self, asset: &str) -> Result<Price> {
+        let url = format!("{}/{}", self.url.trim_end_matches('/'), asset);
+        let response = self
+            .http
+            .get(&url)
+            .send()
+            .await
+            .with_context(|| format!("Price request to {} failed", url))?
+            .error_for_status()
+            .with_context(|| format!("Price request to {} failed", url))?;
+        response
+            .json()
+            .await
+            .with_context(|| format!("Invalid price response from {}", url))
+    }
+}
@@ -0,0 +1,96 @@
+//! Cycle Report
+//!
+//! Structured account of a sweep cycle: every wallet checked, every rule with
+//! the balance and threshold it saw, every transaction emitted and every
+//! failure. Serializes to JSON for the `once --json` command and external
+//! tooling.
+use crate::timeouts::TimedOut;
+use crate::types::{Address, RuleReport, SkippedSweep, SubmittedTx};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fmt;
+
+/// Why a wallet check did not complete
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", content = "detail", rename_all = "snake_case")]
+pub enum WalletFailure {
+    /// Cut short by the balance, wallet or cycle timeout
+    TimedOut(TimedOut),
+    /// Any other error, such as a failing balance source or sweep
+    Error(String),
+}
+
+impl WalletFailure {
+    pub fn from_error(error: &anyhow::Error) -> Self {
+        match TimedOut::find(error) {
+            Some(timed_out) => WalletFailure::TimedOut(timed_out),
+            None => WalletFailure::Error(format!("{:#}", error)),
+        }
+    }
+}
+
+impl fmt::Display for WalletFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WalletFailure::TimedOut(timed_out) => write!(f, "timed out: {}", timed_out),
+            WalletFailure::Error(message) => f.write_str(message),
+        }
+    }
+}
+
+/// What happened to one wallet during a cycle. A failed check keeps what it
+/// did before failing.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WalletReport {
+    pub address: Address,
+    pub label: String,
+    /// Every rule evaluated, in config order
+    pub rules: Vec<RuleReport>,
+    /// Sweeps that were dropped, by a rule, a cap or a changed balance
+    pub skipped: Vec<SkippedSweep>,
+    /// Sweeps held for approval
+    pub held: Vec<SkippedSweep>,
+    /// Transactions handed to the emitter, approved sweeps included
+    pub transactions: Vec<SubmittedTx>,
+    pub error: Option<WalletFailure>,
+}
+
+impl WalletReport {
+    pub fn new(address: Address, label: String) -> Self {
+        Self {
+            address,
+            label,
+            ..Self::default()
+        }
+    }
+
+    pub fn sweep_count(&self) -> usize {
+        self.transactions.len()
+    }
+}
+
+/// Everything a sweep cycle did, wallet by wallet in config order
+#[derive(Debug, Clone, Serialize)]
+pub struct CycleReport {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub sweep_count: usize,
+    /// Wallets whose check failed or timed out
+    pub failed_wallets: usize,
+    pub wallets: Vec<WalletReport>,
+}
+
+impl CycleReport {
+    pub fn new(started_at: DateTime<Utc>, wallets: Vec<WalletReport>) -> Self {
+        Self {
+            started_at,
+            finished_at: Utc::now(),
+            sweep_count: wallets.iter().map(WalletReport::sweep_count).sum(),
+            failed_wallets: wallets
+                .iter()
+                .filter(|wallet| wallet.error.is_some())
+                .count(),
+            wallets,
+        }
+    }
+}
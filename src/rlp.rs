@@ -0,0 +1,67 @@
+//! Recursive Length Prefix encoding
+//!
+//! Only the encoding side is needed to build Ethereum transactions.
+use crate::amount::U256;
+
+/// An RLP item: a byte string or a list of items
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RlpItem {
+    Bytes(Vec<u8>),
+    List(Vec<RlpItem>),
+}
+
+impl RlpItem {
+    pub fn bytes(bytes: &[u8]) -> Self {
+        RlpItem::Bytes(bytes.to_vec())
+    }
+
+    /// Integer as a big-endian byte string without leading zeros
+    pub fn uint(value: U256) -> Self {
+        RlpItem::Bytes(value.to_be_bytes_trimmed_vec())
+    }
+
+    pub fn u64(value: u64) -> Self {
+        Self::uint(U256::from(value))
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode_into(&mut out);
+        out
+    }
+
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        match self {
+            RlpItem::Bytes(bytes) if bytes.len() == 1 && bytes[0] < 0x80 => out.push(bytes[0]),
+            RlpItem::Bytes(bytes) => {
+                encode_header(0x80, bytes.len(), out);
+                out.extend_from_slice(bytes);
+            }
+            RlpItem::List(items) => {
+                let mut payload = Vec::new();
+                for item in items {
+                    item.encode_into(&mut payload);
+                }
+                encode_header(0xc0, payload.len(), out);
+                out.extend_from_slice(&payload);
+            }
+        }
+    }
+}
+
+/// Write the prefix for a payload of `len` bytes (`offset` is 0x80 for
+/// strings, 0xc0 for lists)
+fn encode_header(offset: u8, len: usize, out: &mut Vec<u8>) {
+    if len < 56 {
+        out.push(offset + len as u8);
+    } else {
+        let len_bytes = len.to_be_bytes();
+        let first = len_bytes
+            .iter()
+            .position(|b| *b != 0)
+            .unwrap_or(len_bytes.len() - 1);
+        let len_bytes = &len_bytes[first..];
+        out.push(offset + 55 + len_bytes.len() as u8);
+        out.extend_from_slice(len_bytes);
+    }
+}
@@ -0,0 +1,281 @@
+//! Ethereum JSON-RPC client
+//!
+//! Minimal JSON-RPC 2.0 client over HTTP plus helpers for the hex encodings
+//! used by Ethereum nodes.
+use crate::amount::U256;
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Block tag used for state queries
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockTag {
+    #[default]
+    Latest,
+    Safe,
+    Finalized,
+    Pending,
+}
+
+impl BlockTag {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BlockTag::Latest => "latest",
+            BlockTag::Safe => "safe",
+            BlockTag::Finalized => "finalized",
+            BlockTag::Pending => "pending",
+        }
+    }
+}
+
+/// Error object returned by the node
+#[derive(Debug, Clone, Deserialize)]
+pub struct RpcErrorObject {
+    pub code: i64,
+    pub message: String,
+    #[serde(default)]
+    pub data: Option<Value>,
+}
+
+impl std::fmt::Display for RpcErrorObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RPC error {}: {}", self.code, self.message)?;
+        if let Some(data) = &self.data {
+            write!(f, " ({})", data)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RpcErrorObject {}
+
+impl RpcErrorObject {
+    /// Whether the node may answer the same request on a later attempt:
+    /// rate limits and node-side timeouts
+    pub fn is_transient(&self) -> bool {
+        let message = self.message.to_ascii_lowercase();
+        matches!(self.code, -32005 | 429)
+            || ["rate limit", "too many requests", "timeout", "timed out"]
+                .iter()
+                .any(|pattern| message.contains(pattern))
+    }
+}
+
+/// Non-success HTTP status returned by the node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HttpStatusError {
+    pub status: u16,
+}
+
+impl std::fmt::Display for HttpStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HTTP {}", self.status)
+    }
+}
+
+impl std::error::Error for HttpStatusError {}
+
+impl HttpStatusError {
+    /// Rate limits and server errors, which may clear up on their own
+    pub fn is_transient(&self) -> bool {
+        self.status == 429 || self.status >= 500
+    }
+}
+
+/// JSON-RPC 2.0 client over HTTP
+pub struct JsonRpcClient {
+    url: String,
+    http: reqwest::Client,
+    next_id: AtomicU64,
+}
+
+impl JsonRpcClient {
+    pub fn new(url: impl Into<String>) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .context("Failed to build HTTP client")?;
+
+        Ok(Self {
+            url: url.into(),
+            http,
+            next_id: AtomicU64::new(1),
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Send a request and return its `result` field
+    pub async fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        });
+
+        let response = self
+            .http
+            .post(&self.url)
+            .json(&request)
+            .send()
+            .await
+            .with_context(|| format!("{} request to {} failed", method, self.url))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error = HttpStatusError {
+                status: status.as_u16(),
+            };
+            return Err(
+                anyhow!(error).context(format!("{} request to {} failed", method, self.url))
+            );
+        }
+
+        let mut body: Value = response
+            .json()
+            .await
+            .with_context(|| format!("Invalid JSON-RPC response to {}", method))?;
+
+        if let Some(error) = body.get("error").filter(|error| !error.is_null()) {
+            let error: RpcErrorObject = serde_json::from_value(error.clone())
+                .with_context(|| format!("Invalid error object in response to {}", method))?;
+            return Err(anyhow!(error).context(format!("{} failed", method)));
+        }
+
+        // A null result is meaningful (e.g. a receipt that does not exist yet),
+        // so only a missing field is an error
+        body.get_mut("result")
+            .map(Value::take)
+            .ok_or_else(|| anyhow!("{} response has neither result nor error", method))
+    }
+
+    /// Call a method whose result is a hex quantity
+    pub async fn call_quantity(&self, method: &str, params: Value) -> Result<U256> {
+        let result = self.call(method, params).await?;
+        let hex = result
+            .as_str()
+            .ok_or_else(|| anyhow!("{} returned a non-string result: {}", method, result))?;
+        decode_quantity(hex).with_context(|| format!("Invalid quantity returned by {}", method))
+    }
+}
+
+/// Receipt of a mined transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionReceipt {
+    pub transaction_hash: String,
+    pub block_number: u64,
+    pub gas_used: u64,
+    /// `true` when execution succeeded, `false` when it reverted
+    pub success: bool,
+}
+
+impl JsonRpcClient {
+    /// Broadcast a signed transaction and return the hash reported by the node
+    pub async fn send_raw_transaction(&self, raw_hex: &str) -> Result<String> {
+        let result = self
+            .call("eth_sendRawTransaction", json!([raw_hex]))
+            .await?;
+        result.as_str().map(str::to_string).ok_or_else(|| {
+            anyhow!(
+                "eth_sendRawTransaction returned a non-string result: {}",
+                result
+            )
+        })
+    }
+
+    /// Receipt for `hash`, `None` while the transaction is not mined
+    pub async fn get_transaction_receipt(&self, hash: &str) -> Result<Option<TransactionReceipt>> {
+        let result = self
+            .call("eth_getTransactionReceipt", json!([hash]))
+            .await?;
+        if result.is_null() {
+            return Ok(None);
+        }
+
+        let field = |name: &str| {
+            result[name]
+                .as_str()
+                .ok_or_else(|| anyhow!("Receipt for {} is missing {}", hash, name))
+        };
+        Ok(Some(TransactionReceipt {
+            transaction_hash: field("transactionHash")?.to_string(),
+            block_number: decode_u64(field("blockNumber")?)?,
+            gas_used: decode_u64(field("gasUsed")?)?,
+            success: decode_u64(field("status")?)? == 1,
+        }))
+    }
+
+    /// Native balance of `address` as of `block`, in wei
+    pub async fn get_balance(&self, address: &str, block: BlockTag) -> Result<U256> {
+        self.call_quantity("eth_getBalance", json!([address, block.as_str()]))
+            .await
+    }
+
+    /// Number of transactions sent from `address` as of `block`, i.e. the
+    /// next nonce the node expects
+    pub async fn get_transaction_count(&self, address: &str, block: BlockTag) -> Result<u64> {
+        let count = self
+            .call_quantity("eth_getTransactionCount", json!([address, block.as_str()]))
+            .await?;
+        u64::try_from(count)
+            .map_err(|_| anyhow!("transaction count {} does not fit in 64 bits", count))
+    }
+
+    /// Whether the node still knows about `hash` (pending or mined)
+    pub async fn transaction_known(&self, hash: &str) -> Result<bool> {
+        let result = self.call("eth_getTransactionByHash", json!([hash])).await?;
+        Ok(!result.is_null())
+    }
+}
+
+/// Decode a hex quantity that must fit in 64 bits (block numbers, gas, nonces)
+pub fn decode_u64(hex: &str) -> Result<u64> {
+    let value = decode_quantity(hex)?;
+    u64::try_from(value).map_err(|_| anyhow!("quantity {} does not fit in 64 bits", hex))
+}
+
+/// Decode a hex quantity such as `0x1bc16d674ec80000`
+pub fn decode_quantity(hex: &str) -> Result<U256> {
+    let digits = hex
+        .strip_prefix("0x")
+        .ok_or_else(|| anyhow!("quantity {:?} is missing the 0x prefix", hex))?;
+    if digits.is_empty() || digits.len() > 64 {
+        bail!("quantity {:?} must have between 1 and 64 hex digits", hex);
+    }
+    U256::from_str_radix(digits, 16).map_err(|_| anyhow!("quantity {:?} is not valid hex", hex))
+}
+
+/// Decode a 32-byte ABI word returned by `eth_call` into an integer
+pub fn decode_uint_word(hex: &str) -> Result<U256> {
+    let digits = hex
+        .strip_prefix("0x")
+        .ok_or_else(|| anyhow!("call result {:?} is missing the 0x prefix", hex))?;
+    if digits.len() != 64 {
+        bail!("call result has {} hex digits, expected 64", digits.len());
+    }
+    U256::from_str_radix(digits, 16).map_err(|_| anyhow!("call result {:?} is not valid hex", hex))
+}
+
+/// Parse a `0x`-prefixed 20-byte address
+pub fn parse_address(address: &str) -> Result<[u8; 20]> {
+    let digits = address
+        .strip_prefix("0x")
+        .ok_or_else(|| anyhow!("address {:?} is missing the 0x prefix", address))?;
+    let bytes = hex::decode(digits).with_context(|| format!("address {:?} is not hex", address))?;
+    bytes
+        .try_into()
+        .map_err(|_| anyhow!("address {:?} is not 20 bytes long", address))
+}
+
+/// Calldata for ERC-20 `balanceOf(address)`
+pub fn encode_balance_of(owner: &str) -> Result<String> {
+    let owner = parse_address(owner)?;
+    Ok(format!("0x70a08231{:0>64}", hex::encode(owner)))
+}
@@ -1,90 +1,533 @@
 //! Rules Engine
 //!
 //! Evaluates sweep rules against wallet balances to determine if a sweep should be triggered.
-use crate::balance_checker::DummyBalanceChecker;
-use crate::types::{HotWalletConfig, SweepDecision, SweepRule};
-use anyhow::Result;
+use crate::amount::scale_factor;
+use crate::amount::{Amount, NATIVE_DECIMALS, U256};
+use crate::balance_checker::BalanceSource;
+use crate::destinations::split_amount;
+use crate::price_oracle::PriceOracle;
+use crate::state_manager::StateManager;
+use crate::timeouts::{self, TimeoutKind};
+use crate::types::{
+    Address, Condition, HotWalletConfig, RuleOutcome, RuleReport, SkippedSweep, SweepDecision,
+    SweepRule, SweepSplit,
+};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Arc;
 use tracing::info;
 
+/// Estimated fee deducted from native sweeps
+#[derive(Debug, Clone, Copy)]
+struct NativeFee {
+    /// Most a native sweep can pay, in the native asset's base units
+    fee: U256,
+    /// Skip the sweep when the fee is above this share of its value
+    max_fee_percent: u64,
+}
+
+/// Oracle used to convert USD thresholds
+struct Prices {
+    oracle: Arc<dyn PriceOracle>,
+    /// Older prices make the rule refuse to fire
+    max_age_seconds: u64,
+}
+
+/// Decisions for one wallet, plus the triggered rules that were skipped
+#[derive(Debug, Clone, Default)]
+pub struct Evaluation {
+    pub decisions: Vec<SweepDecision>,
+    pub skipped: Vec<SkippedSweep>,
+    /// Every rule evaluated, in config order
+    pub rules: Vec<RuleReport>,
+}
 
 pub struct RulesEngine {
-    balance_checker: DummyBalanceChecker,
+    balance_checker: Arc<dyn BalanceSource>,
+    native_fee: Option<NativeFee>,
+    state_manager: Option<Arc<StateManager>>,
+    prices: Option<Prices>,
+    balance_timeout_seconds: Option<u64>,
 }
 
 impl RulesEngine {
-    pub fn new(balance_checker: DummyBalanceChecker) -> Self {
-        Self { balance_checker }
+    pub fn new(balance_checker: Arc<dyn BalanceSource>) -> Self {
+        Self {
+            balance_checker,
+            native_fee: None,
+            state_manager: None,
+            prices: None,
+            balance_timeout_seconds: None,
+        }
+    }
+
+    /// Fail balance queries that take longer than `seconds`
+    pub fn with_balance_timeout(mut self, seconds: u64) -> Self {
+        self.balance_timeout_seconds = Some(seconds);
+        self
+    }
+
+    /// Deduct `fee` (in the native asset's base units) from every native sweep,
+    /// skipping sweeps where it is above `max_fee_percent` of the value
+    pub fn with_native_fee(mut self, fee: U256, max_fee_percent: u64) -> Self {
+        self.native_fee = Some(NativeFee {
+            fee,
+            max_fee_percent,
+        });
+        self
+    }
+
+    /// Read last sweep times from `state_manager` for `last_sweep_older_than`
+    /// conditions and cooldowns
+    pub fn with_state_manager(mut self, state_manager: Arc<StateManager>) -> Self {
+        self.state_manager = Some(state_manager);
+        self
+    }
+
+    /// Convert USD thresholds with `oracle`, refusing to fire on prices older
+    /// than `max_age_seconds`
+    pub fn with_price_oracle(mut self, oracle: Arc<dyn PriceOracle>, max_age_seconds: u64) -> Self {
+        self.prices = Some(Prices {
+            oracle,
+            max_age_seconds,
+        });
+        self
+    }
+
+    /// `usd` expressed in base units of `asset`, or why no fresh price is
+    /// available
+    async fn usd_threshold(
+        &self,
+        asset: &str,
+        usd: &Amount,
+        decimals: u8,
+    ) -> Result<std::result::Result<Amount, String>> {
+        let prices = self
+            .prices
+            .as_ref()
+            .context("USD thresholds need a price oracle")?;
+        let price = match prices.oracle.price(asset).await {
+            Ok(price) => price,
+            Err(e) => {
+                return Ok(Err(format!(
+                    "{} price oracle has no price for {}: {:#}",
+                    prices.oracle.name(),
+                    asset,
+                    e
+                )));
+            }
+        };
+        if price.is_stale(prices.max_age_seconds, Utc::now()) {
+            return Ok(Err(format!(
+                "price of {} is stale (updated {}, more than {}s ago)",
+                asset, price.updated_at, prices.max_age_seconds
+            )));
+        }
+        if price.usd.is_zero() {
+            return Ok(Err(format!("price of {} is zero", asset)));
+        }
+
+        // usd / price, rounded down: a whole-unit balance above the rounded
+        // threshold is also above the exact one
+        let units = usd
+            .units()
+            .checked_mul(scale_factor(price.usd.decimals()))
+            .and_then(|units| units.checked_mul(scale_factor(decimals)))
+            .context("USD threshold is too large")?
+            / price
+                .usd
+                .units()
+                .checked_mul(scale_factor(usd.decimals()))
+                .context("Price is too large")?;
+        info!(
+            "USD threshold for {}: {} USD at {} USD",
+            asset, usd, price.usd
+        );
+        Ok(Ok(Amount::from_units(units, decimals)))
+    }
+
+    /// Balance of the native asset (`token_address` is `None`) or a token,
+    /// fetched at most once per `cache`
+    async fn balance(
+        &self,
+        cache: &mut HashMap<Option<Address>, U256>,
+        address: &Address,
+        token_address: Option<&Address>,
+        asset: &str,
+    ) -> Result<U256> {
+        let key = token_address.cloned();
+        if let Some(balance) = cache.get(&key) {
+            return Ok(*balance);
+        }
+
+        let query = async {
+            match token_address {
+                None => self.balance_checker.check_native_balance(address).await,
+                Some(token_address) => {
+                    self.balance_checker
+                        .check_token_balance(address, token_address)
+                        .await
+                }
+            }
+        };
+        let balance = timeouts::run(TimeoutKind::Balance, self.balance_timeout_seconds, query)
+            .await
+            .with_context(|| {
+                format!(
+                    "{} balance source failed to fetch {} balance",
+                    self.balance_checker.name(),
+                    asset
+                )
+            })?;
+        cache.insert(key, balance);
+        Ok(balance)
+    }
+
+    /// Current balance of `asset` (native when `token_address` is `None`) held
+    /// by `address`, in base units
+    pub async fn current_balance(
+        &self,
+        address: &Address,
+        token_address: Option<&Address>,
+        asset: &str,
+    ) -> Result<U256> {
+        self.balance(&mut HashMap::new(), address, token_address, asset)
+            .await
+    }
+
+    /// Most a native sweep pays in gas on top of its amount, in wei; zero
+    /// unless fees are deducted
+    pub fn native_sweep_fee(&self) -> U256 {
+        self.native_fee
+            .map_or(U256::ZERO, |native_fee| native_fee.fee)
+    }
+
+    /// When `address` last swept `asset`, from the sweep history that
+    /// `setting` needs
+    async fn last_sweep(
+        &self,
+        address: &Address,
+        asset: &str,
+        setting: &str,
+    ) -> Result<Option<DateTime<Utc>>> {
+        let state_manager = self
+            .state_manager
+            .as_ref()
+            .with_context(|| format!("{} needs the sweep history", setting))?;
+        Ok(state_manager.last_asset_sweep(address, asset).await)
     }
 
     /// Evaluate all rules for a wallet and return all sweep decisions that trigger
     pub async fn evaluate(&self, wallet_config: &HotWalletConfig) -> Result<Vec<SweepDecision>> {
+        Ok(self.evaluate_detailed(wallet_config).await?.decisions)
+    }
+
+    /// Evaluate all rules for a wallet, keeping the reason for every triggered
+    /// rule that did not become a sweep
+    pub async fn evaluate_detailed(&self, wallet_config: &HotWalletConfig) -> Result<Evaluation> {
+        let mut evaluation = Evaluation::default();
+        self.evaluate_into(wallet_config, &mut evaluation).await?;
+        Ok(evaluation)
+    }
+
+    /// Evaluate all rules for a wallet into `evaluation`, one rule at a time.
+    ///
+    /// Rules evaluated before an error, or before the future is dropped at a
+    /// timeout, stay in `evaluation`.
+    pub async fn evaluate_into(
+        &self,
+        wallet_config: &HotWalletConfig,
+        evaluation: &mut Evaluation,
+    ) -> Result<()> {
         info!("Evaluating rules for wallet {}", &wallet_config.address);
-        
-        let mut decisions = Vec::new();
-        
+
+        // Each balance is fetched once per evaluation, whichever rules read it
+        let mut balances = HashMap::new();
+
         for rule in &wallet_config.rules {
-            match rule {
-                SweepRule::NativeBalance { threshold, asset } => {
-                    let balance = self
-                        .balance_checker
-                        .check_native_balance(&wallet_config.address)
-                        .await?;
+            let report = self
+                .evaluate_rule(wallet_config, rule, &mut balances, evaluation)
+                .await?;
+            evaluation.rules.push(report);
+        }
 
-                    let threshold_value: f64 = threshold.parse().unwrap_or(0.0);
+        if evaluation.decisions.is_empty() {
+            info!("No rules triggered, skipping");
+        } else {
+            info!("No of rules triggered :{}", evaluation.decisions.len());
+        }
 
-                    info!(
-                        "Balance check: {}={:.6} (threshold={})",
-                        asset, balance, threshold
-                    );
-
-                    if balance > threshold_value {
-                        info!("Rule triggered: native_balance");
-                        decisions.push(SweepDecision {
-                            amount: balance.to_string(),
-                            asset: asset.clone(),
-                            rule_type: "native_balance".to_string(),
-                            token_address: None,
-                        });
-                    }
-                }
+        Ok(())
+    }
 
-                SweepRule::TokenBalance {
-                    threshold,
-                    token_address,
-                    asset,
-                } => {
-                    let balance = self
-                        .balance_checker
-                        .check_token_balance(&wallet_config.address, token_address)
-                        .await?;
+    /// Evaluate one rule, adding its sweeps and skips to `evaluation`
+    async fn evaluate_rule(
+        &self,
+        wallet_config: &HotWalletConfig,
+        rule: &SweepRule,
+        balances: &mut HashMap<Option<Address>, U256>,
+        evaluation: &mut Evaluation,
+    ) -> Result<RuleReport> {
+        let asset = rule.asset();
+        let balance = self
+            .balance(
+                balances,
+                &wallet_config.address,
+                rule.token_address(),
+                asset,
+            )
+            .await?;
+        let balance = Amount::from_units(balance, rule.decimals());
+        let mut report = RuleReport {
+            rule_type: rule.rule_type().to_string(),
+            asset: asset.to_string(),
+            balance,
+            threshold: None,
+            outcome: RuleOutcome::NotTriggered,
+        };
 
-                    let threshold_value: u64 = threshold.parse().unwrap_or(0);
+        let skipped = &mut evaluation.skipped;
+        let mut skip = |reason: String| {
+            info!("Rule triggered but skipped: {}", reason);
+            skipped.push(SkippedSweep {
+                asset: asset.to_string(),
+                rule_type: rule.rule_type().to_string(),
+                reason: reason.clone(),
+            });
+            RuleOutcome::Skipped { reason }
+        };
 
-                    info!(
+        let triggered = match rule {
+            SweepRule::Composite { condition, .. } => {
+                let mut needed = Vec::new();
+                condition.balances_needed(&mut needed);
+                for token_address in &needed {
+                    let label = token_address.as_deref().unwrap_or("native");
+                    self.balance(
+                        balances,
+                        &wallet_config.address,
+                        token_address.as_ref(),
+                        label,
+                    )
+                    .await?;
+                }
+                // Only sweeps of this rule's asset count
+                let last_sweep = if condition.needs_last_sweep() {
+                    self.last_sweep(&wallet_config.address, asset, "last_sweep_older_than")
+                        .await?
+                } else {
+                    None
+                };
+
+                let holds = condition_holds(condition, balances, last_sweep, Utc::now())?;
+                info!(
+                    "Balance check: {}={} (condition holds: {})",
+                    asset, balance, holds
+                );
+                holds
+            }
+            _ => {
+                let threshold = match rule.usd_threshold() {
+                    Some(usd) => match self.usd_threshold(asset, usd, rule.decimals()).await? {
+                        Ok(threshold) => threshold,
+                        Err(reason) => {
+                            report.outcome = skip(reason);
+                            return Ok(report);
+                        }
+                    },
+                    None => rule.threshold_units()?.context("Rule has no threshold")?,
+                };
+                report.threshold = Some(threshold);
+                match rule.token_address() {
+                    None => info!(
+                        "Balance check: {}={} (threshold={})",
+                        asset, balance, threshold
+                    ),
+                    Some(token_address) => info!(
                         "Balance check: {}={} (threshold={}, token={})",
                         asset, balance, threshold, token_address
-                    );
-
-                    if balance > threshold_value {
-                        info!("Rule triggered: token_balance");
-                        decisions.push(SweepDecision {
-                            amount: balance.to_string(),
-                            asset: asset.clone(),
-                            rule_type: "token_balance".to_string(),
-                            token_address: Some(token_address.clone()),
-                        });
-                    }
+                    ),
                 }
+                balance > threshold
             }
+        };
+        if !triggered {
+            return Ok(report);
         }
 
-        if decisions.is_empty() {
-            info!("No rules triggered, skipping");
-        } else {
-            info!("No of rules triggered :{}", decisions.len());
+        let cooldown = rule.cooldown_seconds().or(wallet_config.cooldown_seconds);
+        if let Some(cooldown) = cooldown
+            && let Some(last_sweep) = self
+                .last_sweep(&wallet_config.address, asset, "cooldown_seconds")
+                .await?
+            && elapsed_seconds(last_sweep, Utc::now()) < cooldown
+        {
+            report.outcome = skip(format!(
+                "last {} sweep at {} is within cooldown_seconds {}",
+                asset, last_sweep, cooldown
+            ));
+            return Ok(report);
+        }
+
+        // Excess rules leave the retained float behind
+        let amount = match rule.retain_units()? {
+            Some(retain) => match balance.checked_sub(&retain) {
+                Some(amount) if !amount.is_zero() => amount,
+                _ => {
+                    report.outcome = skip(format!(
+                        "balance {} does not exceed retain_amount {}",
+                        balance, retain
+                    ));
+                    return Ok(report);
+                }
+            },
+            None => balance,
+        };
+
+        // A split sweep is one transaction per destination
+        let legs = match rule.split_destinations() {
+            [] => vec![(rule.destination().cloned(), None, amount)],
+            split => split
+                .iter()
+                .zip(split_amount(amount, split))
+                .map(|(leg, share)| {
+                    let split = SweepSplit {
+                        total: amount,
+                        bps: leg.bps,
+                        first: false,
+                    };
+                    (Some(leg.address.clone()), Some(split), share)
+                })
+                .collect(),
+        };
+
+        info!("Rule triggered: {}", rule.rule_type());
+        let mut sweeps = 0;
+        let mut last_skip = None;
+        for (destination, split, amount) in legs {
+            if let Some(split) = split
+                && amount.is_zero()
+            {
+                last_skip = Some(skip(format!(
+                    "{} bps of {} for {} rounds down to nothing",
+                    split.bps,
+                    split.total,
+                    destination.as_deref().unwrap_or_default()
+                )));
+                continue;
+            }
+            // Every transaction pays its own fee
+            let amount = match (rule.token_address(), self.native_fee) {
+                (None, Some(native_fee)) => match deduct_fee(amount, native_fee) {
+                    Ok(amount) => amount,
+                    Err(reason) => {
+                        last_skip = Some(skip(reason));
+                        continue;
+                    }
+                },
+                _ => amount,
+            };
+
+            evaluation.decisions.push(SweepDecision {
+                amount,
+                asset: asset.to_string(),
+                rule_type: rule.rule_type().to_string(),
+                token_address: rule.token_address().cloned(),
+                destination,
+                split: split.map(|split| SweepSplit {
+                    first: sweeps == 0,
+                    ..split
+                }),
+            });
+            sweeps += 1;
         }
-        
-        Ok(decisions)
+
+        report.outcome = match last_skip {
+            Some(outcome) if sweeps == 0 => outcome,
+            _ => RuleOutcome::Triggered { sweeps },
+        };
+        Ok(report)
     }
 }
 
+/// Whether `condition` holds given the wallet's balances (base units keyed by
+/// token address, `None` for native) and its last sweep
+fn condition_holds(
+    condition: &Condition,
+    balances: &HashMap<Option<Address>, U256>,
+    last_sweep: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Result<bool> {
+    Ok(match condition {
+        Condition::All(conditions) => {
+            for condition in conditions {
+                if !condition_holds(condition, balances, last_sweep, now)? {
+                    return Ok(false);
+                }
+            }
+            true
+        }
+        Condition::Any(conditions) => {
+            for condition in conditions {
+                if condition_holds(condition, balances, last_sweep, now)? {
+                    return Ok(true);
+                }
+            }
+            false
+        }
+        Condition::Not(condition) => !condition_holds(condition, balances, last_sweep, now)?,
+        Condition::Balance {
+            token_address,
+            decimals,
+            above,
+            below,
+        } => {
+            let units = balances
+                .get(token_address)
+                .context("Balance for condition was not fetched")?;
+            let balance = Amount::from_units(*units, *decimals);
+            above.is_none_or(|above| balance > above) && below.is_none_or(|below| balance < below)
+        }
+        Condition::LastSweepOlderThan { seconds } => match last_sweep {
+            None => true,
+            Some(last_sweep) => elapsed_seconds(last_sweep, now) > *seconds,
+        },
+    })
+}
+
+/// Whole seconds from `since` to `now`, zero if `since` is in the future
+fn elapsed_seconds(since: DateTime<Utc>, now: DateTime<Utc>) -> u64 {
+    u64::try_from(now.signed_duration_since(since).num_seconds()).unwrap_or(0)
+}
+
+/// Amount left to sweep once the fee is paid, or why the sweep is not worth it
+fn deduct_fee(amount: Amount, native_fee: NativeFee) -> std::result::Result<Amount, String> {
+    // The fee is in wei, whatever decimals the rule gives its asset
+    let fee = Amount::from_units(native_fee.fee, NATIVE_DECIMALS);
+    let fee_share = Amount::from_units(
+        native_fee.fee.saturating_mul(U256::from(100u8)),
+        NATIVE_DECIMALS,
+    );
+    let allowed_share = Amount::from_units(
+        amount
+            .units()
+            .saturating_mul(U256::from(native_fee.max_fee_percent)),
+        amount.decimals(),
+    );
+    if fee_share > allowed_share {
+        return Err(format!(
+            "estimated fee {} is more than {}% of the {} balance to sweep",
+            fee, native_fee.max_fee_percent, amount
+        ));
+    }
+    amount
+        .checked_sub(&fee)
+        .filter(|remaining| !remaining.is_zero())
+        .ok_or_else(|| {
+            format!(
+                "estimated fee {} leaves nothing of {} to sweep",
+                fee, amount
+            )
+        })
+}
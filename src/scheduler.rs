@@ -3,6 +3,8 @@
 //! Orchestrates sweep cycles, either once or continuously on a schedule.
 
 use crate::monitor::WalletMonitor;
+use crate::nonce_reconciler::NonceReconciler;
+use crate::report::CycleReport;
 use crate::types::Config;
 use anyhow::Result;
 use std::sync::Arc;
@@ -13,20 +15,39 @@ use tracing::info;
 pub struct Scheduler {
     monitor: Arc<WalletMonitor>,
     config: Config,
+    nonce_reconciler: Option<Arc<NonceReconciler>>,
 }
 
 impl Scheduler {
     pub fn new(monitor: Arc<WalletMonitor>, config: Config) -> Self {
-        Self { monitor, config }
+        Self {
+            monitor,
+            config,
+            nonce_reconciler: None,
+        }
+    }
+
+    /// Reconcile nonces with the chain before each cycle
+    pub fn with_nonce_reconciler(mut self, reconciler: Arc<NonceReconciler>) -> Self {
+        self.nonce_reconciler = Some(reconciler);
+        self
     }
 
 
-    pub async fn run_once(&self) -> Result<usize> {
+    /// Run one sweep cycle and report what it did
+    pub async fn run_once(&self) -> Result<CycleReport> {
         info!("Starting sweep cycle");
-        let sweep_count = self.monitor.check_all_wallets(&self.config).await?;
-        info!("Sweep cycle complete: {} sweeps executed", sweep_count);
+        if let Some(reconciler) = &self.nonce_reconciler {
+            let discrepancies = reconciler.reconcile_all(&self.config).await;
+            info!("Nonce reconciliation: {} discrepancies", discrepancies.len());
+        }
+        let report = self.monitor.run_cycle(&self.config).await?;
+        info!(
+            "Sweep cycle complete: {} sweeps executed",
+            report.sweep_count
+        );
 
-        Ok(sweep_count)
+        Ok(report)
     }
 
     pub async fn run_continuous(&self) -> Result<()> {
@@ -39,8 +60,8 @@ impl Scheduler {
 
         loop {
             match self.run_once().await {
-                Ok(count) => {
-                    info!("Executed {} sweeps", count);
+                Ok(report) => {
+                    info!("Executed {} sweeps", report.sweep_count);
                 }
                 Err(e) => {
                     tracing::error!("Error in sweep cycle: {}", e);
@@ -2,14 +2,27 @@
 //!
 //! This module implements atomic nonce management with persistent state.
 
-use crate::types::{Address, ServiceState, WalletState};
-use anyhow::{Context, Result};
+use crate::amount::Amount;
+use crate::types::{
+    Address, ApprovalDecision, ApprovalStatus, PendingApproval, PendingTx, ServiceState,
+    SweepDecision, SweepRecord, TxRecord, WalletState,
+};
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
 use dashmap::DashMap;
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::fs;
-use tokio::sync::{Mutex, RwLock};
-use tracing::{debug, info};
+use tokio::sync::{Mutex, OwnedMutexGuard, RwLock};
+use tracing::{debug, info, warn};
+
+/// Transactions kept per wallet in the state file
+const MAX_TX_RECORDS: usize = 100;
+
+/// How long committed sweeps are kept for sweep caps
+const SWEEP_HISTORY_HOURS: i64 = 24;
 
 /// State manager with atomic nonce operations
 pub struct StateManager {
@@ -28,7 +41,7 @@ impl StateManager {
     /// Loads state from the disk
     pub async fn load(state_file_path: PathBuf) -> Result<Self> {
 
-        let state = if state_file_path.exists() {
+        let mut state = if state_file_path.exists() {
             info!("Loading state from {}", state_file_path.display());
             let content = fs::read_to_string(&state_file_path)
                 .await
@@ -41,6 +54,9 @@ impl StateManager {
             ServiceState::new()
         };
 
+        let decisions = read_decisions(&decisions_path(&state_file_path)).await?;
+        apply_decisions(&mut state, &decisions);
+
         for (addr, wallet_state) in &state.wallets {
             debug!(
                 "Wallet {} , next_nonce={}, total_sweeps={}",   
@@ -55,16 +71,52 @@ impl StateManager {
         })
     }
 
-    /// Atomically reserve and increment nonce for a wallet
-    pub async fn reserve_nonce(&self, address: &Address) -> Result<u64> {
-        // Get or create per-wallet lock
-        let lock = self
-            .wallet_locks
+    /// Get or create the per-wallet lock
+    fn wallet_lock(&self, address: &Address) -> Arc<Mutex<()>> {
+        self.wallet_locks
             .entry(address.clone())
             .or_insert_with(|| Arc::new(Mutex::new(())))
-            .clone();
+            .clone()
+    }
+
+    /// Hold the wallet's lock, as a nonce reservation does, while sending a
+    /// transaction at a nonce that was already handed out
+    pub async fn lock_wallet(&self, address: &Address) -> OwnedMutexGuard<()> {
+        self.wallet_lock(address).lock_owned().await
+    }
+
+    /// Reserve the next nonce for a wallet.
+    ///
+    /// The wallet stays locked until the reservation is committed or dropped.
+    /// Nothing is persisted before `commit`, so a reservation that is dropped
+    /// (or explicitly released) hands the same nonce to the next caller.
+    /// Fails while the wallet is halted.
+    pub async fn reserve_nonce(&self, address: &Address) -> Result<NonceReservation<'_>> {
+        let guard = self.wallet_lock(address).lock_owned().await;
+        if let Some(reason) = self.halted_reason(address).await {
+            bail!("Wallet {} is halted: {}", address, reason);
+        }
+        let nonce = self.next_nonce(address).await;
+        debug!("Reserved nonce {} for {}", nonce, address);
+
+        Ok(NonceReservation {
+            state_manager: self,
+            address: address.clone(),
+            nonce,
+            committed: false,
+            _wallet_lock: guard,
+        })
+    }
 
-        let _guard = lock.lock().await;
+    /// Mark `nonce` as used, counting the sweep unless the transaction was
+    /// not one, and recording it in the sweep history if given
+    async fn commit_nonce(
+        &self,
+        address: &Address,
+        nonce: u64,
+        count_sweep: bool,
+        sweep: Option<SweepRecord>,
+    ) -> Result<()> {
         let mut state = self.state.write().await;
 
         if !state.wallets.contains_key(address) {
@@ -79,20 +131,163 @@ impl StateManager {
             .get_mut(address)
             .expect("Wallet state must exist after initialization");
 
-        let current_nonce = wallet_state.next_nonce;
+        wallet_state.next_nonce = nonce + 1;
+        if count_sweep {
+            wallet_state.total_sweeps += 1;
+        }
+        if let Some(record) = sweep {
+            wallet_state
+                .last_sweeps
+                .insert(record.asset.clone(), record.swept_at);
+            let cutoff = record.swept_at - chrono::Duration::hours(SWEEP_HISTORY_HOURS);
+            wallet_state
+                .sweep_history
+                .retain(|record| record.swept_at > cutoff);
+            wallet_state.sweep_history.push(record);
+        }
+        state.last_update = chrono::Utc::now().to_rfc3339();
+
+        self.persist_locked(&state)
+            .await
+            .context("Failed to persist state after nonce commit")
+    }
+
+    /// Record a broadcast transaction and its outcome against a wallet.
+    ///
+    /// A record with the same hash replaces the previous one, so a transaction
+    /// can be recorded as pending and later as confirmed.
+    pub async fn record_transaction(&self, address: &Address, record: TxRecord) -> Result<()> {
+        let mut state = self.state.write().await;
+
+        let wallet_state = state
+            .wallets
+            .entry(address.clone())
+            .or_insert_with(|| WalletState::new(address.clone()));
+
+        wallet_state.transactions.retain(|tx| tx.hash != record.hash);
+        wallet_state.transactions.push(record);
+        let excess = wallet_state
+            .transactions
+            .len()
+            .saturating_sub(MAX_TX_RECORDS);
+        wallet_state.transactions.drain(..excess);
+        state.last_update = chrono::Utc::now().to_rfc3339();
+
+        self.persist_locked(&state)
+            .await
+            .context("Failed to persist state after recording transaction")
+    }
+
+    /// Track a broadcast transaction until it is mined, replacing any entry
+    /// for the same nonce
+    pub async fn track_pending(&self, address: &Address, pending: PendingTx) -> Result<()> {
+        let mut state = self.state.write().await;
+
+        let entries = state
+            .pending_transactions
+            .entry(address.clone())
+            .or_default();
+        entries.retain(|tx| tx.nonce != pending.nonce);
+        entries.push(pending);
+        entries.sort_by_key(|tx| tx.nonce);
+        state.last_update = chrono::Utc::now().to_rfc3339();
+
+        self.persist_locked(&state)
+            .await
+            .context("Failed to persist state after tracking pending transaction")
+    }
+
+    /// Stop tracking the transaction at `nonce`
+    pub async fn clear_pending(&self, address: &Address, nonce: u64) -> Result<()> {
+        let mut state = self.state.write().await;
+
+        if let Some(entries) = state.pending_transactions.get_mut(address) {
+            entries.retain(|tx| tx.nonce != nonce);
+            if entries.is_empty() {
+                state.pending_transactions.remove(address);
+            }
+        }
+        state.last_update = chrono::Utc::now().to_rfc3339();
+
+        self.persist_locked(&state)
+            .await
+            .context("Failed to persist state after clearing pending transaction")
+    }
+
+    /// Unmined transactions for a wallet, lowest nonce first
+    pub async fn pending_transactions(&self, address: &Address) -> Vec<PendingTx> {
+        self.state
+            .read()
+            .await
+            .pending_transactions
+            .get(address)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Nonces from `confirmed_nonce` (the chain's mined transaction count) up
+    /// to the next nonce that were handed out but have no transaction tracked.
+    ///
+    /// Each one blocks every later transaction from the wallet.
+    pub async fn untracked_nonces(&self, address: &Address, confirmed_nonce: u64) -> Vec<u64> {
+        let state = self.state.read().await;
+        let next_nonce = state
+            .wallets
+            .get(address)
+            .map(|wallet| wallet.next_nonce)
+            .unwrap_or(0);
+        let tracked = state.pending_transactions.get(address);
 
-        wallet_state.next_nonce = wallet_state.next_nonce+1;
-        wallet_state.total_sweeps += 1;
-        wallet_state.last_sweep_timestamp = Some(chrono::Utc::now().to_rfc3339());
+        (confirmed_nonce..next_nonce)
+            .filter(|nonce| {
+                !tracked.is_some_and(|entries| entries.iter().any(|tx| tx.nonce == *nonce))
+            })
+            .collect()
+    }
+
+    /// Overwrite the next nonce for a wallet, waiting for any reservation in
+    /// flight to finish first
+    pub async fn set_next_nonce(&self, address: &Address, nonce: u64) -> Result<()> {
+        let _wallet_lock = self.wallet_lock(address).lock_owned().await;
+        let mut state = self.state.write().await;
+
+        state
+            .wallets
+            .entry(address.clone())
+            .or_insert_with(|| WalletState::new(address.clone()))
+            .next_nonce = nonce;
         state.last_update = chrono::Utc::now().to_rfc3339();
-        
+
         self.persist_locked(&state)
             .await
-            .context("Failed to persist state after nonce reservation")?;
+            .context("Failed to persist state after updating nonce")
+    }
+
+    /// Halt a wallet with a reason, or resume it with `None`
+    pub async fn set_halted(&self, address: &Address, reason: Option<String>) -> Result<()> {
+        let mut state = self.state.write().await;
+
+        state
+            .wallets
+            .entry(address.clone())
+            .or_insert_with(|| WalletState::new(address.clone()))
+            .halted = reason;
+        state.last_update = chrono::Utc::now().to_rfc3339();
 
-        Ok(current_nonce)
+        self.persist_locked(&state)
+            .await
+            .context("Failed to persist state after updating halt")
     }
 
+    /// Why the wallet is halted, `None` while sweeps are allowed
+    pub async fn halted_reason(&self, address: &Address) -> Option<String> {
+        self.state
+            .read()
+            .await
+            .wallets
+            .get(address)
+            .and_then(|wallet| wallet.halted.clone())
+    }
 
     /// setup new state for a wallet
     pub async fn initialize_wallet(&self, address: &Address) -> Result<()> {
@@ -119,28 +314,372 @@ impl StateManager {
     /// Store the state to a disk.
     async fn persist_locked(&self, state: &ServiceState) -> Result<()> {
         let json = serde_json::to_string_pretty(state).context("Failed to serialize state")?;
+        write_atomically(&self.state_file_path, &json).await?;
+
+        debug!("State persisted to {}", self.state_file_path.display());
+
+        Ok(())
+    }
+
+    /// Queue a sweep from `wallet`, made of `legs`, for approval, expiring
+    /// after `ttl_seconds`
+    pub async fn queue_approval(
+        &self,
+        wallet: &Address,
+        legs: Vec<SweepDecision>,
+        reason: String,
+        ttl_seconds: u64,
+    ) -> Result<PendingApproval> {
+        if legs.is_empty() {
+            bail!("Cannot queue a sweep without transactions for approval");
+        }
+        let mut state = self.state.write().await;
+
+        state.last_approval_id += 1;
+        let now = Utc::now();
+        let approval = PendingApproval {
+            id: state.last_approval_id,
+            wallet: wallet.clone(),
+            legs,
+            reason,
+            status: ApprovalStatus::Pending,
+            created_at: now,
+            expires_at: now + chrono::Duration::seconds(ttl_seconds as i64),
+        };
+        state.approvals.push(approval.clone());
+        state.last_update = now.to_rfc3339();
 
-        let temp_path = self.state_file_path.with_extension("json.tmp");
+        self.persist_locked(&state)
+            .await
+            .context("Failed to persist state after queueing approval")?;
+        Ok(approval)
+    }
+
+    /// Pick up sweeps approved or rejected by another process, such as
+    /// `approvals approve` run next to a `continuous` sweeper. A decisions
+    /// file that cannot be read is skipped.
+    pub async fn reload_approvals(&self) {
+        match read_decisions(&decisions_path(&self.state_file_path)).await {
+            Ok(decisions) => apply_decisions(&mut *self.state.write().await, &decisions),
+            Err(e) => warn!("Not reloading approval decisions: {:#}", e),
+        }
+    }
+
+    /// Record `decision` on queued sweep `approval` in the decisions file,
+    /// leaving the state file to the sweeper. Decisions on sweeps that have
+    /// left the queue are pruned on the way.
+    async fn record_decision(
+        &self,
+        state: &ServiceState,
+        approval: &PendingApproval,
+        decision: ApprovalDecision,
+    ) -> Result<()> {
+        let path = decisions_path(&self.state_file_path);
+        let mut decisions = read_decisions(&path).await?;
+        decisions.retain(|queued, _| {
+            *queued > state.last_approval_id
+                || state.approvals.iter().any(|approval| approval.id == *queued)
+        });
+        decisions.insert(
+            approval.id,
+            RecordedDecision {
+                created_at: approval.created_at,
+                decision,
+            },
+        );
+
+        let json =
+            serde_json::to_string_pretty(&decisions).context("Failed to serialize decisions")?;
+        write_atomically(&path, &json).await
+    }
+
+    /// Queued sweeps, oldest first
+    pub async fn approvals(&self) -> Vec<PendingApproval> {
+        self.state.read().await.approvals.clone()
+    }
+
+    /// Approve a queued sweep so it runs on the next cycle
+    pub async fn approve(&self, id: u64) -> Result<PendingApproval> {
+        let mut state = self.state.write().await;
+
+        let approval = state
+            .approvals
+            .iter_mut()
+            .find(|approval| approval.id == id)
+            .with_context(|| format!("No queued sweep with id {}", id))?;
+        if approval.expires_at <= Utc::now() {
+            bail!("Queued sweep {} expired at {}", id, approval.expires_at);
+        }
+        approval.status = ApprovalStatus::Approved;
+        let approval = approval.clone();
+
+        self.record_decision(&state, &approval, ApprovalDecision::Approved)
+            .await
+            .context("Failed to record approval")?;
+        Ok(approval)
+    }
+
+    /// Reject a queued sweep so it never runs
+    pub async fn reject(&self, id: u64) -> Result<PendingApproval> {
+        let mut state = self.state.write().await;
+
+        let index = state
+            .approvals
+            .iter()
+            .position(|approval| approval.id == id)
+            .with_context(|| format!("No queued sweep with id {}", id))?;
+        let approval = state.approvals.remove(index);
 
-        fs::write(&temp_path, &json)
+        self.record_decision(&state, &approval, ApprovalDecision::Rejected)
             .await
-            .context("Failed to write temporary state file")?;
+            .context("Failed to record rejection")?;
+        Ok(approval)
+    }
+
+    /// Take a sweep out of the queue once it has run or been dropped
+    pub async fn remove_approval(&self, id: u64) -> Result<PendingApproval> {
+        let mut state = self.state.write().await;
 
-        let file = fs::File::open(&temp_path).await?;
-        file.sync_all()
+        let index = state
+            .approvals
+            .iter()
+            .position(|approval| approval.id == id)
+            .with_context(|| format!("No queued sweep with id {}", id))?;
+        let approval = state.approvals.remove(index);
+        state.last_update = chrono::Utc::now().to_rfc3339();
+
+        self.persist_locked(&state)
             .await
-            .context("Failed to fsync state file")?;
+            .context("Failed to persist state after removing approval")?;
+        Ok(approval)
+    }
+
+    /// Drop queued sweeps whose TTL passed, returning them
+    pub async fn expire_approvals(&self, now: DateTime<Utc>) -> Result<Vec<PendingApproval>> {
+        let mut state = self.state.write().await;
+
+        let (expired, live): (Vec<_>, Vec<_>) = state
+            .approvals
+            .drain(..)
+            .partition(|approval| approval.expires_at <= now);
+        state.approvals = live;
+        if expired.is_empty() {
+            return Ok(expired);
+        }
+        state.last_update = now.to_rfc3339();
 
-        fs::rename(&temp_path, &self.state_file_path)
+        self.persist_locked(&state)
             .await
-            .context("Failed to rename state file")?;
+            .context("Failed to persist state after expiring approvals")?;
+        Ok(expired)
+    }
 
-        debug!("State persisted to {}", self.state_file_path.display());
+    /// Sweeps committed for `address` after `since`, oldest first
+    pub async fn sweeps_since(&self, address: &Address, since: DateTime<Utc>) -> Vec<SweepRecord> {
+        let state = self.state.read().await;
+        state
+            .wallets
+            .get(address)
+            .map(|wallet| {
+                wallet
+                    .sweep_history
+                    .iter()
+                    .filter(|record| record.swept_at > since)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
 
-        Ok(())
+    /// Sweeps committed for every wallet after `since`
+    pub async fn all_sweeps_since(&self, since: DateTime<Utc>) -> Vec<(Address, SweepRecord)> {
+        let state = self.state.read().await;
+        state
+            .wallets
+            .iter()
+            .flat_map(|(address, wallet)| {
+                wallet
+                    .sweep_history
+                    .iter()
+                    .filter(|record| record.swept_at > since)
+                    .map(|record| (address.clone(), record.clone()))
+            })
+            .collect()
+    }
+
+    /// When `asset` was last swept from the wallet, `None` if it never was
+    pub async fn last_asset_sweep(&self, address: &Address, asset: &str) -> Option<DateTime<Utc>> {
+        let state = self.state.read().await;
+        state.wallets.get(address)?.last_sweeps.get(asset).copied()
+    }
+
+    /// Nonce the next reservation for `address` would return
+    pub async fn next_nonce(&self, address: &Address) -> u64 {
+        self.state
+            .read()
+            .await
+            .wallets
+            .get(address)
+            .map(|wallet| wallet.next_nonce)
+            .unwrap_or(0)
     }
 
     pub async fn fetch_snapshot(&self) -> ServiceState {
         self.state.read().await.clone()
     }
 }
+
+/// File the `approvals` command records its decisions in, next to the state
+/// file, so that it never writes the state file a running sweeper owns
+fn decisions_path(state_file_path: &Path) -> PathBuf {
+    state_file_path.with_extension("approvals.json")
+}
+
+/// An operator's decision, tied to the queued sweep it was made on: ids
+/// start over when the state file is deleted, creation times do not
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct RecordedDecision {
+    created_at: DateTime<Utc>,
+    decision: ApprovalDecision,
+}
+
+/// Decisions by queued sweep id, empty when none were recorded yet
+async fn read_decisions(path: &Path) -> Result<BTreeMap<u64, RecordedDecision>> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let content = fs::read_to_string(path)
+        .await
+        .context("Failed to read approval decisions")?;
+    serde_json::from_str(&content).context("Failed to parse approval decisions")
+}
+
+/// Approve or drop the queued sweeps the operator decided on. Decisions on
+/// sweeps no longer queued, or on an earlier sweep under the same id, are
+/// ignored.
+fn apply_decisions(state: &mut ServiceState, decisions: &BTreeMap<u64, RecordedDecision>) {
+    state.approvals.retain_mut(|approval| {
+        let decision = decisions
+            .get(&approval.id)
+            .filter(|recorded| recorded.created_at == approval.created_at)
+            .map(|recorded| recorded.decision);
+        match decision {
+            Some(ApprovalDecision::Approved) => {
+                if approval.status != ApprovalStatus::Approved {
+                    info!("Queued sweep #{} was approved", approval.id);
+                    approval.status = ApprovalStatus::Approved;
+                }
+                true
+            }
+            Some(ApprovalDecision::Rejected) => {
+                info!("Queued sweep #{} was rejected", approval.id);
+                false
+            }
+            None => true,
+        }
+    });
+}
+
+/// Replace `path` with `contents` through a synced temporary file, so a crash
+/// never leaves it half written
+async fn write_atomically(path: &Path, contents: &str) -> Result<()> {
+    let temp_path = path.with_extension("json.tmp");
+
+    fs::write(&temp_path, contents)
+        .await
+        .with_context(|| format!("Failed to write {}", temp_path.display()))?;
+
+    let file = fs::File::open(&temp_path).await?;
+    file.sync_all()
+        .await
+        .with_context(|| format!("Failed to fsync {}", temp_path.display()))?;
+
+    fs::rename(&temp_path, path)
+        .await
+        .with_context(|| format!("Failed to rename {} to {}", temp_path.display(), path.display()))?;
+    Ok(())
+}
+
+/// A nonce held for one transaction, see [`StateManager::reserve_nonce`]
+pub struct NonceReservation<'a> {
+    state_manager: &'a StateManager,
+    address: Address,
+    nonce: u64,
+    committed: bool,
+    _wallet_lock: OwnedMutexGuard<()>,
+}
+
+impl NonceReservation<'_> {
+    pub fn nonce(&self) -> u64 {
+        self.nonce
+    }
+
+    /// Record the nonce as used (the transaction was handed to the network)
+    /// and count the sweep
+    pub async fn commit(mut self) -> Result<u64> {
+        self.committed = true;
+        self.state_manager
+            .commit_nonce(&self.address, self.nonce, true, None)
+            .await?;
+        Ok(self.nonce)
+    }
+
+    /// Record the nonce as used by a transaction that is not a sweep, such as
+    /// a gas station top-up, without counting it
+    pub async fn commit_transfer(mut self) -> Result<u64> {
+        self.committed = true;
+        self.state_manager
+            .commit_nonce(&self.address, self.nonce, false, None)
+            .await?;
+        Ok(self.nonce)
+    }
+
+    /// Commit the nonce of a sweep of `amount` of `asset`, recording it in the
+    /// wallet's sweep history
+    pub async fn commit_sweep(self, asset: &str, amount: Amount) -> Result<u64> {
+        self.commit_record(SweepRecord {
+            asset: asset.to_string(),
+            amount,
+            swept_at: Utc::now(),
+            destination: None,
+            split: None,
+        })
+        .await
+    }
+
+    /// Commit the nonce of the sweep of `decision` sent to `destination`,
+    /// recording the destination and any split in the sweep history
+    pub async fn commit_sweep_to(
+        self,
+        decision: &SweepDecision,
+        destination: &Address,
+    ) -> Result<u64> {
+        self.commit_record(SweepRecord {
+            asset: decision.asset.clone(),
+            amount: decision.amount,
+            swept_at: Utc::now(),
+            destination: Some(destination.clone()),
+            split: decision.split,
+        })
+        .await
+    }
+
+    async fn commit_record(mut self, record: SweepRecord) -> Result<u64> {
+        self.committed = true;
+        self.state_manager
+            .commit_nonce(&self.address, self.nonce, true, Some(record))
+            .await?;
+        Ok(self.nonce)
+    }
+
+    /// Give the nonce back so the next sweep reuses it
+    pub fn release(self) {}
+}
+
+impl Drop for NonceReservation<'_> {
+    fn drop(&mut self) {
+        if !self.committed {
+            info!("Released nonce {} for {}", self.nonce, self.address);
+        }
+    }
+}
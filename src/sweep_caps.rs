@@ -0,0 +1,211 @@
+//! Sweep Caps
+//!
+//! Checks each sweep against the configured caps and the persisted sweep
+//! history before it is emitted. A split sweep is checked once, as a whole.
+use crate::amount::Amount;
+use crate::state_manager::StateManager;
+use crate::types::{Address, CapAction, SweepCap, SweepDecision, SweepRecord, SweepSplit};
+use anyhow::{Context, Result};
+use chrono::{Duration, Utc};
+use std::sync::Arc;
+use tokio::sync::{Mutex, MutexGuard};
+use tracing::info;
+
+/// What the caps allow for one sweep
+#[derive(Debug, Clone)]
+pub enum CapOutcome {
+    /// Send every leg of the sweep, clipped if a `clip` cap required it
+    Allow(Vec<SweepDecision>),
+    /// A `hold` cap was exceeded; the sweep waits for approval
+    Hold(String),
+    /// `clip` caps left nothing to sweep
+    Skip(String),
+}
+
+/// Enforces sweep caps using the sweep history kept by the state manager
+pub struct SweepCaps {
+    caps: Vec<SweepCap>,
+    state_manager: Arc<StateManager>,
+    /// Held from the check to the emitted sweep for caps on all wallets
+    shared: Mutex<()>,
+}
+
+impl SweepCaps {
+    pub fn new(caps: Vec<SweepCap>, state_manager: Arc<StateManager>) -> Self {
+        Self {
+            caps,
+            state_manager,
+            shared: Mutex::new(()),
+        }
+    }
+
+    /// Lock sweeps of `asset` while a cap on all wallets counts them, so
+    /// wallets checked concurrently cannot overshoot it together. Hold the
+    /// guard until the sweep is emitted.
+    pub async fn lock_shared(&self, asset: &str) -> Option<MutexGuard<'_, ()>> {
+        let shared = self.caps.iter().any(|cap| {
+            cap.wallet.is_none() && cap.asset.as_deref().is_none_or(|capped| capped == asset)
+        });
+        match shared {
+            true => Some(self.shared.lock().await),
+            false => None,
+        }
+    }
+
+    /// Sweeps of the last 24 hours counted by `cap`
+    async fn history(&self, cap: &SweepCap) -> Vec<SweepRecord> {
+        let since = Utc::now() - Duration::hours(24);
+        let records = match &cap.wallet {
+            Some(wallet) => self.state_manager.sweeps_since(wallet, since).await,
+            None => self
+                .state_manager
+                .all_sweeps_since(since)
+                .await
+                .into_iter()
+                .map(|(_, record)| record)
+                .collect(),
+        };
+        records
+            .into_iter()
+            .filter(|record| {
+                cap.asset
+                    .as_deref()
+                    .is_none_or(|asset| asset == record.asset)
+            })
+            .collect()
+    }
+
+    /// Check a sweep from `wallet` against every cap that covers it.
+    ///
+    /// `legs` are the sweep's transactions: one decision, or every leg of a
+    /// split. A split is judged by its total and counts as one sweep; a
+    /// `clip` cap scales all of its legs down alike.
+    pub async fn check(&self, wallet: &Address, legs: Vec<SweepDecision>) -> Result<CapOutcome> {
+        let Some(first) = legs.first() else {
+            return Ok(CapOutcome::Allow(legs));
+        };
+        let asset = first.asset.clone();
+        let total = first.split.map_or(first.amount, |split| split.total);
+        let decimals = total.decimals();
+        let mut amount = total;
+        let mut clipped = Vec::new();
+
+        for cap in &self.caps {
+            if !cap.applies_to(wallet, &asset) {
+                continue;
+            }
+            let history = self.history(cap).await;
+            let mut exceeded = Vec::new();
+            let mut allowed = amount;
+
+            // The legs of a split count once
+            let sweeps = history
+                .iter()
+                .filter(|record| record.split.is_none_or(|split| split.first))
+                .count();
+            if let Some(max_sweeps) = cap.max_sweeps_per_day
+                && sweeps as u64 >= max_sweeps
+            {
+                exceeded.push(format!(
+                    "{} sweeps in the last 24h reached max_sweeps_per_day {}",
+                    sweeps, max_sweeps
+                ));
+                allowed = Amount::zero(decimals);
+            }
+            if let Some(max_per_sweep) = &cap.max_per_sweep {
+                let max_per_sweep = max_per_sweep.rescale(decimals)?;
+                if amount > max_per_sweep {
+                    exceeded.push(format!(
+                        "{} is above max_per_sweep {}",
+                        amount, max_per_sweep
+                    ));
+                    allowed = allowed.min(max_per_sweep);
+                }
+            }
+            if let Some(max_per_24h) = &cap.max_per_24h {
+                let max_per_24h = max_per_24h.rescale(decimals)?;
+                let mut swept = Amount::zero(decimals);
+                for record in &history {
+                    swept = swept
+                        .checked_add(&record.amount.rescale(decimals)?)
+                        .context("Sweep history total overflowed")?;
+                }
+                let remaining = max_per_24h
+                    .checked_sub(&swept)
+                    .unwrap_or(Amount::zero(decimals));
+                if amount > remaining {
+                    exceeded.push(format!(
+                        "{} swept in the last 24h leaves {} of max_per_24h {}",
+                        swept, remaining, max_per_24h
+                    ));
+                    allowed = allowed.min(remaining);
+                }
+            }
+
+            if exceeded.is_empty() {
+                continue;
+            }
+            let reason = format!("{}: {}", cap.describe(), exceeded.join(", "));
+            match cap.on_exceed {
+                CapAction::Hold => return Ok(CapOutcome::Hold(reason)),
+                CapAction::Clip => {
+                    amount = allowed;
+                    clipped.push(reason);
+                }
+            }
+        }
+
+        if clipped.is_empty() {
+            return Ok(CapOutcome::Allow(legs));
+        }
+        let reason = clipped.join("; ");
+        let legs = clip_legs(legs, total, amount)?;
+        if legs.is_empty() {
+            return Ok(CapOutcome::Skip(reason));
+        }
+        info!(
+            "SWEEP CLIPPED: {} {} to {} on {}: {}",
+            total, asset, amount, wallet, reason
+        );
+        Ok(CapOutcome::Allow(legs))
+    }
+}
+
+/// Scale the legs of a sweep of `total` down to `allowed`, dropping legs
+/// left with nothing to send
+fn clip_legs(
+    legs: Vec<SweepDecision>,
+    total: Amount,
+    allowed: Amount,
+) -> Result<Vec<SweepDecision>> {
+    let mut clipped = Vec::with_capacity(legs.len());
+    for leg in legs {
+        let leg = match leg.split {
+            None => SweepDecision {
+                amount: allowed,
+                ..leg
+            },
+            Some(split) => {
+                let units = leg
+                    .amount
+                    .units()
+                    .checked_mul(allowed.units())
+                    .context("Clipped split leg overflowed")?
+                    / total.units();
+                SweepDecision {
+                    amount: Amount::from_units(units, leg.amount.decimals()),
+                    split: Some(SweepSplit {
+                        total: allowed,
+                        first: clipped.is_empty(),
+                        ..split
+                    }),
+                    ..leg
+                }
+            }
+        };
+        if !leg.amount.is_zero() {
+            clipped.push(leg);
+        }
+    }
+    Ok(clipped)
+}
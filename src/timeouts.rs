@@ -0,0 +1,118 @@
+//! Timeouts
+//!
+//! Bounds balance queries, wallet checks and sweep cycles by the configured
+//! timeouts, failing with a [`TimedOut`] error that callers can tell apart
+//! from other failures.
+use anyhow::Result;
+use serde::Serialize;
+use std::fmt;
+use std::future::Future;
+use tokio::time::{Duration, Instant};
+
+/// Which configured timeout was hit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeoutKind {
+    /// A single balance query (`timeouts.balance_seconds`)
+    Balance,
+    /// One wallet's check (`timeouts.wallet_seconds`)
+    Wallet,
+    /// The whole sweep cycle (`timeouts.cycle_seconds`)
+    Cycle,
+}
+
+impl fmt::Display for TimeoutKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TimeoutKind::Balance => "balance",
+            TimeoutKind::Wallet => "wallet",
+            TimeoutKind::Cycle => "cycle",
+        };
+        f.write_str(name)
+    }
+}
+
+/// An operation cut short by one of the configured timeouts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TimedOut {
+    pub kind: TimeoutKind,
+    pub seconds: u64,
+}
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} timeout of {}s elapsed", self.kind, self.seconds)
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+impl TimedOut {
+    /// The timeout behind `error`, if it is one, through any added context
+    pub fn find(error: &anyhow::Error) -> Option<TimedOut> {
+        error.downcast_ref::<TimedOut>().copied()
+    }
+}
+
+/// Run `future`, failing with [`TimedOut`] after `seconds` (never when `None`)
+pub async fn run<T>(
+    kind: TimeoutKind,
+    seconds: Option<u64>,
+    future: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let Some(seconds) = seconds else {
+        return future.await;
+    };
+    match tokio::time::timeout(Duration::from_secs(seconds), future).await {
+        Ok(result) => result,
+        Err(_) => Err(TimedOut { kind, seconds }.into()),
+    }
+}
+
+/// When a timeout runs out, for work that stops between steps instead of
+/// being cut off inside one, such as a broadcast sweep
+#[derive(Debug, Clone, Copy)]
+pub struct Deadline {
+    at: Instant,
+    timed_out: TimedOut,
+}
+
+impl Deadline {
+    /// `seconds` from now (never when `None`)
+    pub fn after(kind: TimeoutKind, seconds: Option<u64>) -> Option<Self> {
+        seconds.map(|seconds| Self {
+            at: Instant::now() + Duration::from_secs(seconds),
+            timed_out: TimedOut { kind, seconds },
+        })
+    }
+
+    /// Whichever of `a` and `b` runs out first
+    pub fn earliest(a: Option<Self>, b: Option<Self>) -> Option<Self> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(if b.at < a.at { b } else { a }),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Fail with [`TimedOut`] if the deadline has passed
+    pub fn check(deadline: Option<Self>) -> Result<()> {
+        match deadline {
+            Some(deadline) if Instant::now() >= deadline.at => Err(deadline.timed_out.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Run `future`, failing with [`TimedOut`] once the deadline passes
+    pub async fn run<T>(
+        deadline: Option<Self>,
+        future: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let Some(deadline) = deadline else {
+            return future.await;
+        };
+        match tokio::time::timeout_at(deadline.at, future).await {
+            Ok(result) => result,
+            Err(_) => Err(deadline.timed_out.into()),
+        }
+    }
+}
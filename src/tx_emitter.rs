@@ -1,56 +1,766 @@
-//! Mock Transaction Emitter
+//! Transaction Emitter
 //!
-//! Simulates transaction building and submission.
-//! In a real implementation, this would sign and broadcast transactions to the blockchain.
+//! Defines the `TxEmitter` abstraction used by the wallet monitor to turn sweep
+//! decisions into submitted transactions, and the implementations that can be
+//! selected from config.
 
+use crate::amount::U256;
+use crate::destinations::Destinations;
+use crate::evm_tx::{EvmTransaction, EvmTxBuilder};
+use crate::keystore::Keystore;
+use crate::rpc::{BlockTag, JsonRpcClient, TransactionReceipt};
 use crate::state_manager::StateManager;
-use crate::types::{Address, MockTransaction, SweepDecision};
-use anyhow::Result;
+use crate::types::{
+    Address, Config, EmitterConfig, MockTransaction, PendingTx, SubmittedTx, SweepDecision,
+    TxRecord, TxStatus,
+};
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use k256::ecdsa::SigningKey;
 use std::sync::Arc;
-use tracing::{info};
+use tokio::time::{Duration, Instant, sleep};
+use tracing::{info, warn};
+
+/// Asset label of gas station top-ups in the station's records
+const GAS_TOP_UP: &str = "gas_top_up";
+
+/// Backend that builds and submits sweep transactions
+#[async_trait]
+pub trait TxEmitter: Send + Sync {
+    /// Short name used to identify the backend in logs
+    fn name(&self) -> &str;
+
+    /// Emit a sweep transaction from `from_address` for `decision`
+    async fn emit_sweep(
+        &self,
+        from_address: &Address,
+        decision: &SweepDecision,
+    ) -> Result<SubmittedTx>;
+}
+
+/// Build the emitter selected in config
+pub async fn from_config(
+    config: &Config,
+    state_manager: Arc<StateManager>,
+) -> Result<Arc<dyn TxEmitter>> {
+    let treasury_address = config.treasury_address.clone();
+    let destinations = Destinations::from_config(config);
+    match &config.emitter {
+        EmitterConfig::Mock => Ok(Arc::new(
+            MockTxEmitter::new(state_manager, treasury_address).with_destinations(destinations),
+        )),
+        EmitterConfig::DryRun => Ok(Arc::new(
+            DryRunTxEmitter::new(state_manager, treasury_address).with_destinations(destinations),
+        )),
+        EmitterConfig::Evm { .. } => Ok(Arc::new(
+            EvmTxEmitter::from_config(config, state_manager).await?,
+        )),
+    }
+}
 
 /// Mock transaction emitter
+///
+/// Reserves real nonces and builds transactions, but only logs the submission.
 pub struct MockTxEmitter {
     state_manager: Arc<StateManager>,
-    treasury_address: Address,
+    destinations: Destinations,
 }
 
 impl MockTxEmitter {
     pub fn new(state_manager: Arc<StateManager>, treasury_address: Address) -> Self {
         Self {
             state_manager,
-            treasury_address,
+            destinations: Destinations::new(treasury_address),
         }
     }
 
+    /// Route sweeps with `destinations` instead of only to the treasury
+    pub fn with_destinations(mut self, destinations: Destinations) -> Self {
+        self.destinations = destinations;
+        self
+    }
+}
+
+#[async_trait]
+impl TxEmitter for MockTxEmitter {
+    fn name(&self) -> &str {
+        "mock"
+    }
 
     /// Emit a sweep transaction
-    pub async fn emit_sweep(
+    async fn emit_sweep(
         &self,
         from_address: &Address,
         decision: &SweepDecision,
-    ) -> Result<MockTransaction> {
-        let nonce = self.state_manager.reserve_nonce(from_address).await?;
+    ) -> Result<SubmittedTx> {
+        let destination = self.destinations.resolve(decision)?;
+        let reservation = self.state_manager.reserve_nonce(from_address).await?;
 
         // Step 2: Build mock transaction
         let tx = MockTransaction {
             from: from_address.clone(),
-            to: self.treasury_address.clone(),
-            value: decision.amount.clone(),
+            to: destination.clone(),
+            value: decision.amount,
             asset: decision.asset.clone(),
-            nonce,
+            nonce: reservation.nonce(),
             token_address: decision.token_address.clone(),
         };
 
         info!("GENERATING TX: {}", tx.format_log());
+        reservation
+            .commit_sweep_to(decision, &destination)
+            .await?;
 
-            info!(
-                "SWEEP SUBMITTED: {} {} from {} to {}",
-                decision.amount,
-                decision.asset,
+        info!(
+            "SWEEP SUBMITTED: {} {} from {} to {}",
+            decision.amount, decision.asset, from_address, destination
+        );
+        // Nothing reaches a chain, so there is no hash to report
+        Ok(SubmittedTx { hash: None, tx })
+    }
+}
+
+/// Dry-run emitter
+///
+/// Builds the transaction with the wallet's next nonce but neither reserves
+/// the nonce nor submits anything.
+pub struct DryRunTxEmitter {
+    state_manager: Arc<StateManager>,
+    destinations: Destinations,
+}
+
+impl DryRunTxEmitter {
+    pub fn new(state_manager: Arc<StateManager>, treasury_address: Address) -> Self {
+        Self {
+            state_manager,
+            destinations: Destinations::new(treasury_address),
+        }
+    }
+
+    /// Route sweeps with `destinations` instead of only to the treasury
+    pub fn with_destinations(mut self, destinations: Destinations) -> Self {
+        self.destinations = destinations;
+        self
+    }
+}
+
+#[async_trait]
+impl TxEmitter for DryRunTxEmitter {
+    fn name(&self) -> &str {
+        "dry_run"
+    }
+
+    async fn emit_sweep(
+        &self,
+        from_address: &Address,
+        decision: &SweepDecision,
+    ) -> Result<SubmittedTx> {
+        let destination = self.destinations.resolve(decision)?;
+        let nonce = self.state_manager.next_nonce(from_address).await;
+
+        let tx = MockTransaction {
+            from: from_address.clone(),
+            to: destination,
+            value: decision.amount,
+            asset: decision.asset.clone(),
+            nonce,
+            token_address: decision.token_address.clone(),
+        };
+
+        info!("DRY RUN, NOT SUBMITTED: {}", tx.format_log());
+        Ok(SubmittedTx { hash: None, tx })
+    }
+}
+
+/// How often and for how long to poll for a receipt
+#[derive(Debug, Clone, Copy)]
+pub struct ReceiptPolling {
+    pub interval: Duration,
+    pub timeout: Duration,
+}
+
+/// Final state of a tracked transaction
+#[derive(Debug, Clone)]
+pub struct TxOutcome {
+    /// Hash that was mined, or the latest one broadcast if none was
+    pub hash: String,
+    pub status: TxStatus,
+    pub receipt: Option<TransactionReceipt>,
+}
+
+/// EVM emitter
+///
+/// Signs sweeps with the wallet's local key, broadcasts them with
+/// `eth_sendRawTransaction` and polls `eth_getTransactionReceipt` until the
+/// transaction is mined or dropped. A transaction left unconfirmed for
+/// `gas.replacement.stuck_after_seconds` is rebroadcast at the same nonce with
+/// bumped fees. The outcome is recorded in the wallet's state.
+pub struct EvmTxEmitter {
+    state_manager: Arc<StateManager>,
+    destinations: Destinations,
+    client: Arc<JsonRpcClient>,
+    builder: EvmTxBuilder,
+    keystore: Keystore,
+    polling: ReceiptPolling,
+    gas_station: Option<GasStation>,
+}
+
+/// Wallet that pays for gas on behalf of hot wallets, see
+/// [`EvmTxEmitter::with_gas_station`]
+#[derive(Debug, Clone)]
+struct GasStation {
+    address: Address,
+    /// Fixed top-up in wei; `None` sends just the shortfall
+    top_up_amount: Option<U256>,
+}
+
+impl EvmTxEmitter {
+    pub fn new(
+        state_manager: Arc<StateManager>,
+        treasury_address: Address,
+        client: Arc<JsonRpcClient>,
+        builder: EvmTxBuilder,
+        keystore: Keystore,
+        polling: ReceiptPolling,
+    ) -> Self {
+        Self {
+            state_manager,
+            destinations: Destinations::new(treasury_address),
+            client,
+            builder,
+            keystore,
+            polling,
+            gas_station: None,
+        }
+    }
+
+    /// Route sweeps with `destinations` instead of only to the treasury
+    pub fn with_destinations(mut self, destinations: Destinations) -> Self {
+        self.destinations = destinations;
+        self
+    }
+
+    /// Fund token sweeps from `address` when the hot wallet cannot pay the gas.
+    ///
+    /// The top-up sends at least the shortfall, `top_up_amount` wei when that
+    /// is larger, and must confirm before the sweep is sent.
+    pub fn with_gas_station(mut self, address: Address, top_up_amount: Option<U256>) -> Self {
+        self.gas_station = Some(GasStation {
+            address,
+            top_up_amount,
+        });
+        self
+    }
+
+    /// Build the emitter from the `evm` emitter config
+    pub async fn from_config(config: &Config, state_manager: Arc<StateManager>) -> Result<Self> {
+        let EmitterConfig::Evm {
+            rpc_url,
+            chain_id,
+            keystore,
+            receipt_poll_interval_ms,
+            receipt_timeout_seconds,
+            gas_station,
+        } = &config.emitter
+        else {
+            bail!("The evm emitter is not configured");
+        };
+
+        let keystore = Keystore::load(keystore).await?;
+        let emitter = Self::new(
+            state_manager,
+            config.treasury_address.clone(),
+            Arc::new(JsonRpcClient::new(rpc_url.clone())?),
+            EvmTxBuilder::new(*chain_id, config.gas.clone()),
+            keystore,
+            ReceiptPolling {
+                interval: Duration::from_millis(*receipt_poll_interval_ms),
+                timeout: Duration::from_secs(*receipt_timeout_seconds),
+            },
+        )
+        .with_destinations(Destinations::from_config(config));
+
+        Ok(match gas_station {
+            Some(gas_station) => emitter.with_gas_station(
+                gas_station.address.clone(),
+                gas_station.top_up_amount_wei()?,
+            ),
+            None => emitter,
+        })
+    }
+
+    /// Make sure `address` can pay for a token sweep, topping it up from the
+    /// gas station if needed and waiting for the top-up to confirm
+    async fn ensure_token_gas(&self, address: &Address) -> Result<()> {
+        let Some(station) = &self.gas_station else {
+            return Ok(());
+        };
+
+        let needed = self.builder.gas().token_sweep_fee()?;
+        let balance = self
+            .client
+            .get_balance(address, BlockTag::Pending)
+            .await
+            .with_context(|| format!("Failed to fetch gas balance of {}", address))?;
+        if balance >= needed {
+            return Ok(());
+        }
+        let shortfall = needed - balance;
+        let amount = station.top_up_amount.unwrap_or_default().max(shortfall);
+
+        let signer = self.keystore.signer(&station.address)?;
+        self.resume_pending(&station.address, signer).await?;
+        let reservation = self.state_manager.reserve_nonce(&station.address).await?;
+        let tx = self
+            .builder
+            .build_transfer(reservation.nonce(), address, amount)?;
+        info!(
+            "GAS TOP-UP: {} has {} wei, token sweep needs {} wei; sending {} wei from {}",
+            address, balance, needed, amount, station.address
+        );
+
+        let hash = self
+            .broadcast(signer, &tx)
+            .await
+            .context("Failed to broadcast gas top-up")?;
+        reservation.commit_transfer().await?;
+
+        let pending = tx.to_pending(GAS_TOP_UP, hash);
+        self.state_manager
+            .track_pending(&station.address, pending.clone())
+            .await?;
+        let outcome = self
+            .track(&station.address, signer, pending.clone())
+            .await?;
+        self.settle(&station.address, &pending, &outcome).await?;
+
+        if outcome.status != TxStatus::Confirmed {
+            bail!(
+                "Gas top-up {} for {} is {:?}, not sweeping tokens",
+                outcome.hash,
+                address,
+                outcome.status
+            );
+        }
+        info!("GAS TOP-UP CONFIRMED: {} for {}", outcome.hash, address);
+        Ok(())
+    }
+
+    /// Replace the transaction stuck at `nonce` (the lowest tracked one by
+    /// default) with a zero-value transfer to the wallet itself, then track it
+    /// like a sweep.
+    ///
+    /// Only a tracked transaction can be cancelled, since its fees are what
+    /// the cancellation has to outbid.
+    pub async fn cancel(&self, from_address: &Address, nonce: Option<u64>) -> Result<TxOutcome> {
+        let _wallet_lock = self.state_manager.lock_wallet(from_address).await;
+        let tracked = self.state_manager.pending_transactions(from_address).await;
+        let stuck = match nonce {
+            Some(nonce) => tracked
+                .into_iter()
+                .find(|tx| tx.nonce == nonce)
+                .with_context(|| {
+                    format!(
+                        "No pending transaction tracked for {} at nonce {}",
+                        from_address, nonce
+                    )
+                })?,
+            None => tracked
+                .into_iter()
+                .next()
+                .with_context(|| format!("No pending transaction tracked for {}", from_address))?,
+        };
+
+        self.send_self_transfer(from_address, stuck.nonce, Some(stuck), "cancel")
+            .await
+    }
+
+    /// Nonces below the stored next nonce that no live transaction occupies.
+    ///
+    /// Checked against the chain's confirmed transaction count: a nonce at or
+    /// above it is a gap when nothing was tracked for it, or when the node no
+    /// longer knows any of its hashes.
+    pub async fn find_gaps(&self, address: &Address) -> Result<Vec<u64>> {
+        let confirmed = self
+            .client
+            .get_transaction_count(address, BlockTag::Latest)
+            .await
+            .with_context(|| format!("Failed to fetch transaction count for {}", address))?;
+
+        let mut gaps = self
+            .state_manager
+            .untracked_nonces(address, confirmed)
+            .await;
+        for pending in self.state_manager.pending_transactions(address).await {
+            if pending.nonce < confirmed {
+                continue;
+            }
+            let mut known = false;
+            for hash in &pending.hashes {
+                if self.client.transaction_known(hash).await? {
+                    known = true;
+                    break;
+                }
+            }
+            if !known {
+                gaps.push(pending.nonce);
+            }
+        }
+
+        gaps.sort_unstable();
+        Ok(gaps)
+    }
+
+    /// Occupy a gap with a zero-value transfer to the wallet itself
+    pub async fn fill_gap(&self, address: &Address, nonce: u64) -> Result<TxOutcome> {
+        let _wallet_lock = self.state_manager.lock_wallet(address).await;
+        self.send_self_transfer(address, nonce, None, "gap_fill")
+            .await
+    }
+
+    /// Send a zero-value self-transfer at `nonce`, outbidding `stuck` if it is
+    /// given, and track it until it settles. The caller holds the wallet lock.
+    ///
+    /// `nonce` must be below the stored next nonce: a transaction above it
+    /// would not be recorded, and a later sweep would reuse its nonce.
+    async fn send_self_transfer(
+        &self,
+        from_address: &Address,
+        nonce: u64,
+        stuck: Option<PendingTx>,
+        label: &str,
+    ) -> Result<TxOutcome> {
+        let next_nonce = self.state_manager.next_nonce(from_address).await;
+        if nonce >= next_nonce {
+            bail!(
+                "Nonce {} of {} has not been used yet (next nonce is {})",
+                nonce,
                 from_address,
-                self.treasury_address
+                next_nonce
+            );
+        }
+        let signer = self.keystore.signer(from_address)?;
+        let mut self_transfer = self
+            .builder
+            .build_transfer(nonce, from_address, U256::ZERO)?;
+        if let Some(stuck) = &stuck {
+            // Bump the stuck transaction's fees on the self-transfer, which
+            // has no value to pay the rise out of
+            let replacement = &self.builder.gas().replacement;
+            self_transfer.max_fee_per_gas = stuck.max_fee_per_gas;
+            self_transfer.max_priority_fee_per_gas = stuck.max_priority_fee_per_gas;
+            self_transfer = self_transfer
+                .bumped(
+                    replacement.bump_percent,
+                    replacement.max_fee_per_gas_wei()?,
+                    false,
+                )
+                .with_context(|| {
+                    format!(
+                        "Cannot outbid {}: its fees are already at the replacement cap",
+                        stuck.latest_hash()
+                    )
+                })?;
+        }
+
+        let hash = self.broadcast(signer, &self_transfer).await?;
+        warn!(
+            "SELF-TRANSFER BROADCAST ({}): nonce {} from {} as {}",
+            label, nonce, from_address, hash
+        );
+
+        let mut pending = self_transfer.to_pending(label, hash);
+        if let Some(stuck) = stuck {
+            // Keep watching the original hashes in case one of them is mined first
+            pending.hashes = [stuck.hashes, pending.hashes].concat();
+        }
+        self.state_manager
+            .track_pending(from_address, pending.clone())
+            .await?;
+
+        let outcome = self.track(from_address, signer, pending.clone()).await?;
+        self.settle(from_address, &pending, &outcome).await?;
+        Ok(outcome)
+    }
+
+    /// Sign and broadcast `tx`, returning its hash
+    async fn broadcast(&self, signer: &SigningKey, tx: &EvmTransaction) -> Result<String> {
+        let signed = tx.sign(signer)?;
+        let hash = signed.hash_hex();
+
+        let node_hash = self
+            .client
+            .send_raw_transaction(&signed.raw_hex())
+            .await
+            .with_context(|| format!("Failed to broadcast {}", hash))?;
+        if !node_hash.eq_ignore_ascii_case(&hash) {
+            warn!("Node reported hash {} for transaction {}", node_hash, hash);
+        }
+        Ok(hash)
+    }
+
+    /// The outcome of whichever of `pending`'s hashes was mined, if any
+    async fn mined(&self, pending: &PendingTx) -> Result<Option<TxOutcome>> {
+        for hash in pending.hashes.iter().rev() {
+            if let Some(receipt) = self.client.get_transaction_receipt(hash).await? {
+                let status = if receipt.success {
+                    TxStatus::Confirmed
+                } else {
+                    TxStatus::Reverted
+                };
+                return Ok(Some(TxOutcome {
+                    hash: hash.clone(),
+                    status,
+                    receipt: Some(receipt),
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Rebroadcast `pending` with bumped fees and track the replacement.
+    ///
+    /// Returns `pending` unchanged when the broadcast fails, and `None` when
+    /// its fees are already at the replacement cap or its value cannot pay
+    /// for the rise.
+    async fn replace(
+        &self,
+        from_address: &Address,
+        signer: &SigningKey,
+        pending: &PendingTx,
+    ) -> Result<Option<PendingTx>> {
+        let replacement = &self.builder.gas().replacement;
+        let stuck = EvmTransaction::from_pending(pending)?;
+        // A top-up must still deliver the shortfall; the station pays the rise
+        let Some(bumped) = stuck.bumped(
+            replacement.bump_percent,
+            replacement.max_fee_per_gas_wei()?,
+            pending.asset != GAS_TOP_UP,
+        ) else {
+            warn!(
+                "Not replacing {}: fees already at the replacement cap or above what its value can pay",
+                pending.latest_hash()
             );
-        Ok(tx)
+            return Ok(None);
+        };
+
+        match self.broadcast(signer, &bumped).await {
+            Ok(hash) => {
+                info!(
+                    "TX REPLACED: nonce {} from {} rebroadcast as {} (max fee {} wei)",
+                    pending.nonce, from_address, hash, bumped.max_fee_per_gas
+                );
+                let mut replaced = bumped.to_pending(&pending.asset, hash);
+                replaced.hashes = [pending.hashes.clone(), replaced.hashes].concat();
+                self.state_manager
+                    .track_pending(from_address, replaced.clone())
+                    .await?;
+                Ok(Some(replaced))
+            }
+            // The original may have been mined in the meantime
+            Err(e) => {
+                warn!("Failed to replace {}: {:#}", pending.latest_hash(), e);
+                Ok(Some(pending.clone()))
+            }
+        }
+    }
+
+    /// Settle the transactions still tracked for `address` from earlier
+    /// sweeps or runs that have been mined since, and bump the fees of those
+    /// stuck for `stuck_after_seconds`, so new sweeps do not queue behind
+    /// them at stale fees
+    async fn resume_pending(&self, address: &Address, signer: &SigningKey) -> Result<()> {
+        let stuck_after =
+            chrono::Duration::seconds(self.builder.gas().replacement.stuck_after_seconds as i64);
+        for pending in self.state_manager.pending_transactions(address).await {
+            if let Some(outcome) = self.mined(&pending).await? {
+                info!(
+                    "TX SETTLED: nonce {} from {} is {:?} as {}",
+                    pending.nonce, address, outcome.status, outcome.hash
+                );
+                self.settle(address, &pending, &outcome).await?;
+                continue;
+            }
+            // An unreadable timestamp counts as stuck rather than never bumping
+            let stuck = chrono::DateTime::parse_from_rfc3339(&pending.broadcast_at)
+                .map_or(true, |at| chrono::Utc::now() - at.to_utc() >= stuck_after);
+            if stuck {
+                self.replace(address, signer, &pending).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Poll until one of the pending hashes is mined, all are dropped, or the
+    /// timeout expires, replacing the transaction whenever it looks stuck
+    async fn track(
+        &self,
+        from_address: &Address,
+        signer: &SigningKey,
+        mut pending: PendingTx,
+    ) -> Result<TxOutcome> {
+        let stuck_after = Duration::from_secs(self.builder.gas().replacement.stuck_after_seconds);
+        let deadline = Instant::now() + self.polling.timeout;
+        let mut last_broadcast = Instant::now();
+        let mut at_fee_cap = false;
+
+        loop {
+            if let Some(outcome) = self.mined(&pending).await? {
+                return Ok(outcome);
+            }
+
+            if Instant::now() >= deadline {
+                for hash in pending.hashes.iter().rev() {
+                    if self.client.transaction_known(hash).await? {
+                        return Ok(TxOutcome {
+                            hash: hash.clone(),
+                            status: TxStatus::Pending,
+                            receipt: None,
+                        });
+                    }
+                }
+                return Ok(TxOutcome {
+                    hash: pending.latest_hash().to_string(),
+                    status: TxStatus::Dropped,
+                    receipt: None,
+                });
+            }
+
+            if !at_fee_cap && last_broadcast.elapsed() >= stuck_after {
+                last_broadcast = Instant::now();
+                match self.replace(from_address, signer, &pending).await? {
+                    Some(replaced) => pending = replaced,
+                    None => at_fee_cap = true,
+                }
+            }
+
+            sleep(self.polling.interval).await;
+        }
+    }
+
+    /// Record the outcome and stop tracking the nonce once it is settled
+    async fn settle(
+        &self,
+        from_address: &Address,
+        pending: &PendingTx,
+        outcome: &TxOutcome,
+    ) -> Result<()> {
+        let now = chrono::Utc::now().to_rfc3339();
+        let mined = outcome.receipt.is_some();
+
+        if mined {
+            for hash in pending.hashes.iter().filter(|hash| **hash != outcome.hash) {
+                self.state_manager
+                    .record_transaction(
+                        from_address,
+                        TxRecord {
+                            hash: hash.clone(),
+                            nonce: pending.nonce,
+                            asset: pending.asset.clone(),
+                            status: TxStatus::Replaced,
+                            block_number: None,
+                            gas_used: None,
+                            recorded_at: now.clone(),
+                        },
+                    )
+                    .await?;
+            }
+        }
+
+        self.state_manager
+            .record_transaction(
+                from_address,
+                TxRecord {
+                    hash: outcome.hash.clone(),
+                    nonce: pending.nonce,
+                    asset: pending.asset.clone(),
+                    status: outcome.status,
+                    block_number: outcome.receipt.as_ref().map(|r| r.block_number),
+                    gas_used: outcome.receipt.as_ref().map(|r| r.gas_used),
+                    recorded_at: now,
+                },
+            )
+            .await?;
+
+        if outcome.status != TxStatus::Pending {
+            self.state_manager
+                .clear_pending(from_address, pending.nonce)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TxEmitter for EvmTxEmitter {
+    fn name(&self) -> &str {
+        "evm"
+    }
+
+    async fn emit_sweep(
+        &self,
+        from_address: &Address,
+        decision: &SweepDecision,
+    ) -> Result<SubmittedTx> {
+        let signer = self.keystore.signer(from_address)?;
+        let destination = self.destinations.resolve(decision)?;
+        self.resume_pending(from_address, signer).await?;
+        if decision.token_address.is_some() {
+            self.ensure_token_gas(from_address).await?;
+        }
+        let reservation = self.state_manager.reserve_nonce(from_address).await?;
+        let nonce = reservation.nonce();
+
+        let evm_tx = self.builder.build_sweep(nonce, &destination, decision)?;
+
+        let tx = MockTransaction {
+            from: from_address.clone(),
+            to: destination.clone(),
+            value: decision.amount,
+            asset: decision.asset.clone(),
+            nonce,
+            token_address: decision.token_address.clone(),
+        };
+        info!("GENERATING TX: {}", tx.format_log());
+
+        let hash = self.broadcast(signer, &evm_tx).await?;
+        // The node accepted the transaction, so the nonce is now spent
+        reservation
+            .commit_sweep_to(decision, &destination)
+            .await?;
+        info!(
+            "SWEEP BROADCAST: {} {} from {} to {} (hash={})",
+            decision.amount, decision.asset, from_address, destination, hash
+        );
+
+        let pending = evm_tx.to_pending(&decision.asset, hash);
+        self.state_manager
+            .track_pending(from_address, pending.clone())
+            .await?;
+        let outcome = self.track(from_address, signer, pending.clone()).await?;
+        self.settle(from_address, &pending, &outcome).await?;
+
+        let hash = outcome.hash;
+        match (outcome.status, &outcome.receipt) {
+            (TxStatus::Confirmed, Some(receipt)) => info!(
+                "SWEEP CONFIRMED: {} in block {} (gas used {})",
+                hash, receipt.block_number, receipt.gas_used
+            ),
+            (TxStatus::Reverted, Some(receipt)) => warn!(
+                "SWEEP REVERTED: {} in block {} (gas used {})",
+                hash, receipt.block_number, receipt.gas_used
+            ),
+            (TxStatus::Pending, _) => warn!(
+                "SWEEP PENDING: {} not mined after {}s",
+                hash,
+                self.polling.timeout.as_secs()
+            ),
+            _ => bail!("Transaction {} was dropped by the node", hash),
+        }
+
+        Ok(SubmittedTx {
+            hash: Some(hash),
+            tx,
+        })
     }
 }
@@ -1,8 +1,15 @@
 //! Core data types for the Treasury Sweeper Service
+use crate::amount::{Amount, MAX_DECIMALS, NATIVE_DECIMALS, U256};
+use crate::destinations::Destinations;
+use crate::rpc::BlockTag;
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
 #[allow(unused)]
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
 
 pub type Address = String;
 
@@ -11,6 +18,640 @@ pub struct Config {
     pub treasury_address: Address,
     pub hot_wallets: Vec<HotWalletConfig>,
     pub sweep_interval_seconds: u64,
+    #[serde(default)]
+    pub balance_source: BalanceSourceConfig,
+    #[serde(default)]
+    pub emitter: EmitterConfig,
+    #[serde(default)]
+    pub gas: GasConfig,
+    /// Compare stored nonces with the chain before sweeping; off when absent
+    #[serde(default)]
+    pub nonce_reconciliation: Option<NonceReconciliationConfig>,
+    /// Prices for USD thresholds; required when a rule uses one
+    #[serde(default)]
+    pub price_oracle: Option<PriceOracleConfig>,
+    /// Limits on sweep amounts and counts, checked before each sweep
+    #[serde(default)]
+    pub caps: Vec<SweepCap>,
+    /// Sweeps that wait for manual approval
+    #[serde(default)]
+    pub approval: Option<ApprovalConfig>,
+    /// Destination per asset, instead of `treasury_address`
+    #[serde(default)]
+    pub routes: HashMap<String, Address>,
+    /// The only addresses sweeps may be sent to; just `treasury_address` when
+    /// empty
+    #[serde(default)]
+    pub allowed_destinations: Vec<Address>,
+    /// Wallets checked at the same time; each wallet's rules still run in order
+    #[serde(default = "default_max_parallel_wallets")]
+    pub max_parallel_wallets: usize,
+    #[serde(default)]
+    pub timeouts: TimeoutConfig,
+}
+
+fn default_max_parallel_wallets() -> usize {
+    8
+}
+
+/// Limits on how long balance queries, wallet checks and cycles may take
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeoutConfig {
+    /// Each balance query
+    #[serde(default = "default_balance_timeout_seconds")]
+    pub balance_seconds: u64,
+    /// Each wallet's check; sweeps already started are let finish, no new
+    /// one starts after it. No limit when absent
+    #[serde(default)]
+    pub wallet_seconds: Option<u64>,
+    /// All wallet checks of a cycle together; no limit when absent
+    #[serde(default)]
+    pub cycle_seconds: Option<u64>,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self {
+            balance_seconds: default_balance_timeout_seconds(),
+            wallet_seconds: None,
+            cycle_seconds: None,
+        }
+    }
+}
+
+fn default_balance_timeout_seconds() -> u64 {
+    30
+}
+
+/// Envelope used for EVM transactions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TxType {
+    /// Type-2 transaction with `max_fee_per_gas` / `max_priority_fee_per_gas`
+    #[default]
+    Eip1559,
+    /// Legacy transaction with EIP-155 replay protection; `max_fee_per_gas`
+    /// is used as the gas price
+    Legacy,
+}
+
+/// Gas limits and fee caps for EVM transactions, fees in gwei
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GasConfig {
+    #[serde(default)]
+    pub tx_type: TxType,
+    #[serde(default = "default_native_gas_limit")]
+    pub native_gas_limit: u64,
+    #[serde(default = "default_token_gas_limit")]
+    pub token_gas_limit: u64,
+    #[serde(default = "default_max_fee_per_gas")]
+    pub max_fee_per_gas_gwei: Amount,
+    #[serde(default = "default_max_priority_fee_per_gas")]
+    pub max_priority_fee_per_gas_gwei: Amount,
+    #[serde(default)]
+    pub replacement: ReplacementConfig,
+    /// Native sweeps whose estimated fee is above this share of the swept
+    /// value are skipped (`evm` emitter only)
+    #[serde(default = "default_max_native_fee_percent")]
+    pub max_native_fee_percent: u64,
+}
+
+/// Smallest fee increase nodes accept for a same-nonce replacement
+pub const MIN_REPLACEMENT_BUMP_PERCENT: u64 = 10;
+
+/// Rebroadcasting of transactions that stay unconfirmed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplacementConfig {
+    /// Seconds without a receipt before the transaction is replaced with
+    /// higher fees
+    #[serde(default = "default_stuck_after_seconds")]
+    pub stuck_after_seconds: u64,
+    /// Fee increase per replacement, in percent
+    #[serde(default = "default_bump_percent")]
+    pub bump_percent: u64,
+    /// Highest `max_fee_per_gas` (gas price for legacy) a replacement may pay
+    #[serde(default = "default_max_replacement_fee_per_gas")]
+    pub max_fee_per_gas_gwei: Amount,
+}
+
+fn default_stuck_after_seconds() -> u64 {
+    60
+}
+
+fn default_bump_percent() -> u64 {
+    MIN_REPLACEMENT_BUMP_PERCENT
+}
+
+fn default_max_replacement_fee_per_gas() -> Amount {
+    Amount::from_units(U256::from(100u8), 0)
+}
+
+impl Default for ReplacementConfig {
+    fn default() -> Self {
+        Self {
+            stuck_after_seconds: default_stuck_after_seconds(),
+            bump_percent: default_bump_percent(),
+            max_fee_per_gas_gwei: default_max_replacement_fee_per_gas(),
+        }
+    }
+}
+
+impl ReplacementConfig {
+    pub fn max_fee_per_gas_wei(&self) -> Result<U256> {
+        gwei_to_wei(&self.max_fee_per_gas_gwei).context("Invalid replacement max_fee_per_gas_gwei")
+    }
+}
+
+fn default_max_native_fee_percent() -> u64 {
+    10
+}
+
+fn default_native_gas_limit() -> u64 {
+    21_000
+}
+
+fn default_token_gas_limit() -> u64 {
+    65_000
+}
+
+fn default_max_fee_per_gas() -> Amount {
+    Amount::from_units(U256::from(30u8), 0)
+}
+
+fn default_max_priority_fee_per_gas() -> Amount {
+    Amount::from_units(U256::from(15u8), 1)
+}
+
+impl Default for GasConfig {
+    fn default() -> Self {
+        Self {
+            tx_type: TxType::default(),
+            native_gas_limit: default_native_gas_limit(),
+            token_gas_limit: default_token_gas_limit(),
+            max_fee_per_gas_gwei: default_max_fee_per_gas(),
+            max_priority_fee_per_gas_gwei: default_max_priority_fee_per_gas(),
+            replacement: ReplacementConfig::default(),
+            max_native_fee_percent: default_max_native_fee_percent(),
+        }
+    }
+}
+
+impl GasConfig {
+    pub fn max_fee_per_gas_wei(&self) -> Result<U256> {
+        gwei_to_wei(&self.max_fee_per_gas_gwei).context("Invalid max_fee_per_gas_gwei")
+    }
+
+    pub fn max_priority_fee_per_gas_wei(&self) -> Result<U256> {
+        gwei_to_wei(&self.max_priority_fee_per_gas_gwei)
+            .context("Invalid max_priority_fee_per_gas_gwei")
+    }
+
+    /// Most a token sweep can pay in fees (gas limit × max fee), in wei
+    pub fn token_sweep_fee(&self) -> Result<U256> {
+        U256::from(self.token_gas_limit)
+            .checked_mul(self.max_fee_per_gas_wei()?)
+            .context("Token sweep fee overflows")
+    }
+
+    /// Most a native sweep can pay in fees (gas limit × max fee), in wei
+    pub fn native_sweep_fee(&self) -> Result<U256> {
+        U256::from(self.native_gas_limit)
+            .checked_mul(self.max_fee_per_gas_wei()?)
+            .context("Native sweep fee overflows")
+    }
+}
+
+/// Convert a gwei amount to wei, rejecting sub-wei precision
+pub fn gwei_to_wei(gwei: &Amount) -> Result<U256> {
+    Ok(gwei.rescale(GWEI_DECIMALS)?.units())
+}
+
+const GWEI_DECIMALS: u8 = 9;
+
+/// Which backend turns sweep decisions into transactions
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EmitterConfig {
+    /// Reserve nonces and log transactions without sending them
+    #[default]
+    Mock,
+    /// Log the transactions that would be sent, leaving nonces untouched
+    DryRun,
+    /// Sign locally and broadcast to an EVM node, then track the receipt
+    Evm {
+        rpc_url: String,
+        chain_id: u64,
+        /// JSON file mapping hot wallet addresses to private keys
+        keystore: PathBuf,
+        #[serde(default = "default_receipt_poll_interval_ms")]
+        receipt_poll_interval_ms: u64,
+        #[serde(default = "default_receipt_timeout_seconds")]
+        receipt_timeout_seconds: u64,
+        /// Wallet that funds gas for token sweeps from hot wallets without ETH
+        #[serde(default)]
+        gas_station: Option<GasStationConfig>,
+    },
+}
+
+/// Funding wallet for hot wallets that cannot pay for a token sweep
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GasStationConfig {
+    /// Sender of top-ups; its key must be in the keystore
+    pub address: Address,
+    /// Native amount sent per top-up (e.g. "0.01"); defaults to the
+    /// shortfall for one token sweep. Never less than the shortfall.
+    #[serde(default)]
+    pub top_up_amount: Option<Amount>,
+}
+
+impl GasStationConfig {
+    pub fn top_up_amount_wei(&self) -> Result<Option<U256>> {
+        self.top_up_amount
+            .map(|amount| amount.rescale(NATIVE_DECIMALS).map(|amount| amount.units()))
+            .transpose()
+            .context("Invalid gas station top_up_amount")
+    }
+}
+
+fn default_receipt_poll_interval_ms() -> u64 {
+    2_000
+}
+
+fn default_receipt_timeout_seconds() -> u64 {
+    180
+}
+
+/// What to do when a wallet's stored nonce disagrees with the chain
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoncePolicy {
+    /// Overwrite the stored nonce with the chain's pending transaction count
+    Adopt,
+    /// Stop sweeping the wallet until the nonces agree again
+    #[default]
+    Halt,
+    /// Log the discrepancy and keep sweeping with the stored nonce
+    Alert,
+}
+
+/// Nonce reconciliation against `eth_getTransactionCount(addr, "pending")`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NonceReconciliationConfig {
+    #[serde(default)]
+    pub policy: NoncePolicy,
+    /// Node to query, defaults to the `evm` emitter's `rpc_url`
+    #[serde(default)]
+    pub rpc_url: Option<String>,
+}
+
+/// Which balance source the rules engine queries
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BalanceSourceConfig {
+    /// Random balances in `[min, max)`, for local runs without a node
+    Dummy { min: f64, max: f64 },
+    /// Ethereum JSON-RPC node (`eth_getBalance` / ERC-20 `balanceOf`)
+    Rpc {
+        url: String,
+        #[serde(default)]
+        block: BlockTag,
+        /// Retries of transient failures such as timeouts and rate limits
+        #[serde(default)]
+        retry: RetryPolicy,
+    },
+}
+
+/// How a balance source retries transient failures: capped exponential
+/// backoff with jitter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// Attempts per query, the first one included; 1 disables retries
+    #[serde(default = "default_retry_max_attempts")]
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for each retry after it
+    #[serde(default = "default_retry_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    /// Longest delay between two attempts, before jitter
+    #[serde(default = "default_retry_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+    /// How long one attempt may take before it counts as a transient
+    /// failure; keep it well below `timeouts.balance_seconds`, which bounds
+    /// all attempts together
+    #[serde(default = "default_retry_attempt_timeout_ms")]
+    pub attempt_timeout_ms: u64,
+}
+
+fn default_retry_max_attempts() -> u32 {
+    3
+}
+
+fn default_retry_initial_backoff_ms() -> u64 {
+    250
+}
+
+fn default_retry_max_backoff_ms() -> u64 {
+    5_000
+}
+
+fn default_retry_attempt_timeout_ms() -> u64 {
+    5_000
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_retry_max_attempts(),
+            initial_backoff_ms: default_retry_initial_backoff_ms(),
+            max_backoff_ms: default_retry_max_backoff_ms(),
+            attempt_timeout_ms: default_retry_attempt_timeout_ms(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn attempt_timeout(&self) -> Duration {
+        Duration::from_millis(self.attempt_timeout_ms)
+    }
+
+    /// Delay before retry number `retry` (1 for the first), without jitter
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 1u64
+            .checked_shl(retry.saturating_sub(1))
+            .unwrap_or(u64::MAX);
+        let backoff_ms = self
+            .initial_backoff_ms
+            .saturating_mul(factor)
+            .min(self.max_backoff_ms);
+        Duration::from_millis(backoff_ms)
+    }
+}
+
+/// What to do with a sweep that would exceed a cap
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CapAction {
+    /// Sweep only what the cap still allows
+    Clip,
+    /// Do not send the sweep; it waits for approval
+    #[default]
+    Hold,
+}
+
+/// Limit on the sweeps of one wallet and/or asset.
+///
+/// Without `wallet` the cap covers every wallet together; without `asset` it
+/// covers every asset, which only `max_sweeps_per_day` can count. Amounts are
+/// in the asset's whole units ("1.5" ETH). Totals and counts are over a
+/// rolling 24 hours.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SweepCap {
+    #[serde(default)]
+    pub wallet: Option<Address>,
+    #[serde(default)]
+    pub asset: Option<String>,
+    #[serde(default)]
+    pub max_per_sweep: Option<Amount>,
+    #[serde(default)]
+    pub max_per_24h: Option<Amount>,
+    #[serde(default)]
+    pub max_sweeps_per_day: Option<u64>,
+    #[serde(default)]
+    pub on_exceed: CapAction,
+}
+
+impl SweepCap {
+    /// Whether the cap covers a sweep of `asset` from `wallet`
+    pub fn applies_to(&self, wallet: &Address, asset: &str) -> bool {
+        self.wallet
+            .as_ref()
+            .is_none_or(|cap_wallet| cap_wallet == wallet)
+            && self
+                .asset
+                .as_deref()
+                .is_none_or(|cap_asset| cap_asset == asset)
+    }
+
+    /// Short description for logs, e.g. "ETH cap on 0x..."
+    pub fn describe(&self) -> String {
+        format!(
+            "{} cap on {}",
+            self.asset.as_deref().unwrap_or("sweep"),
+            self.wallet.as_deref().unwrap_or("all wallets")
+        )
+    }
+}
+
+/// Manual approval of large sweeps
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApprovalConfig {
+    /// Sweeps of an asset above its amount here wait for approval
+    #[serde(default)]
+    pub above: HashMap<String, Amount>,
+    /// Queued sweeps not executed within this long expire
+    #[serde(default = "default_approval_ttl_seconds")]
+    pub ttl_seconds: u64,
+}
+
+impl Default for ApprovalConfig {
+    fn default() -> Self {
+        Self {
+            above: HashMap::new(),
+            ttl_seconds: default_approval_ttl_seconds(),
+        }
+    }
+}
+
+fn default_approval_ttl_seconds() -> u64 {
+    86400
+}
+
+/// Where USD prices are read from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PriceOracleConfig {
+    /// JSON file of prices keyed by asset, re-read on every lookup
+    Static {
+        path: PathBuf,
+        #[serde(default = "default_max_price_age_seconds")]
+        max_age_seconds: u64,
+    },
+    /// HTTP service answering `GET {url}/{asset}`
+    Http {
+        url: String,
+        #[serde(default = "default_max_price_age_seconds")]
+        max_age_seconds: u64,
+    },
+}
+
+impl PriceOracleConfig {
+    /// Prices older than this make USD rules refuse to fire
+    pub fn max_age_seconds(&self) -> u64 {
+        match self {
+            PriceOracleConfig::Static {
+                max_age_seconds, ..
+            }
+            | PriceOracleConfig::Http {
+                max_age_seconds, ..
+            } => *max_age_seconds,
+        }
+    }
+}
+
+fn default_max_price_age_seconds() -> u64 {
+    300
+}
+
+impl Config {
+    /// Parse a config file and validate it
+    pub fn from_json(content: &str) -> Result<Self> {
+        let config: Config = serde_json::from_str(content)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Check that every rule is valid for its asset's decimals and that gas
+    /// fees are valid
+    pub fn validate(&self) -> Result<()> {
+        let max_fee = self.gas.max_fee_per_gas_wei()?;
+        self.gas.max_priority_fee_per_gas_wei()?;
+        if self.gas.replacement.bump_percent < MIN_REPLACEMENT_BUMP_PERCENT {
+            bail!(
+                "Replacement bump_percent must be at least {}",
+                MIN_REPLACEMENT_BUMP_PERCENT
+            );
+        }
+        if let EmitterConfig::Evm {
+            gas_station: Some(gas_station),
+            ..
+        } = &self.emitter
+        {
+            gas_station.top_up_amount_wei()?;
+        }
+        if let BalanceSourceConfig::Rpc { retry, .. } = &self.balance_source {
+            if retry.max_attempts == 0 {
+                bail!("balance_source retry max_attempts must be at least 1");
+            }
+            if retry.initial_backoff_ms > retry.max_backoff_ms {
+                bail!("balance_source retry initial_backoff_ms is above max_backoff_ms");
+            }
+            if retry.attempt_timeout_ms == 0
+                || retry.attempt_timeout_ms >= self.timeouts.balance_seconds.saturating_mul(1_000)
+            {
+                bail!(
+                    "balance_source retry attempt_timeout_ms must be above 0 and below \
+                     timeouts.balance_seconds"
+                );
+            }
+        }
+        if self.max_parallel_wallets == 0 {
+            bail!("max_parallel_wallets must be at least 1");
+        }
+        let timeouts = &self.timeouts;
+        if timeouts.balance_seconds == 0
+            || timeouts.wallet_seconds == Some(0)
+            || timeouts.cycle_seconds == Some(0)
+        {
+            bail!("Timeouts must be at least 1 second");
+        }
+        if self.gas.max_native_fee_percent > 100 {
+            bail!("max_native_fee_percent must be at most 100");
+        }
+        if self.gas.replacement.max_fee_per_gas_wei()? < max_fee {
+            bail!("Replacement max_fee_per_gas_gwei is below max_fee_per_gas_gwei");
+        }
+
+        let allowlist = Destinations::from_config(self);
+        let destinations = std::iter::once(&self.treasury_address)
+            .chain(self.routes.values())
+            .chain(
+                self.hot_wallets
+                    .iter()
+                    .flat_map(|wallet| &wallet.rules)
+                    .flat_map(|rule| {
+                        rule.destination()
+                            .into_iter()
+                            .chain(rule.split_destinations().iter().map(|leg| &leg.address))
+                    }),
+            );
+        for destination in destinations {
+            if !allowlist.is_allowed(destination) {
+                bail!("Destination {} is not in allowed_destinations", destination);
+            }
+        }
+
+        for cap in &self.caps {
+            if cap.asset.is_none() && (cap.max_per_sweep.is_some() || cap.max_per_24h.is_some()) {
+                bail!("{} limits amounts, so it needs an asset", cap.describe());
+            }
+        }
+
+        for wallet in &self.hot_wallets {
+            for rule in &wallet.rules {
+                rule.validate().with_context(|| {
+                    format!(
+                        "Invalid {} rule on wallet {} ({})",
+                        rule.asset(),
+                        wallet.address,
+                        wallet.label
+                    )
+                })?;
+                if let Some(limit) = self
+                    .approval
+                    .as_ref()
+                    .and_then(|approval| approval.above.get(rule.asset()))
+                {
+                    limit.rescale(rule.decimals()).with_context(|| {
+                        format!(
+                            "Invalid {} approval amount for {} decimals",
+                            rule.asset(),
+                            rule.decimals()
+                        )
+                    })?;
+                }
+                for cap in &self.caps {
+                    if !cap.applies_to(&wallet.address, rule.asset()) {
+                        continue;
+                    }
+                    for limit in [&cap.max_per_sweep, &cap.max_per_24h].into_iter().flatten() {
+                        limit.rescale(rule.decimals()).with_context(|| {
+                            format!(
+                                "Invalid {} for {} decimals",
+                                cap.describe(),
+                                rule.decimals()
+                            )
+                        })?;
+                    }
+                }
+                // The evm emitter sends native amounts and pays their fees in wei
+                if let EmitterConfig::Evm { .. } = self.emitter
+                    && rule.token_address().is_none()
+                    && rule.decimals() != NATIVE_DECIMALS
+                {
+                    bail!(
+                        "{} rule on wallet {} has {} decimals, but native assets have {} with the evm emitter",
+                        rule.asset(),
+                        wallet.address,
+                        rule.decimals(),
+                        NATIVE_DECIMALS
+                    );
+                }
+                if rule.usd_threshold().is_some() && self.price_oracle.is_none() {
+                    bail!(
+                        "{} rule on wallet {} has a USD threshold but no price_oracle is configured",
+                        rule.asset(),
+                        wallet.address
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for BalanceSourceConfig {
+    fn default() -> Self {
+        BalanceSourceConfig::Dummy { min: 0.0, max: 4.0 }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,6 +659,9 @@ pub struct HotWalletConfig {
     pub address: Address,
     pub label: String,
     pub rules: Vec<SweepRule>,
+    /// Default `cooldown_seconds` for the wallet's rules
+    #[serde(default)]
+    pub cooldown_seconds: Option<u64>,
 }
 
 // Dummy eth address generator
@@ -29,25 +673,461 @@ pub fn generate_eth_address() -> String {
     format!("0x{}", hex::encode(bytes))
 }
 
+/// Thresholds are human-readable amounts ("0.1") interpreted with the rule's
+/// `decimals`: 18 by default for native assets, and 0 (base units) by default
+/// for tokens. A threshold can also be given in USD (`{"usd": "5000"}`) and
+/// is converted with the price oracle when the rule is evaluated.
+///
+/// The `*_balance` rules sweep the whole balance; the `*_excess` rules sweep
+/// only what is above `retain_amount`, leaving a float in the hot wallet.
+///
+/// A rule with `cooldown_seconds` (or on a wallet with one) does not sweep its
+/// asset again until that long after the wallet's last sweep of the asset.
+/// `destination` sends the rule's sweeps somewhere other than the asset's
+/// route or the treasury; `destinations` splits each sweep between several
+/// addresses instead.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum SweepRule {
     #[serde(rename = "native_balance")]
     NativeBalance {
-        threshold: String,
+        threshold: Threshold,
         asset: String, //eth,sol,dot etc
+        #[serde(default = "native_decimals")]
+        decimals: u8,
+        #[serde(default)]
+        cooldown_seconds: Option<u64>,
+        #[serde(default)]
+        destination: Option<Address>,
+        #[serde(default)]
+        destinations: Vec<SplitDestination>,
     },
 
     #[serde(rename = "token_balance")]
     TokenBalance {
-        threshold: String,
+        threshold: Threshold,
         token_address: Address,
         asset: String, //usdc,usdt,dai etc
+        #[serde(default)]
+        decimals: u8,
+        #[serde(default)]
+        cooldown_seconds: Option<u64>,
+        #[serde(default)]
+        destination: Option<Address>,
+        #[serde(default)]
+        destinations: Vec<SplitDestination>,
+    },
+
+    #[serde(rename = "native_excess")]
+    NativeExcess {
+        trigger_threshold: Threshold,
+        retain_amount: Amount,
+        asset: String,
+        #[serde(default = "native_decimals")]
+        decimals: u8,
+        #[serde(default)]
+        cooldown_seconds: Option<u64>,
+        #[serde(default)]
+        destination: Option<Address>,
+        #[serde(default)]
+        destinations: Vec<SplitDestination>,
+    },
+
+    #[serde(rename = "token_excess")]
+    TokenExcess {
+        trigger_threshold: Threshold,
+        retain_amount: Amount,
+        token_address: Address,
+        asset: String,
+        #[serde(default)]
+        decimals: u8,
+        #[serde(default)]
+        cooldown_seconds: Option<u64>,
+        #[serde(default)]
+        destination: Option<Address>,
+        #[serde(default)]
+        destinations: Vec<SplitDestination>,
+    },
+
+    /// Sweep `asset` (native unless `token_address` is set) when `condition`
+    /// holds, keeping `retain_amount` if given
+    #[serde(rename = "composite")]
+    Composite {
+        condition: Condition,
+        asset: String,
+        #[serde(default)]
+        token_address: Option<Address>,
+        decimals: u8,
+        #[serde(default)]
+        retain_amount: Option<Amount>,
+        #[serde(default)]
+        cooldown_seconds: Option<u64>,
+        #[serde(default)]
+        destination: Option<Address>,
+        #[serde(default)]
+        destinations: Vec<SplitDestination>,
     },
 }
 
+/// One leg of a split sweep: `bps` basis points of the amount go to `address`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SplitDestination {
+    pub address: Address,
+    pub bps: u16,
+    /// Receives the rounding dust; the first destination when none is marked
+    #[serde(default)]
+    pub primary: bool,
+}
+
+/// Basis points in a whole split sweep
+pub const TOTAL_BPS: u16 = 10_000;
+
+/// Trigger level of a rule: an amount of the asset, or a USD value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum Threshold {
+    Amount(Amount),
+    Usd { usd: Amount },
+}
+
+impl<'de> Deserialize<'de> for Threshold {
+    /// A string is an asset amount, `{"usd": "..."}` a USD value; written by
+    /// hand so that malformed amounts keep their parse error
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        struct ThresholdVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for ThresholdVisitor {
+            type Value = Threshold;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("an amount string or {\"usd\": amount}")
+            }
+
+            fn visit_str<E: serde::de::Error>(
+                self,
+                value: &str,
+            ) -> std::result::Result<Threshold, E> {
+                value.parse().map(Threshold::Amount).map_err(E::custom)
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(
+                self,
+                map: A,
+            ) -> std::result::Result<Threshold, A::Error> {
+                #[derive(Deserialize)]
+                #[serde(deny_unknown_fields)]
+                struct Usd {
+                    usd: Amount,
+                }
+                let Usd { usd } =
+                    Usd::deserialize(serde::de::value::MapAccessDeserializer::new(map))?;
+                Ok(Threshold::Usd { usd })
+            }
+        }
+
+        deserializer.deserialize_any(ThresholdVisitor)
+    }
+}
+
+impl From<Amount> for Threshold {
+    fn from(amount: Amount) -> Self {
+        Threshold::Amount(amount)
+    }
+}
+
+impl std::str::FromStr for Threshold {
+    type Err = anyhow::Error;
+
+    /// Parse an asset amount threshold
+    fn from_str(value: &str) -> Result<Self> {
+        Ok(Threshold::Amount(value.parse()?))
+    }
+}
+
+/// Condition tree of a composite rule, e.g.
+/// `{"any": [{"balance": {...}}, {"all": [...]}]}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    /// Every condition holds (AND)
+    All(Vec<Condition>),
+    /// At least one condition holds (OR)
+    Any(Vec<Condition>),
+    Not(Box<Condition>),
+    /// The wallet's balance of an asset (native when `token_address` is
+    /// absent) is strictly above `above` and/or strictly below `below`
+    Balance {
+        #[serde(default)]
+        token_address: Option<Address>,
+        decimals: u8,
+        #[serde(default)]
+        above: Option<Amount>,
+        #[serde(default)]
+        below: Option<Amount>,
+    },
+    /// The wallet's last sweep is older than `seconds`, or it never swept
+    LastSweepOlderThan {
+        seconds: u64,
+    },
+}
+
+impl Condition {
+    /// Check amounts against their decimals and reject empty groups
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Condition::All(conditions) | Condition::Any(conditions) => {
+                if conditions.is_empty() {
+                    bail!("all/any needs at least one condition");
+                }
+                conditions.iter().try_for_each(Condition::validate)
+            }
+            Condition::Not(condition) => condition.validate(),
+            Condition::Balance {
+                decimals,
+                above,
+                below,
+                ..
+            } => {
+                if above.is_none() && below.is_none() {
+                    bail!("balance condition needs above or below");
+                }
+                for bound in [above, below].into_iter().flatten() {
+                    bound.rescale(*decimals)?;
+                }
+                Ok(())
+            }
+            Condition::LastSweepOlderThan { .. } => Ok(()),
+        }
+    }
+
+    /// Balances the condition reads: `None` for native, token addresses otherwise
+    pub fn balances_needed(&self, needed: &mut Vec<Option<Address>>) {
+        match self {
+            Condition::All(conditions) | Condition::Any(conditions) => {
+                for condition in conditions {
+                    condition.balances_needed(needed);
+                }
+            }
+            Condition::Not(condition) => condition.balances_needed(needed),
+            Condition::Balance { token_address, .. } => {
+                if !needed.contains(token_address) {
+                    needed.push(token_address.clone());
+                }
+            }
+            Condition::LastSweepOlderThan { .. } => {}
+        }
+    }
+
+    pub fn needs_last_sweep(&self) -> bool {
+        match self {
+            Condition::All(conditions) | Condition::Any(conditions) => {
+                conditions.iter().any(Condition::needs_last_sweep)
+            }
+            Condition::Not(condition) => condition.needs_last_sweep(),
+            Condition::Balance { .. } => false,
+            Condition::LastSweepOlderThan { .. } => true,
+        }
+    }
+}
+
+fn native_decimals() -> u8 {
+    NATIVE_DECIMALS
+}
+
+impl SweepRule {
+    pub fn asset(&self) -> &str {
+        match self {
+            SweepRule::NativeBalance { asset, .. }
+            | SweepRule::TokenBalance { asset, .. }
+            | SweepRule::NativeExcess { asset, .. }
+            | SweepRule::TokenExcess { asset, .. }
+            | SweepRule::Composite { asset, .. } => asset,
+        }
+    }
+
+    pub fn decimals(&self) -> u8 {
+        match self {
+            SweepRule::NativeBalance { decimals, .. }
+            | SweepRule::TokenBalance { decimals, .. }
+            | SweepRule::NativeExcess { decimals, .. }
+            | SweepRule::TokenExcess { decimals, .. }
+            | SweepRule::Composite { decimals, .. } => *decimals,
+        }
+    }
+
+    /// Cooldown set on the rule itself
+    pub fn cooldown_seconds(&self) -> Option<u64> {
+        match self {
+            SweepRule::NativeBalance {
+                cooldown_seconds, ..
+            }
+            | SweepRule::TokenBalance {
+                cooldown_seconds, ..
+            }
+            | SweepRule::NativeExcess {
+                cooldown_seconds, ..
+            }
+            | SweepRule::TokenExcess {
+                cooldown_seconds, ..
+            }
+            | SweepRule::Composite {
+                cooldown_seconds, ..
+            } => *cooldown_seconds,
+        }
+    }
+
+    /// Destination set on the rule itself
+    pub fn destination(&self) -> Option<&Address> {
+        match self {
+            SweepRule::NativeBalance { destination, .. }
+            | SweepRule::TokenBalance { destination, .. }
+            | SweepRule::NativeExcess { destination, .. }
+            | SweepRule::TokenExcess { destination, .. }
+            | SweepRule::Composite { destination, .. } => destination.as_ref(),
+        }
+    }
+
+    /// Destinations the rule splits its sweeps between, empty when it does not
+    pub fn split_destinations(&self) -> &[SplitDestination] {
+        match self {
+            SweepRule::NativeBalance { destinations, .. }
+            | SweepRule::TokenBalance { destinations, .. }
+            | SweepRule::NativeExcess { destinations, .. }
+            | SweepRule::TokenExcess { destinations, .. }
+            | SweepRule::Composite { destinations, .. } => destinations,
+        }
+    }
+
+    /// Token contract for token rules, `None` for native ones
+    pub fn token_address(&self) -> Option<&Address> {
+        match self {
+            SweepRule::TokenBalance { token_address, .. }
+            | SweepRule::TokenExcess { token_address, .. } => Some(token_address),
+            SweepRule::Composite { token_address, .. } => token_address.as_ref(),
+            SweepRule::NativeBalance { .. } | SweepRule::NativeExcess { .. } => None,
+        }
+    }
+
+    /// Name used for the rule in config and sweep decisions
+    pub fn rule_type(&self) -> &'static str {
+        match self {
+            SweepRule::NativeBalance { .. } => "native_balance",
+            SweepRule::TokenBalance { .. } => "token_balance",
+            SweepRule::NativeExcess { .. } => "native_excess",
+            SweepRule::TokenExcess { .. } => "token_excess",
+            SweepRule::Composite { .. } => "composite",
+        }
+    }
+
+    /// Threshold or trigger threshold; `None` for composite rules, which
+    /// trigger on their condition instead
+    pub fn threshold(&self) -> Option<&Threshold> {
+        match self {
+            SweepRule::NativeBalance { threshold, .. }
+            | SweepRule::TokenBalance { threshold, .. } => Some(threshold),
+            SweepRule::NativeExcess {
+                trigger_threshold, ..
+            }
+            | SweepRule::TokenExcess {
+                trigger_threshold, ..
+            } => Some(trigger_threshold),
+            SweepRule::Composite { .. } => None,
+        }
+    }
+
+    /// Threshold expressed in the asset's base units; `None` for composite
+    /// rules and USD thresholds, which need a price first
+    pub fn threshold_units(&self) -> Result<Option<Amount>> {
+        match self.threshold() {
+            Some(Threshold::Amount(threshold)) => Ok(Some(threshold.rescale(self.decimals())?)),
+            Some(Threshold::Usd { .. }) | None => Ok(None),
+        }
+    }
+
+    /// USD value the balance must exceed, for rules with a USD threshold
+    pub fn usd_threshold(&self) -> Option<&Amount> {
+        match self.threshold() {
+            Some(Threshold::Usd { usd }) => Some(usd),
+            Some(Threshold::Amount(_)) | None => None,
+        }
+    }
+
+    /// Amount left in the wallet after a sweep, in the asset's base units;
+    /// `None` when the rule sweeps the whole balance
+    pub fn retain_units(&self) -> Result<Option<Amount>> {
+        match self {
+            SweepRule::NativeExcess { retain_amount, .. }
+            | SweepRule::TokenExcess { retain_amount, .. } => {
+                Ok(Some(retain_amount.rescale(self.decimals())?))
+            }
+            SweepRule::Composite { retain_amount, .. } => retain_amount
+                .map(|retain| retain.rescale(self.decimals()))
+                .transpose(),
+            SweepRule::NativeBalance { .. } | SweepRule::TokenBalance { .. } => Ok(None),
+        }
+    }
+
+    /// Check that the asset's decimals are supported, that the amounts fit
+    /// them and that the retained float does not exceed the trigger
+    pub fn validate(&self) -> Result<()> {
+        if self.decimals() > MAX_DECIMALS {
+            bail!(
+                "{} decimals is above the supported maximum of {}",
+                self.decimals(),
+                MAX_DECIMALS
+            );
+        }
+        let threshold = self.threshold_units()?;
+        let retain = self.retain_units()?;
+        if let (Some(threshold), Some(retain)) = (threshold, retain)
+            && retain > threshold
+        {
+            bail!(
+                "retain_amount {} is above trigger_threshold {}",
+                retain,
+                threshold
+            );
+        }
+        if let SweepRule::Composite { condition, .. } = self {
+            condition.validate()?;
+        }
+        let split = self.split_destinations();
+        if !split.is_empty() {
+            if self.destination().is_some() {
+                bail!("A rule cannot set both destination and destinations");
+            }
+            if let Some(leg) = split.iter().find(|leg| leg.bps == 0) {
+                bail!("Split destination {} has 0 bps", leg.address);
+            }
+            let total: u32 = split.iter().map(|leg| u32::from(leg.bps)).sum();
+            if total != u32::from(TOTAL_BPS) {
+                bail!(
+                    "Split destinations add up to {} bps, not {}",
+                    total,
+                    TOTAL_BPS
+                );
+            }
+            if split.iter().filter(|leg| leg.primary).count() > 1 {
+                bail!("Only one split destination can be primary");
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServiceState {
     pub wallets: HashMap<Address, WalletState>,
+    /// Broadcast transactions still waiting to be mined, per wallet
+    #[serde(default)]
+    pub pending_transactions: HashMap<Address, Vec<PendingTx>>,
+    /// Sweeps waiting for manual approval or for the next cycle once approved
+    #[serde(default)]
+    pub approvals: Vec<PendingApproval>,
+    /// Id of the most recently queued approval
+    #[serde(default)]
+    pub last_approval_id: u64,
     pub last_update: String,
 }
 
@@ -55,6 +1135,9 @@ impl ServiceState {
     pub fn new() -> Self {
         Self {
             wallets: HashMap::new(),
+            pending_transactions: HashMap::new(),
+            approvals: Vec::new(),
+            last_approval_id: 0,
             last_update: chrono::Utc::now().to_rfc3339(),
         }
     }
@@ -71,8 +1154,19 @@ impl Default for ServiceState {
 pub struct WalletState {
     pub address: Address,
     pub next_nonce: u64,
-    pub last_sweep_timestamp: Option<String>,
+    /// When each asset was last swept from this wallet
+    #[serde(default)]
+    pub last_sweeps: HashMap<String, DateTime<Utc>>,
+    /// Sweeps committed in the last 24 hours, oldest first, for sweep caps
+    #[serde(default)]
+    pub sweep_history: Vec<SweepRecord>,
     pub total_sweeps: u64,
+    /// Most recent broadcast transactions and their outcome, oldest first
+    #[serde(default)]
+    pub transactions: Vec<TxRecord>,
+    /// Why sweeps from this wallet are stopped, `None` while it is active
+    #[serde(default)]
+    pub halted: Option<String>,
 }
 
 impl WalletState {
@@ -80,33 +1174,209 @@ impl WalletState {
         Self {
             address,
             next_nonce: 0,
-            last_sweep_timestamp: None,
+            last_sweeps: HashMap::new(),
+            sweep_history: Vec::new(),
             total_sweeps: 0,
+            transactions: Vec::new(),
+            halted: None,
         }
     }
 }
 
+/// Outcome of a broadcast transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TxStatus {
+    /// Still unconfirmed when tracking stopped
+    Pending,
+    /// Mined and executed successfully
+    Confirmed,
+    /// Mined but execution reverted
+    Reverted,
+    /// No longer known to the node
+    Dropped,
+    /// Superseded by a transaction at the same nonce
+    Replaced,
+}
+
+/// A broadcast EVM transaction that has not been mined yet.
+///
+/// Holds everything needed to re-sign it at the same nonce with higher fees.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingTx {
+    pub nonce: u64,
+    pub asset: String,
+    pub tx_type: TxType,
+    pub chain_id: u64,
+    /// Transaction recipient (the token contract for token sweeps)
+    pub to: Address,
+    pub value: U256,
+    /// Hex-encoded calldata
+    pub data: String,
+    pub gas_limit: u64,
+    pub max_fee_per_gas: U256,
+    pub max_priority_fee_per_gas: U256,
+    /// Every hash broadcast at this nonce, the latest last
+    pub hashes: Vec<String>,
+    /// When the latest hash was broadcast
+    pub broadcast_at: String,
+}
+
+impl PendingTx {
+    pub fn latest_hash(&self) -> &str {
+        self.hashes.last().map(String::as_str).unwrap_or_default()
+    }
+}
+
+/// Where a queued sweep stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalStatus {
+    Pending,
+    /// Runs on the next cycle
+    Approved,
+}
+
+/// An operator's decision on a queued sweep, recorded by the `approvals`
+/// command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalDecision {
+    Approved,
+    Rejected,
+}
+
+/// A sweep held for manual approval
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingApproval {
+    pub id: u64,
+    pub wallet: Address,
+    /// The sweep's transactions: one, or every leg of a split, approved and
+    /// run together
+    pub legs: Vec<SweepDecision>,
+    /// Why the sweep was held
+    pub reason: String,
+    pub status: ApprovalStatus,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl PendingApproval {
+    pub fn asset(&self) -> &str {
+        self.legs.first().map(|leg| leg.asset.as_str()).unwrap_or_default()
+    }
+
+    /// Amount of the whole sweep, as judged against the approval amounts
+    pub fn amount(&self) -> Amount {
+        self.legs.first().map_or(Amount::zero(0), |leg| {
+            leg.split.map_or(leg.amount, |split| split.total)
+        })
+    }
+}
+
+/// A committed sweep, as counted by sweep caps
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SweepRecord {
+    pub asset: String,
+    pub amount: Amount,
+    pub swept_at: DateTime<Utc>,
+    #[serde(default)]
+    pub destination: Option<Address>,
+    /// Set when the sweep was one leg of a split
+    #[serde(default)]
+    pub split: Option<SweepSplit>,
+}
+
+/// A transaction recorded against a wallet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TxRecord {
+    pub hash: String,
+    pub nonce: u64,
+    pub asset: String,
+    pub status: TxStatus,
+    pub block_number: Option<u64>,
+    pub gas_used: Option<u64>,
+    pub recorded_at: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SweepDecision {
-    pub amount: String,
+    pub amount: Amount,
     pub asset: String,
     #[allow(dead_code)]
     pub rule_type: String,
     pub token_address: Option<Address>,
+    /// Where to send the sweep; `None` uses the asset's route or the treasury
+    #[serde(default)]
+    pub destination: Option<Address>,
+    /// The whole sweep when this is one leg of a split
+    #[serde(default)]
+    pub split: Option<SweepSplit>,
+}
+
+/// The split sweep a leg belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SweepSplit {
+    /// Amount of the whole sweep, before any per-leg fee
+    pub total: Amount,
+    pub bps: u16,
+    /// Set on the split's first leg, so the split counts as one sweep
+    #[serde(default)]
+    pub first: bool,
+}
+
+/// A triggered rule that did not produce a sweep
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkippedSweep {
+    pub asset: String,
+    pub rule_type: String,
+    pub reason: String,
 }
 
-#[derive(Debug, Clone)]
+/// How a rule came out of one evaluation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum RuleOutcome {
+    NotTriggered,
+    /// Produced `sweeps` sweep decisions (one per destination of a split)
+    Triggered { sweeps: usize },
+    /// Triggered, but nothing will be swept
+    Skipped { reason: String },
+}
+
+/// One rule's evaluation: the balance it saw, the threshold it compared it
+/// with (`None` for composite rules or when no price was available) and the
+/// outcome
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleReport {
+    pub rule_type: String,
+    pub asset: String,
+    pub balance: Amount,
+    pub threshold: Option<Amount>,
+    #[serde(flatten)]
+    pub outcome: RuleOutcome,
+}
+
+/// Handle for a transaction handed to an emitter backend
+#[derive(Debug, Clone, Serialize)]
+pub struct SubmittedTx {
+    /// Transaction hash, `None` when nothing was actually submitted (mock and
+    /// dry run)
+    pub hash: Option<String>,
+    pub tx: MockTransaction,
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct MockTransaction {
     pub from: Address,
     pub to: Address,
-    pub value: String,
+    pub value: Amount,
     pub asset: String,
     pub nonce: u64,
     pub token_address: Option<Address>,
 }
 
 impl MockTransaction {
-
     pub fn format_log(&self) -> String {
         match &self.token_address {
             Some(token) => {
@@ -0,0 +1,98 @@
+use treasury_sweeper::amount::{Amount, U256};
+use treasury_sweeper::types::Config;
+
+#[test]
+fn test_parse_exact_base_units() {
+    let amount = Amount::parse("0.1", 18).unwrap();
+    assert_eq!(amount.units(), U256::from(100_000_000_000_000_000u128));
+    assert_eq!(amount.decimals(), 18);
+
+    let amount = Amount::parse("1234.000001", 6).unwrap();
+    assert_eq!(amount.units(), U256::from(1_234_000_001u64));
+    assert_eq!(amount.to_string(), "1234.000001");
+
+    // One wei survives a round trip
+    let wei = Amount::parse("1.000000000000000001", 18).unwrap();
+    assert_eq!(wei.to_string(), "1.000000000000000001");
+}
+
+#[test]
+fn test_parse_rejects_invalid_input() {
+    for input in [
+        "", "abc", "0.1x", "-1", "+1", "1e18", " 1", "1.", ".5", "1.2.3", "0,1",
+    ] {
+        assert!(Amount::parse(input, 18).is_err(), "accepted {:?}", input);
+    }
+    // More precision than the asset supports is an error, not a rounding
+    assert!(Amount::parse("0.0000001", 6).is_err());
+}
+
+#[test]
+fn test_compare_across_decimals() {
+    let threshold: Amount = "0.1".parse().unwrap();
+    let balance = Amount::from_units(U256::from(100_000_000_000_000_001u128), 18);
+    assert!(balance > threshold);
+    assert_eq!(Amount::parse("0.1", 18).unwrap(), threshold);
+    assert!(Amount::zero(18) < threshold);
+}
+
+#[test]
+fn test_compare_above_max_decimals_does_not_panic() {
+    // 10^70 at 100 decimals is 10^-30
+    let tiny = Amount::from_units(U256::from(10u8).pow(U256::from(70u8)), 100);
+    assert_eq!(tiny, Amount::from_units(U256::from(1u8), 30));
+    assert!(tiny < Amount::from_units(U256::from(1u8), 0));
+    let just_above = Amount::from_units(tiny.units() + U256::from(1u8), 100);
+    assert!(just_above > Amount::from_units(U256::from(1u8), 30));
+    assert!(Amount::from_units(U256::from(1u8), 90) < Amount::from_units(U256::from(1u8), 80));
+}
+
+#[test]
+fn test_config_rejects_invalid_threshold() {
+    let config = |threshold: &str, decimals: u8| {
+        format!(
+            r#"{{
+                "treasury_address": "0xT",
+                "hot_wallets": [{{
+                    "address": "0xW",
+                    "label": "Hot Wallet 1",
+                    "rules": [{{"token_balance": {{
+                        "threshold": "{}",
+                        "token_address": "0xUSDC",
+                        "asset": "USDC",
+                        "decimals": {}
+                    }}}}]
+                }}],
+                "sweep_interval_seconds": 60
+            }}"#,
+            threshold, decimals
+        )
+    };
+
+    assert!(Config::from_json(&config("100.5", 6)).is_ok());
+
+    let err = Config::from_json(&config("1O0", 6)).unwrap_err();
+    assert!(err.to_string().contains("invalid amount \"1O0\""));
+
+    let err = Config::from_json(&config("0.5", 0)).unwrap_err();
+    assert!(format!("{:#}", err).contains("Invalid USDC rule on wallet 0xW"));
+}
+
+#[test]
+fn test_serde_round_trip_keeps_scale() {
+    for amount in [
+        Amount::parse("1.5", 18).unwrap(),
+        Amount::parse("2", 6).unwrap(),
+        Amount::zero(18),
+        Amount::parse("250", 0).unwrap(),
+    ] {
+        let json = serde_json::to_string(&amount).unwrap();
+        let reloaded: Amount = serde_json::from_str(&json).unwrap();
+        assert_eq!(reloaded.units(), amount.units(), "{}", json);
+        assert_eq!(reloaded.decimals(), amount.decimals(), "{}", json);
+    }
+    assert_eq!(
+        serde_json::to_string(&Amount::parse("1.5", 18).unwrap()).unwrap(),
+        r#""1.500000000000000000""#
+    );
+}
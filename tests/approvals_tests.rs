@@ -0,0 +1,466 @@
+mod common;
+
+use common::test_config;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
+use treasury_sweeper::amount::{Amount, U256};
+use treasury_sweeper::approvals::ApprovalQueue;
+use treasury_sweeper::balance_checker::BalanceSource;
+use treasury_sweeper::destinations::Destinations;
+use treasury_sweeper::monitor::WalletMonitor;
+use treasury_sweeper::rules_engine::RulesEngine;
+use treasury_sweeper::state_manager::StateManager;
+use treasury_sweeper::tx_emitter::MockTxEmitter;
+use treasury_sweeper::types::{
+    ApprovalConfig, ApprovalStatus, Config, HotWalletConfig, SplitDestination, SweepRule,
+};
+
+const WALLET: &str = "0xWallet";
+
+/// Native balance the test can change between cycles
+struct AdjustableBalance {
+    wei: Mutex<U256>,
+}
+
+impl AdjustableBalance {
+    fn set_eth(&self, eth: &str) {
+        *self.wei.lock().unwrap() = Amount::parse(eth, 18).unwrap().units();
+    }
+}
+
+#[async_trait::async_trait]
+impl BalanceSource for AdjustableBalance {
+    fn name(&self) -> &str {
+        "adjustable"
+    }
+
+    async fn check_native_balance(&self, _address: &String) -> anyhow::Result<U256> {
+        Ok(*self.wei.lock().unwrap())
+    }
+
+    async fn check_token_balance(
+        &self,
+        _address: &String,
+        _token: &String,
+    ) -> anyhow::Result<U256> {
+        Ok(U256::ZERO)
+    }
+}
+
+struct Setup {
+    monitor: WalletMonitor,
+    state_manager: Arc<StateManager>,
+    balance: Arc<AdjustableBalance>,
+    config: Config,
+    state_path: PathBuf,
+    _temp_dir: TempDir,
+}
+
+/// Monitor sweeping through `state_manager`, as one sweeper process would
+fn monitor(
+    state_manager: Arc<StateManager>,
+    balance: Arc<AdjustableBalance>,
+    approval: ApprovalConfig,
+) -> WalletMonitor {
+    let rules_engine = Arc::new(RulesEngine::new(balance));
+    let emitter = Arc::new(MockTxEmitter::new(
+        state_manager.clone(),
+        "0xTREASURY".to_string(),
+    ));
+    WalletMonitor::new(rules_engine, emitter)
+        .with_approvals(ApprovalQueue::new(approval, state_manager))
+}
+
+async fn setup(ttl_seconds: u64) -> Setup {
+    let temp_dir = TempDir::new().unwrap();
+    let state_path = temp_dir.path().join("state.json");
+    let state_manager = Arc::new(StateManager::load(state_path.clone()).await.unwrap());
+    let balance = Arc::new(AdjustableBalance {
+        wei: Mutex::new(U256::ZERO),
+    });
+    let approval = ApprovalConfig {
+        above: HashMap::from([("ETH".to_string(), "10".parse().unwrap())]),
+        ttl_seconds,
+    };
+    let monitor = monitor(state_manager.clone(), balance.clone(), approval.clone());
+
+    let config = Config {
+        approval: Some(approval),
+        ..test_config(vec![HotWalletConfig {
+            address: WALLET.to_string(),
+            label: "Test Wallet".to_string(),
+            rules: vec![SweepRule::NativeBalance {
+                threshold: "1".parse().unwrap(),
+                asset: "ETH".to_string(),
+                decimals: 18,
+                cooldown_seconds: None,
+                destination: None,
+                destinations: vec![],
+            }],
+            cooldown_seconds: None,
+        }])
+    };
+
+    Setup {
+        monitor,
+        state_manager,
+        balance,
+        config,
+        state_path,
+        _temp_dir: temp_dir,
+    }
+}
+
+#[tokio::test]
+async fn test_large_sweep_waits_for_approval() {
+    let setup = setup(3600).await;
+
+    // Below the approval amount: swept automatically
+    setup.balance.set_eth("5");
+    assert_eq!(
+        setup
+            .monitor
+            .check_all_wallets(&setup.config)
+            .await
+            .unwrap(),
+        1
+    );
+
+    // Above it: queued once, however many cycles see it
+    setup.balance.set_eth("25");
+    for _ in 0..2 {
+        assert_eq!(
+            setup
+                .monitor
+                .check_all_wallets(&setup.config)
+                .await
+                .unwrap(),
+            0
+        );
+    }
+    let approvals = setup.state_manager.approvals().await;
+    assert_eq!(approvals.len(), 1);
+    assert_eq!(approvals[0].status, ApprovalStatus::Pending);
+    assert_eq!(approvals[0].amount(), Amount::parse("25", 18).unwrap());
+    assert!(approvals[0].reason.contains("approval amount 10"));
+
+    // Approved, but the wallet no longer holds the approved amount
+    setup.state_manager.approve(approvals[0].id).await.unwrap();
+    setup.balance.set_eth("0.5");
+    assert_eq!(
+        setup
+            .monitor
+            .check_all_wallets(&setup.config)
+            .await
+            .unwrap(),
+        0
+    );
+    assert!(setup.state_manager.approvals().await.is_empty());
+    assert_eq!(setup.state_manager.next_nonce(&WALLET.to_string()).await, 1);
+}
+
+#[tokio::test]
+async fn test_approved_sweep_executes_after_balance_check() {
+    let setup = setup(3600).await;
+    setup.balance.set_eth("25");
+    setup
+        .monitor
+        .check_all_wallets(&setup.config)
+        .await
+        .unwrap();
+    let id = setup.state_manager.approvals().await[0].id;
+    setup.state_manager.approve(id).await.unwrap();
+
+    // The balance still covers the approved amount; the new balance above
+    // the approval amount is queued again
+    setup.balance.set_eth("30");
+    assert_eq!(
+        setup
+            .monitor
+            .check_all_wallets(&setup.config)
+            .await
+            .unwrap(),
+        1
+    );
+    let approvals = setup.state_manager.approvals().await;
+    assert_eq!(approvals.len(), 1);
+    assert_ne!(approvals[0].id, id);
+    assert_eq!(approvals[0].amount(), Amount::parse("30", 18).unwrap());
+
+    // Rejected sweeps leave the queue
+    setup.state_manager.reject(approvals[0].id).await.unwrap();
+    assert!(setup.state_manager.approvals().await.is_empty());
+    assert!(setup.state_manager.approve(approvals[0].id).await.is_err());
+}
+
+#[tokio::test]
+async fn test_queued_sweeps_expire() {
+    let setup = setup(0).await;
+    setup.balance.set_eth("25");
+    setup
+        .monitor
+        .check_all_wallets(&setup.config)
+        .await
+        .unwrap();
+
+    let id = setup.state_manager.approvals().await[0].id;
+    let err = setup.state_manager.approve(id).await.unwrap_err();
+    assert!(err.to_string().contains("expired"));
+
+    // The next cycle drops the expired sweep and queues the balance again
+    setup.balance.set_eth("26");
+    setup
+        .monitor
+        .check_all_wallets(&setup.config)
+        .await
+        .unwrap();
+    let approvals = setup.state_manager.approvals().await;
+    assert_eq!(approvals.len(), 1);
+    assert_eq!(approvals[0].amount(), Amount::parse("26", 18).unwrap());
+}
+
+#[tokio::test]
+async fn test_approved_sweep_keeps_its_amount_across_a_restart() {
+    let setup = setup(3600).await;
+    setup.balance.set_eth("25");
+    setup
+        .monitor
+        .check_all_wallets(&setup.config)
+        .await
+        .unwrap();
+    let id = setup.state_manager.approvals().await[0].id;
+    setup.state_manager.approve(id).await.unwrap();
+
+    // The sweeper restarts between the approval and the next cycle
+    let state_manager = Arc::new(StateManager::load(setup.state_path.clone()).await.unwrap());
+    let approval = setup.config.approval.clone().unwrap();
+    let restarted = monitor(state_manager.clone(), setup.balance.clone(), approval);
+    let report = restarted.run_cycle(&setup.config).await.unwrap();
+
+    assert_eq!(report.sweep_count, 1);
+    assert_eq!(
+        report.wallets[0].transactions[0].tx.value,
+        Amount::parse("25", 18).unwrap()
+    );
+    // The balance left is queued again under a new id
+    let approvals = state_manager.approvals().await;
+    assert!(approvals.iter().all(|approval| approval.id != id));
+}
+
+#[tokio::test]
+async fn test_approved_sweep_stays_queued_when_it_fails() {
+    let setup = setup(3600).await;
+    let wallet = WALLET.to_string();
+    setup.balance.set_eth("25");
+    setup
+        .monitor
+        .check_all_wallets(&setup.config)
+        .await
+        .unwrap();
+    let id = setup.state_manager.approvals().await[0].id;
+    setup.state_manager.approve(id).await.unwrap();
+
+    // The sweep cannot be emitted while the wallet is halted
+    setup
+        .state_manager
+        .set_halted(&wallet, Some("nonce mismatch".to_string()))
+        .await
+        .unwrap();
+    let report = setup.monitor.run_cycle(&setup.config).await.unwrap();
+    assert!(report.wallets[0].error.is_some());
+    let approvals = setup.state_manager.approvals().await;
+    assert_eq!(approvals.len(), 1);
+    assert_eq!(approvals[0].status, ApprovalStatus::Approved);
+
+    setup.state_manager.set_halted(&wallet, None).await.unwrap();
+    setup.balance.set_eth("5");
+    let report = setup.monitor.run_cycle(&setup.config).await.unwrap();
+    assert_eq!(report.sweep_count, 1);
+    assert!(setup.state_manager.approvals().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_running_sweeper_picks_up_decisions_from_another_process() {
+    let setup = setup(3600).await;
+    let wallet = WALLET.to_string();
+    setup.balance.set_eth("25");
+    setup
+        .monitor
+        .check_all_wallets(&setup.config)
+        .await
+        .unwrap();
+    let id = setup.state_manager.approvals().await[0].id;
+
+    // `approvals approve` runs against the same state file, and the sweeper
+    // writes its own state before the approval lands
+    let cli = StateManager::load(setup.state_path.clone()).await.unwrap();
+    setup
+        .state_manager
+        .set_next_nonce(&wallet, 1)
+        .await
+        .unwrap();
+    cli.approve(id).await.unwrap();
+
+    // The approval does not overwrite what the sweeper wrote
+    let on_disk = StateManager::load(setup.state_path.clone()).await.unwrap();
+    assert_eq!(on_disk.next_nonce(&wallet).await, 1);
+    assert_eq!(
+        on_disk.approvals().await[0].status,
+        ApprovalStatus::Approved
+    );
+
+    setup.balance.set_eth("5");
+    assert_eq!(
+        setup
+            .monitor
+            .check_all_wallets(&setup.config)
+            .await
+            .unwrap(),
+        1
+    );
+    assert!(setup.state_manager.approvals().await.is_empty());
+    assert_eq!(setup.state_manager.next_nonce(&wallet).await, 2);
+
+    // `approvals reject` removes a sweep the sweeper still holds in memory
+    setup.balance.set_eth("25");
+    setup
+        .monitor
+        .check_all_wallets(&setup.config)
+        .await
+        .unwrap();
+    let rejected = setup.state_manager.approvals().await[0].id;
+    let cli = StateManager::load(setup.state_path.clone()).await.unwrap();
+    cli.reject(rejected).await.unwrap();
+
+    setup
+        .monitor
+        .check_all_wallets(&setup.config)
+        .await
+        .unwrap();
+    let approvals = setup.state_manager.approvals().await;
+    assert_eq!(approvals.len(), 1);
+    assert_ne!(approvals[0].id, rejected);
+    assert_eq!(approvals[0].status, ApprovalStatus::Pending);
+}
+
+#[tokio::test]
+async fn test_decisions_do_not_carry_over_to_a_reset_state() {
+    let setup = setup(3600).await;
+    setup.balance.set_eth("25");
+    setup
+        .monitor
+        .check_all_wallets(&setup.config)
+        .await
+        .unwrap();
+    let id = setup.state_manager.approvals().await[0].id;
+    setup.state_manager.approve(id).await.unwrap();
+
+    // The state file is deleted to start fresh, leaving the decisions file
+    std::fs::remove_file(&setup.state_path).unwrap();
+    let state_manager = Arc::new(StateManager::load(setup.state_path.clone()).await.unwrap());
+    let approval = setup.config.approval.clone().unwrap();
+    let restarted = monitor(state_manager.clone(), setup.balance.clone(), approval);
+    let report = restarted.run_cycle(&setup.config).await.unwrap();
+
+    // The new sweep reuses the id but not the old approval
+    assert_eq!(report.sweep_count, 0);
+    state_manager.reload_approvals().await;
+    let approvals = state_manager.approvals().await;
+    assert_eq!(approvals.len(), 1);
+    assert_eq!(approvals[0].id, id);
+    assert_eq!(approvals[0].status, ApprovalStatus::Pending);
+}
+
+#[tokio::test]
+async fn test_split_sweep_is_approved_and_run_as_one() {
+    let mut setup = setup(3600).await;
+    let split = |address: &str, primary| SplitDestination {
+        address: address.to_string(),
+        bps: 5000,
+        primary,
+    };
+    if let SweepRule::NativeBalance { destinations, .. } = &mut setup.config.hot_wallets[0].rules[0]
+    {
+        *destinations = vec![split("0xCOLD", false), split("0xEXCHANGE", true)];
+    }
+    setup.config.allowed_destinations = vec!["0xCOLD".to_string(), "0xEXCHANGE".to_string()];
+    let emitter = Arc::new(
+        MockTxEmitter::new(setup.state_manager.clone(), "0xTREASURY".to_string())
+            .with_destinations(Destinations::from_config(&setup.config)),
+    );
+    let monitor = WalletMonitor::new(Arc::new(RulesEngine::new(setup.balance.clone())), emitter)
+        .with_approvals(ApprovalQueue::new(
+            setup.config.approval.clone().unwrap(),
+            setup.state_manager.clone(),
+        ));
+
+    // Both 15 ETH legs of the 30 ETH sweep wait in one entry
+    setup.balance.set_eth("30");
+    for _ in 0..2 {
+        assert_eq!(monitor.check_all_wallets(&setup.config).await.unwrap(), 0);
+    }
+    let approvals = setup.state_manager.approvals().await;
+    assert_eq!(approvals.len(), 1);
+    assert_eq!(approvals[0].legs.len(), 2);
+    assert_eq!(approvals[0].amount(), Amount::parse("30", 18).unwrap());
+
+    // Approving it sends every leg, counted as one sweep
+    setup.state_manager.approve(approvals[0].id).await.unwrap();
+    let report = monitor.run_cycle(&setup.config).await.unwrap();
+    let destinations: Vec<_> = report.wallets[0]
+        .transactions
+        .iter()
+        .map(|submitted| submitted.tx.to.as_str())
+        .collect();
+    assert_eq!(destinations, vec!["0xCOLD", "0xEXCHANGE"]);
+    let history = setup.state_manager.fetch_snapshot().await.wallets[WALLET]
+        .sweep_history
+        .clone();
+    let firsts: Vec<_> = history
+        .iter()
+        .map(|record| record.split.unwrap().first)
+        .collect();
+    assert_eq!(firsts, vec![true, false]);
+
+    // The balance queued again is rejected as a whole
+    let approvals = setup.state_manager.approvals().await;
+    assert_eq!(approvals.len(), 1);
+    setup.state_manager.reject(approvals[0].id).await.unwrap();
+    assert!(setup.state_manager.approvals().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_approved_native_sweep_needs_its_fee() {
+    let setup = setup(3600).await;
+    let fee = Amount::parse("0.01", 18).unwrap().units();
+    let rules_engine = RulesEngine::new(setup.balance.clone()).with_native_fee(fee, 50);
+    let emitter = Arc::new(MockTxEmitter::new(
+        setup.state_manager.clone(),
+        "0xTREASURY".to_string(),
+    ));
+    let monitor = WalletMonitor::new(Arc::new(rules_engine), emitter).with_approvals(
+        ApprovalQueue::new(
+            setup.config.approval.clone().unwrap(),
+            setup.state_manager.clone(),
+        ),
+    );
+
+    setup.balance.set_eth("25");
+    monitor.check_all_wallets(&setup.config).await.unwrap();
+    let approvals = setup.state_manager.approvals().await;
+    assert_eq!(approvals[0].amount(), Amount::parse("24.99", 18).unwrap());
+    setup.state_manager.approve(approvals[0].id).await.unwrap();
+
+    // The wallet holds the approved amount but not its fee: dropped, not
+    // left to fail its broadcast every cycle
+    setup.balance.set_eth("24.995");
+    let report = monitor.run_cycle(&setup.config).await.unwrap();
+    assert_eq!(report.sweep_count, 0);
+    assert!(report.wallets[0].skipped[0].reason.contains("and its fees"));
+    let queued = setup.state_manager.approvals().await;
+    assert_eq!(queued.len(), 1);
+    assert_ne!(queued[0].id, approvals[0].id);
+    assert_eq!(queued[0].amount(), Amount::parse("24.985", 18).unwrap());
+}
@@ -0,0 +1,335 @@
+mod common;
+
+use anyhow::anyhow;
+use common::{MockRpcServer, quantity, rpc_error};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use treasury_sweeper::amount::U256;
+use treasury_sweeper::balance_checker::{
+    BalanceError, BalanceSource, RetryingBalanceSource, RpcBalanceChecker,
+};
+use treasury_sweeper::rpc::{BlockTag, HttpStatusError, decode_quantity};
+use treasury_sweeper::types::{BalanceSourceConfig, Config, RetryPolicy};
+
+const WALLET: &str = "0x701b519ed7cc5fe1a9e03a594816f909e25f6be8";
+const TOKEN: &str = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
+
+#[test]
+fn test_decode_quantity() {
+    assert_eq!(decode_quantity("0x0").unwrap(), U256::ZERO);
+    assert_eq!(
+        decode_quantity("0x1bc16d674ec80000").unwrap(),
+        U256::from(2_000_000_000_000_000_000u128)
+    );
+    assert_eq!(
+        decode_quantity(&format!("0x{}", "f".repeat(64))).unwrap(),
+        U256::MAX
+    );
+    assert!(decode_quantity(&format!("0x1{}", "0".repeat(64))).is_err());
+    assert!(decode_quantity("1bc16d").is_err());
+    assert!(decode_quantity("0x").is_err());
+    assert!(decode_quantity("0xzz").is_err());
+}
+
+#[tokio::test]
+async fn test_native_balance_uses_block_tag() {
+    let server = MockRpcServer::start(|method, _params| match method {
+        "eth_getBalance" => Ok(quantity(1_500_000_000_000_000_000)),
+        _ => Err(rpc_error(-32601, "method not found")),
+    })
+    .await;
+
+    let checker = RpcBalanceChecker::new(server.url.clone(), BlockTag::Finalized).unwrap();
+    let balance = checker
+        .check_native_balance(&WALLET.to_string())
+        .await
+        .unwrap();
+    assert_eq!(balance, U256::from(1_500_000_000_000_000_000u128));
+
+    let calls = server.calls("eth_getBalance");
+    assert_eq!(calls, vec![json!([WALLET, "finalized"])]);
+}
+
+#[tokio::test]
+async fn test_token_balance_calls_balance_of() {
+    let server = MockRpcServer::start(|method, _params| match method {
+        "eth_call" => Ok(Value::String(format!("0x{:064x}", 150_000_000u64))),
+        _ => Err(rpc_error(-32601, "method not found")),
+    })
+    .await;
+
+    let checker = RpcBalanceChecker::new(server.url.clone(), BlockTag::Safe).unwrap();
+    let balance = checker
+        .check_token_balance(&WALLET.to_string(), &TOKEN.to_string())
+        .await
+        .unwrap();
+    assert_eq!(balance, U256::from(150_000_000u64));
+
+    let calls = server.calls("eth_call");
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0][0]["to"], TOKEN);
+    assert_eq!(
+        calls[0][0]["data"],
+        format!("0x70a08231000000000000000000000000{}", &WALLET[2..])
+    );
+    assert_eq!(calls[0][1], "safe");
+}
+
+#[tokio::test]
+async fn test_rpc_error_object_is_reported() {
+    let server =
+        MockRpcServer::start(|_method, _params| Err(rpc_error(-32000, "header not found"))).await;
+
+    let checker = RpcBalanceChecker::new(server.url.clone(), BlockTag::Latest).unwrap();
+    let err = checker
+        .check_native_balance(&WALLET.to_string())
+        .await
+        .unwrap_err();
+    let message = format!("{:#}", err);
+    assert!(message.contains("-32000"));
+    assert!(message.contains("header not found"));
+}
+
+#[tokio::test]
+async fn test_invalid_address_is_rejected_before_calling() {
+    let server = MockRpcServer::start(|_method, _params| Ok(quantity(1))).await;
+
+    let checker = RpcBalanceChecker::new(server.url.clone(), BlockTag::Latest).unwrap();
+    assert!(
+        checker
+            .check_native_balance(&"0x1234".to_string())
+            .await
+            .is_err()
+    );
+    assert!(server.requests().is_empty());
+}
+
+fn fast_retries(max_attempts: u32) -> RetryPolicy {
+    RetryPolicy {
+        max_attempts,
+        initial_backoff_ms: 1,
+        max_backoff_ms: 5,
+        attempt_timeout_ms: 200,
+    }
+}
+
+#[tokio::test]
+async fn test_transient_errors_are_retried() {
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let counter = attempts.clone();
+    let server = MockRpcServer::start(move |_method, _params| {
+        match counter.fetch_add(1, Ordering::SeqCst) {
+            0 => Err(rpc_error(-32005, "request rate limit exceeded")),
+            1 => Err(rpc_error(-32000, "upstream timed out")),
+            _ => Ok(quantity(7)),
+        }
+    })
+    .await;
+
+    let checker = RpcBalanceChecker::new(server.url.clone(), BlockTag::Latest).unwrap();
+    let source = RetryingBalanceSource::new(Arc::new(checker), fast_retries(3));
+    let balance = source
+        .check_native_balance(&WALLET.to_string())
+        .await
+        .unwrap();
+    assert_eq!(balance, U256::from(7u64));
+    assert_eq!(server.calls("eth_getBalance").len(), 3);
+}
+
+#[tokio::test]
+async fn test_permanent_errors_are_not_retried() {
+    let server =
+        MockRpcServer::start(|_method, _params| Err(rpc_error(3, "execution reverted"))).await;
+
+    let checker = RpcBalanceChecker::new(server.url.clone(), BlockTag::Latest).unwrap();
+    let source = RetryingBalanceSource::new(Arc::new(checker), fast_retries(5));
+    let err = source
+        .check_token_balance(&WALLET.to_string(), &TOKEN.to_string())
+        .await
+        .unwrap_err();
+    assert!(!BalanceError::classify(err).is_transient());
+    assert_eq!(server.calls("eth_call").len(), 1);
+
+    // Invalid addresses never reach the node
+    let err = source
+        .check_native_balance(&"0x1234".to_string())
+        .await
+        .unwrap_err();
+    assert!(!BalanceError::classify(err).is_transient());
+    assert!(server.calls("eth_getBalance").is_empty());
+}
+
+#[tokio::test]
+async fn test_retries_stop_after_max_attempts() {
+    let server =
+        MockRpcServer::start(|_method, _params| Err(rpc_error(429, "too many requests"))).await;
+
+    let checker = RpcBalanceChecker::new(server.url.clone(), BlockTag::Latest).unwrap();
+    let source = RetryingBalanceSource::new(Arc::new(checker), fast_retries(2));
+    let err = source
+        .check_native_balance(&WALLET.to_string())
+        .await
+        .unwrap_err();
+    assert!(format!("{:#}", err).contains("failed 2 times"));
+    assert!(BalanceError::classify(err).is_transient());
+    assert_eq!(server.calls("eth_getBalance").len(), 2);
+}
+
+#[tokio::test]
+async fn test_request_errors_without_io_cause_are_not_retried() {
+    // A server that answers every request with something that is not HTTP:
+    // a request error with no I/O error beneath it
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let accepted = Arc::new(AtomicUsize::new(0));
+    let counter = accepted.clone();
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let _ = socket.write_all(b"not http\r\n\r\n").await;
+        }
+    });
+
+    let checker = RpcBalanceChecker::new(url, BlockTag::Latest).unwrap();
+    let source = RetryingBalanceSource::new(Arc::new(checker), fast_retries(5));
+    let err = source
+        .check_native_balance(&WALLET.to_string())
+        .await
+        .unwrap_err();
+    let BalanceError::Permanent(err) = BalanceError::classify(err) else {
+        panic!("expected a permanent error");
+    };
+    let request_error = err
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<reqwest::Error>())
+        .unwrap();
+    assert!(request_error.is_request());
+    assert_eq!(accepted.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_reset_connections_are_retried() {
+    let server = MockRpcServer::start(|_method, _params| Ok(quantity(7))).await;
+    let upstream = server.url.trim_start_matches("http://").to_string();
+
+    // A proxy that resets the first connection once the request is in, and
+    // passes the others through to the node
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let accepted = Arc::new(AtomicUsize::new(0));
+    let counter = accepted.clone();
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                let _ = socket.read(&mut [0; 1024]).await;
+                socket.set_zero_linger().unwrap();
+                continue;
+            }
+            let upstream = upstream.clone();
+            tokio::spawn(async move {
+                let mut node = tokio::net::TcpStream::connect(upstream).await.unwrap();
+                let _ = tokio::io::copy_bidirectional(&mut socket, &mut node).await;
+            });
+        }
+    });
+
+    let checker = RpcBalanceChecker::new(url, BlockTag::Latest).unwrap();
+    let source = RetryingBalanceSource::new(Arc::new(checker), fast_retries(3));
+    let balance = source
+        .check_native_balance(&WALLET.to_string())
+        .await
+        .unwrap();
+    assert_eq!(balance, U256::from(7u64));
+    assert_eq!(accepted.load(Ordering::SeqCst), 2);
+    assert_eq!(server.calls("eth_getBalance").len(), 1);
+}
+
+/// Source whose first query never answers
+#[derive(Default)]
+struct StallingSource {
+    calls: AtomicUsize,
+}
+
+#[async_trait::async_trait]
+impl BalanceSource for StallingSource {
+    fn name(&self) -> &str {
+        "stalling"
+    }
+
+    async fn check_native_balance(&self, _address: &String) -> anyhow::Result<U256> {
+        if self.calls.fetch_add(1, Ordering::SeqCst) == 0 {
+            std::future::pending::<()>().await;
+        }
+        Ok(U256::from(7u64))
+    }
+
+    async fn check_token_balance(
+        &self,
+        _address: &String,
+        _token: &String,
+    ) -> anyhow::Result<U256> {
+        Ok(U256::ZERO)
+    }
+}
+
+#[tokio::test]
+async fn test_stalled_attempts_time_out_and_are_retried() {
+    let inner = Arc::new(StallingSource::default());
+    let source = RetryingBalanceSource::new(inner.clone(), fast_retries(2));
+    let balance = source
+        .check_native_balance(&WALLET.to_string())
+        .await
+        .unwrap();
+    assert_eq!(balance, U256::from(7u64));
+    assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+
+    // Without retries the stalled attempt fails as a transient error
+    let source = RetryingBalanceSource::new(Arc::new(StallingSource::default()), fast_retries(1));
+    let err = source
+        .check_native_balance(&WALLET.to_string())
+        .await
+        .unwrap_err();
+    assert!(format!("{:#}", err).contains("timed out after 200ms"));
+    assert!(BalanceError::classify(err).is_transient());
+}
+
+#[test]
+fn test_error_classification_and_backoff() {
+    let http = |status| BalanceError::classify(anyhow!(HttpStatusError { status }).context("call"));
+    assert!(http(429).is_transient());
+    assert!(http(503).is_transient());
+    assert!(!http(400).is_transient());
+    assert!(!BalanceError::classify(anyhow!("bad response")).is_transient());
+
+    let policy: RetryPolicy =
+        serde_json::from_value(json!({"initial_backoff_ms": 250, "max_backoff_ms": 1000})).unwrap();
+    assert_eq!(policy.max_attempts, 3);
+    assert_eq!(policy.attempt_timeout_ms, 5_000);
+    let backoffs: Vec<_> = (1..=4)
+        .map(|retry| policy.backoff(retry).as_millis())
+        .collect();
+    assert_eq!(backoffs, vec![250, 500, 1000, 1000]);
+    assert_eq!(policy.backoff(200).as_millis(), 1000);
+}
+
+#[test]
+fn test_attempt_timeout_must_fit_the_balance_timeout() {
+    let config = |attempt_timeout_ms| Config {
+        balance_source: BalanceSourceConfig::Rpc {
+            url: "http://127.0.0.1:8545".to_string(),
+            block: BlockTag::Latest,
+            retry: RetryPolicy {
+                attempt_timeout_ms,
+                ..RetryPolicy::default()
+            },
+        },
+        ..common::test_config(vec![])
+    };
+
+    assert!(config(5_000).validate().is_ok());
+    assert!(config(0).validate().is_err());
+    // timeouts.balance_seconds defaults to 30
+    assert!(config(30_000).validate().is_err());
+}
@@ -0,0 +1,204 @@
+//! Local JSON-RPC stand-in and config builder shared by the integration tests
+#![allow(dead_code)]
+
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use treasury_sweeper::types::{
+    BalanceSourceConfig, Config, EmitterConfig, GasConfig, HotWalletConfig, TimeoutConfig,
+};
+
+type Handler = dyn Fn(&str, &Value) -> Result<Value, Value> + Send + Sync;
+
+/// Minimal HTTP JSON-RPC server answering requests with a handler closure.
+///
+/// The handler receives the method and params; `Ok` becomes the `result`,
+/// `Err` becomes the `error` object.
+pub struct MockRpcServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<(String, Value)>>>,
+}
+
+impl MockRpcServer {
+    pub async fn start<F>(handler: F) -> Self
+    where
+        F: Fn(&str, &Value) -> Result<Value, Value> + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler: Arc<Handler> = Arc::new(handler);
+
+        let recorded = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else {
+                    return;
+                };
+                let handler = handler.clone();
+                let recorded = recorded.clone();
+                tokio::spawn(async move {
+                    let (read, mut write) = stream.into_split();
+                    let mut reader = BufReader::new(read);
+                    while let Some((_, body)) = read_request(&mut reader).await {
+                        let request: Value = serde_json::from_slice(&body).unwrap();
+                        let method = request["method"].as_str().unwrap_or_default().to_string();
+                        let params = request["params"].clone();
+                        recorded
+                            .lock()
+                            .unwrap()
+                            .push((method.clone(), params.clone()));
+
+                        let response = match handler(&method, &params) {
+                            Ok(result) => {
+                                json!({"jsonrpc": "2.0", "id": request["id"], "result": result})
+                            }
+                            Err(error) => {
+                                json!({"jsonrpc": "2.0", "id": request["id"], "error": error})
+                            }
+                        };
+                        let payload = response.to_string();
+                        let http = format!(
+                            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                            payload.len(),
+                            payload
+                        );
+                        if write.write_all(http.as_bytes()).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    /// Every `(method, params)` received so far
+    pub fn requests(&self) -> Vec<(String, Value)> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Requests received for one method
+    pub fn calls(&self, method: &str) -> Vec<Value> {
+        self.requests()
+            .into_iter()
+            .filter(|(m, _)| m == method)
+            .map(|(_, params)| params)
+            .collect()
+    }
+}
+
+/// Plain HTTP server answering `GET` requests with a handler closure.
+///
+/// The handler receives the request path; `Some` is returned as a JSON body,
+/// `None` as a 404.
+pub struct MockHttpServer {
+    pub url: String,
+}
+
+impl MockHttpServer {
+    pub async fn start<F>(handler: F) -> Self
+    where
+        F: Fn(&str) -> Option<Value> + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handler = Arc::new(handler);
+
+        tokio::spawn(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else {
+                    return;
+                };
+                let handler = handler.clone();
+                tokio::spawn(async move {
+                    let (read, mut write) = stream.into_split();
+                    let mut reader = BufReader::new(read);
+                    while let Some((request_line, _)) = read_request(&mut reader).await {
+                        let path = request_line.split(' ').nth(1).unwrap_or_default();
+                        let (status, payload) = match handler(path) {
+                            Some(body) => ("200 OK", body.to_string()),
+                            None => ("404 Not Found", String::new()),
+                        };
+                        let http = format!(
+                            "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                            status,
+                            payload.len(),
+                            payload
+                        );
+                        if write.write_all(http.as_bytes()).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+
+        Self { url }
+    }
+}
+
+/// Read one HTTP request and return its request line and body, or `None` when
+/// the peer closes
+async fn read_request<R: AsyncBufReadExt + Unpin>(reader: &mut R) -> Option<(String, Vec<u8>)> {
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).await.ok()? == 0 {
+        return None;
+    }
+
+    let mut content_length = 0;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await.ok()? == 0 {
+            return None;
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            content_length = value.trim().parse().ok()?;
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).await.ok()?;
+    Some((request_line.trim_end().to_string(), body))
+}
+
+/// JSON-RPC error object
+pub fn rpc_error(code: i64, message: &str) -> Value {
+    json!({"code": code, "message": message})
+}
+
+/// Hex quantity encoding used by Ethereum nodes
+pub fn quantity(value: u128) -> Value {
+    Value::String(format!("0x{:x}", value))
+}
+
+/// Config sweeping `hot_wallets` to `0xTREASURY` with every optional setting
+/// at its default; override fields with struct update syntax
+pub fn test_config(hot_wallets: Vec<HotWalletConfig>) -> Config {
+    Config {
+        treasury_address: "0xTREASURY".to_string(),
+        hot_wallets,
+        sweep_interval_seconds: 60,
+        balance_source: BalanceSourceConfig::default(),
+        emitter: EmitterConfig::default(),
+        gas: GasConfig::default(),
+        nonce_reconciliation: None,
+        price_oracle: None,
+        caps: vec![],
+        approval: None,
+        routes: HashMap::new(),
+        allowed_destinations: vec![],
+        max_parallel_wallets: 8,
+        timeouts: TimeoutConfig::default(),
+    }
+}
@@ -0,0 +1,244 @@
+use k256::ecdsa::SigningKey;
+use treasury_sweeper::amount::{Amount, U256};
+use treasury_sweeper::evm_tx::{
+    EvmTransaction, EvmTxBuilder, address_of, encode_erc20_transfer, format_address,
+};
+use treasury_sweeper::keystore::Keystore;
+use treasury_sweeper::rlp::RlpItem;
+use treasury_sweeper::types::{GasConfig, SweepDecision, TxType};
+
+// Key and recipient from the EIP-155 example
+const PRIVATE_KEY: &str = "4646464646464646464646464646464646464646464646464646464646464646";
+const SENDER: &str = "0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f";
+const RECIPIENT: &str = "0x3535353535353535353535353535353535353535";
+const USDC: &str = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
+
+fn signing_key() -> SigningKey {
+    SigningKey::from_slice(&hex::decode(PRIVATE_KEY).unwrap()).unwrap()
+}
+
+fn one_ether() -> U256 {
+    U256::from(1_000_000_000_000_000_000u128)
+}
+
+fn gas(tx_type: TxType, max_fee_gwei: &str) -> GasConfig {
+    GasConfig {
+        tx_type,
+        max_fee_per_gas_gwei: max_fee_gwei.parse().unwrap(),
+        max_priority_fee_per_gas_gwei: "1.5".parse().unwrap(),
+        ..GasConfig::default()
+    }
+}
+
+#[test]
+fn test_rlp_encoding() {
+    assert_eq!(RlpItem::bytes(b"dog").encode(), b"\x83dog");
+    assert_eq!(RlpItem::u64(0).encode(), vec![0x80]);
+    assert_eq!(RlpItem::u64(15).encode(), vec![0x0f]);
+    assert_eq!(RlpItem::u64(1024).encode(), vec![0x82, 0x04, 0x00]);
+    assert_eq!(RlpItem::List(vec![]).encode(), vec![0xc0]);
+    assert_eq!(
+        RlpItem::List(vec![RlpItem::bytes(b"cat"), RlpItem::bytes(b"dog")]).encode(),
+        b"\xc8\x83cat\x83dog"
+    );
+
+    let long = vec![b'a'; 56];
+    let encoded = RlpItem::bytes(&long).encode();
+    assert_eq!(&encoded[..2], &[0xb8, 56]);
+    assert_eq!(encoded.len(), 58);
+}
+
+#[test]
+fn test_address_derivation() {
+    assert_eq!(
+        format_address(&address_of(signing_key().verifying_key())),
+        SENDER
+    );
+}
+
+#[test]
+fn test_legacy_eip155_vector() {
+    let builder = EvmTxBuilder::new(1, gas(TxType::Legacy, "20"));
+    let tx = builder
+        .build_transfer(9, &RECIPIENT.to_string(), one_ether())
+        .unwrap();
+
+    let signed = tx.sign(&signing_key()).unwrap();
+    assert_eq!(
+        signed.raw_hex(),
+        "0xf86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83"
+    );
+}
+
+#[test]
+fn test_eip1559_native_sweep_vector() {
+    let builder = EvmTxBuilder::new(1, gas(TxType::Eip1559, "30"));
+    let decision = SweepDecision {
+        amount: Amount::from_units(one_ether(), 18),
+        asset: "ETH".to_string(),
+        rule_type: "native_balance".to_string(),
+        token_address: None,
+        destination: None,
+        split: None,
+    };
+    let tx = builder
+        .build_sweep(9, &RECIPIENT.to_string(), &decision)
+        .unwrap();
+    assert_eq!(tx.gas_limit, 21_000);
+    assert_eq!(tx.max_fee_cost(), U256::from(630_000_000_000_000u64));
+
+    let signed = tx.sign(&signing_key()).unwrap();
+    assert_eq!(
+        signed.raw_hex(),
+        "0x02f87301098459682f008506fc23ac00825208943535353535353535353535353535353535353535880de0b6b3a764000080c001a053d7eae6224bc2e855439bd276c8d14bc1315b41cc4a65b7bfa948a9aa7fb4cba0107244aa695014ff68200481a1c8673c1286a96d8e30057b49b628a69373e363"
+    );
+    assert_eq!(
+        signed.hash_hex(),
+        "0x0c6562f4b2ad1075caf65fa1537f143d80c982607279c0a76900142ffea8ad85"
+    );
+}
+
+#[test]
+fn test_erc20_sweep_vector() {
+    let builder = EvmTxBuilder::new(11_155_111, gas(TxType::Eip1559, "30"));
+    let decision = SweepDecision {
+        amount: Amount::parse("150", 6).unwrap(),
+        asset: "USDC".to_string(),
+        rule_type: "token_balance".to_string(),
+        token_address: Some(USDC.to_string()),
+        destination: None,
+        split: None,
+    };
+    let tx = builder
+        .build_sweep(0, &RECIPIENT.to_string(), &decision)
+        .unwrap();
+
+    // The call goes to the token contract, carrying no ETH
+    assert_eq!(format_address(&tx.to), USDC);
+    assert_eq!(tx.value, U256::ZERO);
+    assert_eq!(tx.gas_limit, 65_000);
+    assert_eq!(
+        tx.data,
+        encode_erc20_transfer(&[0x35; 20], U256::from(150_000_000u64))
+    );
+    assert_eq!(hex::encode(&tx.data[..4]), "a9059cbb");
+
+    let signed = tx.sign(&signing_key()).unwrap();
+    assert_eq!(
+        signed.raw_hex(),
+        "0x02f8b383aa36a7808459682f008506fc23ac0082fde894a0b86991c6218b36c1d19d4a2e9eb0ce3606eb4880b844a9059cbb00000000000000000000000035353535353535353535353535353535353535350000000000000000000000000000000000000000000000000000000008f0d180c080a081e2f54d14d884652be2138eb104e88823a2deccc21027752abf0820870d38b9a01b6f3ea640bda9a7a349fb56d633f037163e4220eef8e991c1e9f8cc06b1c14c"
+    );
+}
+
+#[test]
+fn test_invalid_destination_is_rejected() {
+    let builder = EvmTxBuilder::new(1, GasConfig::default());
+    assert!(
+        builder
+            .build_transfer(0, &"0xTREASURY".to_string(), U256::ZERO)
+            .is_err()
+    );
+}
+
+#[test]
+fn test_keystore_checks_key_matches_address() {
+    let keystore =
+        Keystore::from_json(&format!(r#"{{"{}": "0x{}"}}"#, SENDER, PRIVATE_KEY)).unwrap();
+    let key = keystore
+        .signer(&SENDER.to_uppercase().replace("0X", "0x"))
+        .unwrap();
+    assert_eq!(key, &signing_key());
+    assert!(keystore.signer(&RECIPIENT.to_string()).is_err());
+
+    let err = Keystore::from_json(&format!(r#"{{"{}": "0x{}"}}"#, RECIPIENT, PRIVATE_KEY))
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains(SENDER));
+}
+
+#[test]
+fn test_signed_transaction_hash_is_keccak_of_raw() {
+    let tx = EvmTransaction {
+        tx_type: TxType::Eip1559,
+        chain_id: 1,
+        nonce: 0,
+        gas_limit: 21_000,
+        max_fee_per_gas: U256::from(1u8),
+        max_priority_fee_per_gas: U256::from(1u8),
+        to: [0x35; 20],
+        value: U256::ZERO,
+        data: Vec::new(),
+    };
+    let signed = tx.sign(&signing_key()).unwrap();
+    assert_eq!(
+        signed.hash,
+        treasury_sweeper::evm_tx::keccak256(&signed.raw)
+    );
+    assert_eq!(signed.raw[0], 0x02);
+}
+
+#[test]
+fn test_fee_bump_rounds_up_and_respects_cap() {
+    let builder = EvmTxBuilder::new(1, gas(TxType::Eip1559, "30"));
+    let tx = builder
+        .build_transfer(4, &RECIPIENT.to_string(), one_ether())
+        .unwrap();
+
+    let bumped = tx.bumped(10, U256::from(100_000_000_000u64), true).unwrap();
+    assert_eq!(bumped.max_fee_per_gas, U256::from(33_000_000_000u64));
+    assert_eq!(bumped.max_priority_fee_per_gas, U256::from(1_650_000_000u64));
+    assert_eq!(bumped.nonce, 4);
+    // The native transfer pays the 3 gwei per gas rise out of its value
+    assert_eq!(
+        bumped.value,
+        tx.value - U256::from(21_000u64 * 3_000_000_000)
+    );
+
+    let odd = EvmTransaction {
+        max_fee_per_gas: U256::from(11u8),
+        max_priority_fee_per_gas: U256::from(1u8),
+        ..tx.clone()
+    };
+    let bumped = odd.bumped(10, U256::from(100u8), true).unwrap();
+    assert_eq!(bumped.max_fee_per_gas, U256::from(13u8));
+    assert_eq!(bumped.max_priority_fee_per_gas, U256::from(2u8));
+
+    assert!(tx.bumped(10, U256::from(32_999_999_999u64), true).is_none());
+
+    // A value too small to cover the rise cannot be bumped
+    let dust = EvmTransaction {
+        value: U256::from(1_000u64),
+        ..tx.clone()
+    };
+    assert!(dust.bumped(10, U256::MAX, true).is_none());
+    // Unless the sender pays the rise, the full value is still sent
+    assert_eq!(dust.bumped(10, U256::MAX, false).unwrap().value, dust.value);
+    // Zero-value transfers pay the rise from the balance
+    let empty = EvmTransaction {
+        value: U256::ZERO,
+        ..tx.clone()
+    };
+    assert_eq!(empty.bumped(10, U256::MAX, true).unwrap().value, U256::ZERO);
+}
+
+#[test]
+fn test_pending_round_trip() {
+    let builder = EvmTxBuilder::new(11_155_111, gas(TxType::Legacy, "20"));
+    let decision = SweepDecision {
+        amount: Amount::parse("150", 6).unwrap(),
+        asset: "USDC".to_string(),
+        rule_type: "token_balance".to_string(),
+        token_address: Some(USDC.to_string()),
+        destination: None,
+        split: None,
+    };
+    let tx = builder
+        .build_sweep(7, &RECIPIENT.to_string(), &decision)
+        .unwrap();
+
+    let pending = tx.to_pending("USDC", "0xabc".to_string());
+    let json = serde_json::to_string(&pending).unwrap();
+    let restored = serde_json::from_str(&json).unwrap();
+    assert_eq!(pending, restored);
+    assert_eq!(EvmTransaction::from_pending(&restored).unwrap(), tx);
+}
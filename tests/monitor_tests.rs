@@ -1,11 +1,25 @@
-use std::sync::Arc;
+mod common;
+
+use common::test_config;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tempfile::TempDir;
-use treasury_sweeper::balance_checker::DummyBalanceChecker;
+use treasury_sweeper::amount::{Amount, U256};
+use treasury_sweeper::balance_checker::{BalanceSource, DummyBalanceChecker};
+use treasury_sweeper::destinations::Destinations;
 use treasury_sweeper::monitor::WalletMonitor;
+use treasury_sweeper::report::WalletFailure;
 use treasury_sweeper::rules_engine::RulesEngine;
+use treasury_sweeper::scheduler::Scheduler;
 use treasury_sweeper::state_manager::StateManager;
-use treasury_sweeper::tx_emitter::MockTxEmitter;
-use treasury_sweeper::types::{Config, HotWalletConfig, SweepRule};
+use treasury_sweeper::sweep_caps::SweepCaps;
+use treasury_sweeper::timeouts::{TimedOut, TimeoutKind};
+use treasury_sweeper::tx_emitter::{MockTxEmitter, TxEmitter};
+use treasury_sweeper::types::{
+    CapAction, Config, HotWalletConfig, MockTransaction, RuleOutcome, SplitDestination,
+    SubmittedTx, SweepCap, SweepDecision, SweepRule, TimeoutConfig,
+};
 
 async fn create_test_monitor() -> (WalletMonitor, TempDir) {
     let temp_dir = TempDir::new().unwrap();
@@ -13,7 +27,7 @@ async fn create_test_monitor() -> (WalletMonitor, TempDir) {
     let state_manager = Arc::new(StateManager::load(state_path).await.unwrap());
 
     let balance_checker = DummyBalanceChecker::new(0.5, 1.0);
-    let rules_engine = Arc::new(RulesEngine::new(balance_checker));
+    let rules_engine = Arc::new(RulesEngine::new(Arc::new(balance_checker)));
 
     let tx_emitter = Arc::new(MockTxEmitter::new(
         state_manager,
@@ -29,18 +43,19 @@ async fn create_test_monitor() -> (WalletMonitor, TempDir) {
 async fn test_wallet_native_balance_sweep() {
     let (monitor, _temp_dir) = create_test_monitor().await;
 
-    let config = Config {
-        treasury_address: "0xTREASURY".to_string(),
-        hot_wallets: vec![HotWalletConfig {
-            address: "0x1234".to_string(),
-            label: "Test Wallet".to_string(),
-            rules: vec![SweepRule::NativeBalance {
-                threshold: "0.1".to_string(),
-                asset: "ETH".to_string(),
-            }],
+    let config = test_config(vec![HotWalletConfig {
+        address: "0x1234".to_string(),
+        label: "Test Wallet".to_string(),
+        rules: vec![SweepRule::NativeBalance {
+            threshold: "0.1".parse().unwrap(),
+            asset: "ETH".to_string(),
+            decimals: 18,
+            cooldown_seconds: None,
+            destination: None,
+            destinations: vec![],
         }],
-        sweep_interval_seconds: 60,
-    };
+        cooldown_seconds: None,
+    }]);
 
     let sweep_count = monitor.check_all_wallets(&config).await.unwrap();
     assert_eq!(sweep_count, 1);
@@ -51,25 +66,30 @@ async fn test_wallet_native_balance_sweep() {
 async fn test_multiple_rules_per_wallet() {
     let (monitor, _temp_dir) = create_test_monitor().await;
 
-    let config = Config {
-        treasury_address: "0xTREASURY".to_string(),
-        hot_wallets: vec![HotWalletConfig {
-            address: "0x1234".to_string(),
-            label: "Multi-Rule Wallet".to_string(),
-            rules: vec![
-                SweepRule::NativeBalance {
-                    threshold: "0.1".to_string(),
-                    asset: "ETH".to_string(),
-                },
-                SweepRule::TokenBalance {
-                    threshold: "50".to_string(),
-                    token_address: "0xUSDC".to_string(),
-                    asset: "USDC".to_string(),
-                },
-            ],
-        }],
-        sweep_interval_seconds: 60,
-    };
+    let config = test_config(vec![HotWalletConfig {
+        address: "0x1234".to_string(),
+        label: "Multi-Rule Wallet".to_string(),
+        rules: vec![
+            SweepRule::NativeBalance {
+                threshold: "0.1".parse().unwrap(),
+                asset: "ETH".to_string(),
+                decimals: 18,
+                cooldown_seconds: None,
+                destination: None,
+                destinations: vec![],
+            },
+            SweepRule::TokenBalance {
+                threshold: "50".parse().unwrap(),
+                token_address: "0xUSDC".to_string(),
+                asset: "USDC".to_string(),
+                decimals: 0,
+                cooldown_seconds: None,
+                destination: None,
+                destinations: vec![],
+            },
+        ],
+        cooldown_seconds: None,
+    }]);
 
     let sweep_count = monitor.check_all_wallets(&config).await.unwrap();
     assert_eq!(sweep_count, 2);
@@ -79,19 +99,567 @@ async fn test_multiple_rules_per_wallet() {
 async fn test_no_sweep_when_threshold_not_met() {
     let (monitor, _temp_dir) = create_test_monitor().await;
 
-    let config = Config {
-        treasury_address: "0xTREASURY".to_string(),
-        hot_wallets: vec![HotWalletConfig {
-            address: "0x1234".to_string(),
-            label: "High Threshold Wallet".to_string(),
-            rules: vec![SweepRule::NativeBalance {
-                threshold: "10.0".to_string(),
-                asset: "ETH".to_string(),
-            }],
+    let config = test_config(vec![HotWalletConfig {
+        address: "0x1234".to_string(),
+        label: "High Threshold Wallet".to_string(),
+        rules: vec![SweepRule::NativeBalance {
+            threshold: "10.0".parse().unwrap(),
+            asset: "ETH".to_string(),
+            decimals: 18,
+            cooldown_seconds: None,
+            destination: None,
+            destinations: vec![],
         }],
-        sweep_interval_seconds: 60,
-    };
+        cooldown_seconds: None,
+    }]);
 
     let sweep_count = monitor.check_all_wallets(&config).await.unwrap();
     assert_eq!(sweep_count, 0);
 }
+
+/// Emitter that records every sweep instead of building transactions
+#[derive(Default)]
+struct RecordingEmitter {
+    sweeps: Mutex<Vec<(String, Amount)>>,
+}
+
+#[async_trait::async_trait]
+impl TxEmitter for RecordingEmitter {
+    fn name(&self) -> &str {
+        "recording"
+    }
+
+    async fn emit_sweep(
+        &self,
+        from_address: &String,
+        decision: &SweepDecision,
+    ) -> anyhow::Result<SubmittedTx> {
+        self.sweeps
+            .lock()
+            .unwrap()
+            .push((decision.asset.clone(), decision.amount));
+        Ok(SubmittedTx {
+            hash: None,
+            tx: MockTransaction {
+                from: from_address.clone(),
+                to: "0xTREASURY".to_string(),
+                value: decision.amount,
+                asset: decision.asset.clone(),
+                nonce: 0,
+                token_address: decision.token_address.clone(),
+            },
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_custom_emitter_receives_decisions() {
+    let rules_engine = Arc::new(RulesEngine::new(Arc::new(DummyBalanceChecker::new(0.5, 1.0))));
+    let emitter = Arc::new(RecordingEmitter::default());
+    let monitor = WalletMonitor::new(rules_engine, emitter.clone());
+
+    let config = test_config(vec![HotWalletConfig {
+        address: "0x1234".to_string(),
+        label: "Test Wallet".to_string(),
+        rules: vec![SweepRule::NativeBalance {
+            threshold: "0.1".parse().unwrap(),
+            asset: "ETH".to_string(),
+            decimals: 18,
+            cooldown_seconds: None,
+            destination: None,
+            destinations: vec![],
+        }],
+        cooldown_seconds: None,
+    }]);
+
+    assert_eq!(monitor.check_all_wallets(&config).await.unwrap(), 1);
+    let sweeps = emitter.sweeps.lock().unwrap();
+    assert_eq!(sweeps.len(), 1);
+    assert_eq!(sweeps[0].0, "ETH");
+    assert!(sweeps[0].1 >= Amount::parse("0.5", 18).unwrap());
+}
+
+#[tokio::test]
+async fn test_sweep_caps_apply_before_emitting() {
+    let temp_dir = TempDir::new().unwrap();
+    let state_manager = Arc::new(
+        StateManager::load(temp_dir.path().join("state.json"))
+            .await
+            .unwrap(),
+    );
+    let rules_engine = Arc::new(RulesEngine::new(Arc::new(DummyBalanceChecker::new(0.5, 1.0))));
+    let emitter = Arc::new(RecordingEmitter::default());
+    let cap = |asset: &str, on_exceed| SweepCap {
+        wallet: None,
+        asset: Some(asset.to_string()),
+        max_per_sweep: Some("0.25".parse().unwrap()),
+        max_per_24h: None,
+        max_sweeps_per_day: None,
+        on_exceed,
+    };
+    let monitor = WalletMonitor::new(rules_engine, emitter.clone()).with_sweep_caps(
+        SweepCaps::new(
+            vec![cap("ETH", CapAction::Clip), cap("WETH", CapAction::Hold)],
+            state_manager,
+        ),
+    );
+
+    let native_rule = |asset: &str| SweepRule::NativeBalance {
+        threshold: "0.1".parse().unwrap(),
+        asset: asset.to_string(),
+        decimals: 18,
+        cooldown_seconds: None,
+        destination: None,
+        destinations: vec![],
+    };
+    let config = test_config(vec![HotWalletConfig {
+        address: "0x1234".to_string(),
+        label: "Test Wallet".to_string(),
+        rules: vec![native_rule("ETH"), native_rule("WETH")],
+        cooldown_seconds: None,
+    }]);
+
+    // The ETH sweep is clipped and the WETH sweep held
+    assert_eq!(monitor.check_all_wallets(&config).await.unwrap(), 1);
+    let sweeps = emitter.sweeps.lock().unwrap();
+    assert_eq!(
+        *sweeps,
+        vec![("ETH".to_string(), Amount::parse("0.25", 18).unwrap())]
+    );
+}
+
+#[tokio::test]
+async fn test_split_sweep_uses_consecutive_nonces_and_is_audited() {
+    let temp_dir = TempDir::new().unwrap();
+    let state_manager = Arc::new(
+        StateManager::load(temp_dir.path().join("state.json"))
+            .await
+            .unwrap(),
+    );
+    let rules_engine = Arc::new(RulesEngine::new(Arc::new(DummyBalanceChecker::new(0.5, 1.0))));
+
+    let mut config = test_config(vec![HotWalletConfig {
+        address: "0x1234".to_string(),
+        label: "Test Wallet".to_string(),
+        rules: vec![SweepRule::NativeBalance {
+            threshold: "0.1".parse().unwrap(),
+            asset: "ETH".to_string(),
+            decimals: 18,
+            cooldown_seconds: None,
+            destination: None,
+            destinations: vec![
+                SplitDestination {
+                    address: "0xCOLD".to_string(),
+                    bps: 8000,
+                    primary: false,
+                },
+                SplitDestination {
+                    address: "0xEXCHANGE".to_string(),
+                    bps: 2000,
+                    primary: true,
+                },
+            ],
+        }],
+        cooldown_seconds: None,
+    }]);
+    config.allowed_destinations = vec!["0xCOLD".to_string(), "0xEXCHANGE".to_string()];
+    let tx_emitter = Arc::new(
+        MockTxEmitter::new(state_manager.clone(), config.treasury_address.clone())
+            .with_destinations(Destinations::from_config(&config)),
+    );
+    let monitor = WalletMonitor::new(rules_engine, tx_emitter);
+
+    assert_eq!(monitor.check_all_wallets(&config).await.unwrap(), 2);
+
+    let wallet = state_manager.fetch_snapshot().await.wallets["0x1234"].clone();
+    assert_eq!(wallet.next_nonce, 2);
+    let [cold, exchange] = &wallet.sweep_history[..] else {
+        panic!("expected two sweep records");
+    };
+    assert_eq!(cold.destination.as_deref(), Some("0xCOLD"));
+    assert_eq!(exchange.destination.as_deref(), Some("0xEXCHANGE"));
+    let (cold_split, exchange_split) = (cold.split.unwrap(), exchange.split.unwrap());
+    assert_eq!((cold_split.bps, exchange_split.bps), (8000, 2000));
+    assert_eq!(cold_split.total, exchange_split.total);
+    assert!(cold_split.first && !exchange_split.first);
+
+    // The cold share is rounded down; the exchange takes the dust
+    let total = cold_split.total.units();
+    assert_eq!(cold.amount.units(), total * U256::from(8000) / U256::from(10_000));
+    assert_eq!(cold.amount.checked_add(&exchange.amount), Some(cold_split.total));
+}
+
+#[tokio::test]
+async fn test_split_sweep_is_capped_as_a_whole() {
+    let temp_dir = TempDir::new().unwrap();
+    let state_manager = Arc::new(
+        StateManager::load(temp_dir.path().join("state.json"))
+            .await
+            .unwrap(),
+    );
+    let rules_engine = Arc::new(RulesEngine::new(Arc::new(DummyBalanceChecker::new(0.5, 1.0))));
+    let emitter = Arc::new(RecordingEmitter::default());
+    let monitor = WalletMonitor::new(rules_engine, emitter.clone()).with_sweep_caps(
+        SweepCaps::new(
+            vec![SweepCap {
+                wallet: None,
+                asset: Some("ETH".to_string()),
+                max_per_sweep: Some("0.49".parse().unwrap()),
+                max_per_24h: None,
+                max_sweeps_per_day: None,
+                on_exceed: CapAction::Hold,
+            }],
+            state_manager,
+        ),
+    );
+
+    let split = |address: &str, primary| SplitDestination {
+        address: address.to_string(),
+        bps: 5000,
+        primary,
+    };
+    let mut config = test_config(vec![HotWalletConfig {
+        address: "0x1234".to_string(),
+        label: "Test Wallet".to_string(),
+        rules: vec![SweepRule::NativeBalance {
+            threshold: "0.1".parse().unwrap(),
+            asset: "ETH".to_string(),
+            decimals: 18,
+            cooldown_seconds: None,
+            destination: None,
+            destinations: vec![split("0xCOLD", false), split("0xEXCHANGE", true)],
+        }],
+        cooldown_seconds: None,
+    }]);
+    config.allowed_destinations = vec!["0xCOLD".to_string(), "0xEXCHANGE".to_string()];
+
+    // Each leg may be under the cap, but the whole sweep is not: no leg is sent
+    let report = monitor.run_cycle(&config).await.unwrap();
+    assert!(emitter.sweeps.lock().unwrap().is_empty());
+    assert_eq!(report.wallets[0].held.len(), 2);
+}
+
+/// Native balance of 1 ETH that takes a while to read, tracking how many reads
+/// overlap; fails for `0xBAD`
+#[derive(Default)]
+struct SlowBalance {
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+}
+
+#[async_trait::async_trait]
+impl BalanceSource for SlowBalance {
+    fn name(&self) -> &str {
+        "slow"
+    }
+
+    async fn check_native_balance(&self, address: &String) -> anyhow::Result<U256> {
+        let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+        anyhow::ensure!(address != "0xBAD", "node unavailable");
+        Ok(Amount::parse("1", 18).unwrap().units())
+    }
+
+    async fn check_token_balance(
+        &self,
+        _address: &String,
+        _token: &String,
+    ) -> anyhow::Result<U256> {
+        Ok(U256::ZERO)
+    }
+}
+
+#[tokio::test]
+async fn test_wallets_are_checked_with_bounded_parallelism() {
+    let temp_dir = TempDir::new().unwrap();
+    let state_manager = Arc::new(
+        StateManager::load(temp_dir.path().join("state.json"))
+            .await
+            .unwrap(),
+    );
+    let balance = Arc::new(SlowBalance::default());
+    let rules_engine = Arc::new(RulesEngine::new(balance.clone()));
+    let tx_emitter = Arc::new(MockTxEmitter::new(
+        state_manager.clone(),
+        "0xTREASURY".to_string(),
+    ));
+    let monitor = WalletMonitor::new(rules_engine, tx_emitter);
+
+    let wallet = |address: String| HotWalletConfig {
+        address,
+        label: "Test Wallet".to_string(),
+        rules: vec![SweepRule::NativeBalance {
+            threshold: "0.1".parse().unwrap(),
+            asset: "ETH".to_string(),
+            decimals: 18,
+            cooldown_seconds: None,
+            destination: None,
+            destinations: vec![],
+        }],
+        cooldown_seconds: None,
+    };
+    let mut wallets: Vec<_> = (0..10).map(|i| wallet(format!("0x{:04}", i))).collect();
+    wallets.insert(4, wallet("0xBAD".to_string()));
+    let mut config = test_config(wallets);
+    config.max_parallel_wallets = 3;
+
+    // The failing wallet is left out of the count without stopping the others
+    assert_eq!(monitor.check_all_wallets(&config).await.unwrap(), 10);
+    assert_eq!(balance.max_in_flight.load(Ordering::SeqCst), 3);
+
+    let state = state_manager.fetch_snapshot().await;
+    assert_eq!(state.wallets.len(), 10);
+    assert!(state.wallets.values().all(|wallet| wallet.next_nonce == 1));
+}
+
+/// Native balance of 1 ETH, except for `0xHANG` whose query never returns
+struct HangingBalance;
+
+#[async_trait::async_trait]
+impl BalanceSource for HangingBalance {
+    fn name(&self) -> &str {
+        "hanging"
+    }
+
+    async fn check_native_balance(&self, address: &String) -> anyhow::Result<U256> {
+        if address == "0xHANG" {
+            std::future::pending::<()>().await;
+        }
+        Ok(Amount::parse("1", 18).unwrap().units())
+    }
+
+    async fn check_token_balance(
+        &self,
+        address: &String,
+        _token: &String,
+    ) -> anyhow::Result<U256> {
+        if address == "0xSLOW" {
+            std::future::pending::<()>().await;
+        }
+        Ok(U256::ZERO)
+    }
+}
+
+async fn hanging_setup(
+    balance_timeout: Option<u64>,
+    timeouts: TimeoutConfig,
+) -> (WalletMonitor, Config, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let state_manager = Arc::new(
+        StateManager::load(temp_dir.path().join("state.json"))
+            .await
+            .unwrap(),
+    );
+    let mut rules_engine = RulesEngine::new(Arc::new(HangingBalance));
+    if let Some(seconds) = balance_timeout {
+        rules_engine = rules_engine.with_balance_timeout(seconds);
+    }
+    let tx_emitter = Arc::new(MockTxEmitter::new(state_manager, "0xTREASURY".to_string()));
+    let monitor = WalletMonitor::new(Arc::new(rules_engine), tx_emitter);
+
+    let wallet = |address: &str| HotWalletConfig {
+        address: address.to_string(),
+        label: "Test Wallet".to_string(),
+        rules: vec![SweepRule::NativeBalance {
+            threshold: "0.1".parse().unwrap(),
+            asset: "ETH".to_string(),
+            decimals: 18,
+            cooldown_seconds: None,
+            destination: None,
+            destinations: vec![],
+        }],
+        cooldown_seconds: None,
+    };
+    let mut config = test_config(vec![wallet("0xHANG"), wallet("0xOK")]);
+    config.timeouts = timeouts;
+    (monitor, config, temp_dir)
+}
+
+fn timed_out(kind: TimeoutKind) -> Option<WalletFailure> {
+    Some(WalletFailure::TimedOut(TimedOut { kind, seconds: 1 }))
+}
+
+#[tokio::test]
+async fn test_balance_and_wallet_timeouts_fail_only_that_wallet() {
+    let (monitor, config, _temp_dir) = hanging_setup(Some(1), TimeoutConfig::default()).await;
+    let checks = monitor.check_wallets(&config).await.unwrap();
+    assert_eq!(checks[0].address, "0xHANG");
+    assert_eq!(checks[0].error, timed_out(TimeoutKind::Balance));
+    assert_eq!(checks[1].error, None);
+    assert_eq!(checks[1].sweep_count(), 1);
+
+    let timeouts = TimeoutConfig {
+        wallet_seconds: Some(1),
+        ..TimeoutConfig::default()
+    };
+    let (monitor, config, _temp_dir) = hanging_setup(None, timeouts).await;
+    assert_eq!(monitor.check_all_wallets(&config).await.unwrap(), 1);
+    let checks = monitor.check_wallets(&config).await.unwrap();
+    assert_eq!(checks[0].error, timed_out(TimeoutKind::Wallet));
+}
+
+#[tokio::test]
+async fn test_cycle_timeout_marks_unfinished_wallets() {
+    let timeouts = TimeoutConfig {
+        cycle_seconds: Some(1),
+        ..TimeoutConfig::default()
+    };
+    let (monitor, mut config, _temp_dir) = hanging_setup(None, timeouts).await;
+    // One at a time, so the second wallet never gets to start
+    config.max_parallel_wallets = 1;
+
+    let checks = monitor.check_wallets(&config).await.unwrap();
+    assert_eq!(checks[0].error, timed_out(TimeoutKind::Cycle));
+    assert_eq!(checks[1].error, timed_out(TimeoutKind::Cycle));
+}
+
+/// Emitter whose sweeps take longer than the wallet timeout to confirm
+#[derive(Default)]
+struct SlowEmitter {
+    inner: RecordingEmitter,
+}
+
+#[async_trait::async_trait]
+impl TxEmitter for SlowEmitter {
+    fn name(&self) -> &str {
+        "slow"
+    }
+
+    async fn emit_sweep(
+        &self,
+        from_address: &String,
+        decision: &SweepDecision,
+    ) -> anyhow::Result<SubmittedTx> {
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        self.inner.emit_sweep(from_address, decision).await
+    }
+}
+
+#[tokio::test]
+async fn test_wallet_timeout_lets_a_started_sweep_finish() {
+    let rules_engine = Arc::new(RulesEngine::new(Arc::new(HangingBalance)));
+    let emitter = Arc::new(SlowEmitter::default());
+    let monitor = WalletMonitor::new(rules_engine, emitter.clone());
+    let native_rule = |asset: &str| SweepRule::NativeBalance {
+        threshold: "0.1".parse().unwrap(),
+        asset: asset.to_string(),
+        decimals: 18,
+        cooldown_seconds: None,
+        destination: None,
+        destinations: vec![],
+    };
+    let mut config = test_config(vec![HotWalletConfig {
+        address: "0xOK".to_string(),
+        label: "Test Wallet".to_string(),
+        rules: vec![native_rule("ETH"), native_rule("WETH")],
+        cooldown_seconds: None,
+    }]);
+    config.timeouts = TimeoutConfig {
+        wallet_seconds: Some(1),
+        ..TimeoutConfig::default()
+    };
+
+    // The first sweep outlasts the timeout but is reported; the second is
+    // never started
+    let checks = monitor.check_wallets(&config).await.unwrap();
+    assert_eq!(checks[0].error, timed_out(TimeoutKind::Wallet));
+    assert_eq!(checks[0].sweep_count(), 1);
+    assert_eq!(checks[0].transactions[0].tx.asset, "ETH");
+    assert_eq!(emitter.inner.sweeps.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_cycle_report_covers_rules_transactions_and_failures() {
+    let temp_dir = TempDir::new().unwrap();
+    let state_manager = Arc::new(
+        StateManager::load(temp_dir.path().join("state.json"))
+            .await
+            .unwrap(),
+    );
+    let reservation = state_manager.reserve_nonce(&"0xOK".to_string()).await.unwrap();
+    reservation
+        .commit_sweep("WETH", Amount::parse("1", 18).unwrap())
+        .await
+        .unwrap();
+    let rules_engine = RulesEngine::new(Arc::new(HangingBalance))
+        .with_state_manager(state_manager.clone())
+        .with_balance_timeout(1);
+    let tx_emitter = Arc::new(MockTxEmitter::new(state_manager, "0xTREASURY".to_string()));
+    let monitor = WalletMonitor::new(Arc::new(rules_engine), tx_emitter);
+
+    let rule = |asset: &str, threshold: &str, cooldown_seconds| SweepRule::NativeBalance {
+        threshold: threshold.parse().unwrap(),
+        asset: asset.to_string(),
+        decimals: 18,
+        cooldown_seconds,
+        destination: None,
+        destinations: vec![],
+    };
+    let wallet = |address: &str| HotWalletConfig {
+        address: address.to_string(),
+        label: address.to_string(),
+        rules: vec![
+            rule("ETH", "0.1", None),
+            rule("ETH", "5", None),
+            rule("WETH", "0.1", Some(3600)),
+        ],
+        cooldown_seconds: None,
+    };
+    // Its native rule is evaluated before its token balance hangs
+    let slow = HotWalletConfig {
+        address: "0xSLOW".to_string(),
+        label: "0xSLOW".to_string(),
+        rules: vec![
+            rule("ETH", "5", None),
+            SweepRule::TokenBalance {
+                threshold: "50".parse().unwrap(),
+                token_address: "0xUSDC".to_string(),
+                asset: "USDC".to_string(),
+                decimals: 6,
+                cooldown_seconds: None,
+                destination: None,
+                destinations: vec![],
+            },
+        ],
+        cooldown_seconds: None,
+    };
+    let config = test_config(vec![wallet("0xOK"), wallet("0xHANG"), slow]);
+
+    let report = Scheduler::new(Arc::new(monitor), config).run_once().await.unwrap();
+    assert_eq!(report.sweep_count, 1);
+    assert_eq!(report.failed_wallets, 2);
+
+    let ok = &report.wallets[0];
+    let outcomes: Vec<_> = ok.rules.iter().map(|rule| rule.outcome.clone()).collect();
+    assert!(matches!(
+        &outcomes[..],
+        [
+            RuleOutcome::Triggered { sweeps: 1 },
+            RuleOutcome::NotTriggered,
+            RuleOutcome::Skipped { reason },
+        ] if reason.contains("cooldown_seconds")
+    ));
+    assert_eq!(ok.rules[1].balance, Amount::parse("1", 18).unwrap());
+    assert_eq!(ok.rules[1].threshold, Some(Amount::parse("5", 18).unwrap()));
+    assert_eq!(ok.transactions.len(), 1);
+    assert_eq!(ok.transactions[0].tx.nonce, 1);
+    assert_eq!(ok.skipped.len(), 1);
+
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["wallets"][0]["rules"][1]["outcome"], "not_triggered");
+    assert_eq!(
+        json["wallets"][0]["rules"][1]["threshold"],
+        "5.000000000000000000"
+    );
+    assert_eq!(json["wallets"][1]["address"], "0xHANG");
+    assert_eq!(json["wallets"][1]["error"]["kind"], "timed_out");
+    assert_eq!(json["wallets"][1]["error"]["detail"]["kind"], "balance");
+
+    // A failed wallet keeps the rules evaluated before the failure
+    let slow = &report.wallets[2];
+    assert_eq!(slow.rules.len(), 1);
+    assert_eq!(slow.rules[0].outcome, RuleOutcome::NotTriggered);
+    assert!(matches!(slow.error, Some(WalletFailure::TimedOut(_))));
+}
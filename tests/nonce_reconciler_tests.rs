@@ -0,0 +1,210 @@
+mod common;
+
+use common::{MockRpcServer, test_config};
+use serde_json::json;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tempfile::TempDir;
+use treasury_sweeper::nonce_reconciler::{NonceReconciler, ReconcileAction};
+use treasury_sweeper::rpc::JsonRpcClient;
+use treasury_sweeper::state_manager::StateManager;
+use treasury_sweeper::types::{
+    Config, EmitterConfig, GasStationConfig, HotWalletConfig, NoncePolicy,
+    NonceReconciliationConfig,
+};
+
+const WALLET: &str = "0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f";
+const STATION: &str = "0x5aeda56215b167893e80b4fe645ba6d5bab767de";
+
+/// Node whose pending transaction count can be changed by the test
+async fn node(count: Arc<AtomicU64>) -> MockRpcServer {
+    MockRpcServer::start(move |method, params| match method {
+        "eth_getTransactionCount" => {
+            assert_eq!(params[1], "pending");
+            Ok(json!(format!("{:#x}", count.load(Ordering::SeqCst))))
+        }
+        _ => panic!("unexpected method {}", method),
+    })
+    .await
+}
+
+async fn setup(
+    policy: NoncePolicy,
+    url: &str,
+    local_nonce: u64,
+) -> (NonceReconciler, Arc<StateManager>, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let state_manager = Arc::new(
+        StateManager::load(temp_dir.path().join("state.json"))
+            .await
+            .unwrap(),
+    );
+    state_manager
+        .set_next_nonce(&WALLET.to_string(), local_nonce)
+        .await
+        .unwrap();
+
+    let rpc = Arc::new(JsonRpcClient::new(url).unwrap());
+    let reconciler = NonceReconciler::new(rpc, state_manager.clone(), policy);
+    (reconciler, state_manager, temp_dir)
+}
+
+fn config(reconciliation: Option<NonceReconciliationConfig>) -> Config {
+    Config {
+        nonce_reconciliation: reconciliation,
+        ..test_config(vec![HotWalletConfig {
+            address: WALLET.to_string(),
+            label: "Test Wallet".to_string(),
+            rules: vec![],
+            cooldown_seconds: None,
+        }])
+    }
+}
+
+#[tokio::test]
+async fn test_matching_nonce_is_not_a_discrepancy() {
+    let server = node(Arc::new(AtomicU64::new(4))).await;
+    let (reconciler, _state_manager, _temp_dir) = setup(NoncePolicy::Halt, &server.url, 4).await;
+
+    assert!(reconciler.reconcile_all(&config(None)).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_adopt_policy_takes_chain_nonce() {
+    let server = node(Arc::new(AtomicU64::new(7))).await;
+    let (reconciler, state_manager, _temp_dir) = setup(NoncePolicy::Adopt, &server.url, 4).await;
+
+    let discrepancy = reconciler
+        .reconcile_wallet(&WALLET.to_string())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(discrepancy.local_nonce, 4);
+    assert_eq!(discrepancy.chain_nonce, 7);
+    assert_eq!(discrepancy.action, ReconcileAction::Adopted);
+
+    let reservation = state_manager
+        .reserve_nonce(&WALLET.to_string())
+        .await
+        .unwrap();
+    assert_eq!(reservation.nonce(), 7);
+}
+
+#[tokio::test]
+async fn test_halt_policy_blocks_reservations_until_nonces_agree() {
+    let count = Arc::new(AtomicU64::new(2));
+    let server = node(count.clone()).await;
+    let (reconciler, state_manager, _temp_dir) = setup(NoncePolicy::Halt, &server.url, 5).await;
+    let wallet = WALLET.to_string();
+
+    let discrepancy = reconciler.reconcile_wallet(&wallet).await.unwrap().unwrap();
+    assert_eq!(discrepancy.action, ReconcileAction::Halted);
+    assert_eq!(state_manager.next_nonce(&wallet).await, 5);
+
+    let err = state_manager.reserve_nonce(&wallet).await.err().unwrap();
+    assert!(err.to_string().contains("halted"));
+
+    // The missing transactions reach the node
+    count.store(5, Ordering::SeqCst);
+    assert!(
+        reconciler
+            .reconcile_wallet(&wallet)
+            .await
+            .unwrap()
+            .is_none()
+    );
+    assert_eq!(state_manager.halted_reason(&wallet).await, None);
+    assert_eq!(
+        state_manager.reserve_nonce(&wallet).await.unwrap().nonce(),
+        5
+    );
+}
+
+#[tokio::test]
+async fn test_alert_policy_keeps_stored_nonce() {
+    let server = node(Arc::new(AtomicU64::new(9))).await;
+    let (reconciler, state_manager, _temp_dir) = setup(NoncePolicy::Alert, &server.url, 3).await;
+
+    let discrepancies = reconciler.reconcile_all(&config(None)).await;
+    assert_eq!(discrepancies.len(), 1);
+    assert_eq!(discrepancies[0].action, ReconcileAction::Alerted);
+    assert_eq!(
+        state_manager
+            .reserve_nonce(&WALLET.to_string())
+            .await
+            .unwrap()
+            .nonce(),
+        3
+    );
+}
+
+#[tokio::test]
+async fn test_reconcile_all_covers_the_gas_station() {
+    // The hot wallet agrees with the chain; the station is behind it
+    let server = MockRpcServer::start(|method, params| match method {
+        "eth_getTransactionCount" => match params[0].as_str().unwrap() {
+            WALLET => Ok(json!("0x4")),
+            STATION => Ok(json!("0x2")),
+            address => panic!("unexpected address {}", address),
+        },
+        _ => panic!("unexpected method {}", method),
+    })
+    .await;
+    let (reconciler, state_manager, _temp_dir) = setup(NoncePolicy::Adopt, &server.url, 4).await;
+    let config = Config {
+        emitter: EmitterConfig::Evm {
+            rpc_url: server.url.clone(),
+            chain_id: 1,
+            keystore: "keys.json".into(),
+            receipt_poll_interval_ms: 10,
+            receipt_timeout_seconds: 1,
+            gas_station: Some(GasStationConfig {
+                address: STATION.to_string(),
+                top_up_amount: None,
+            }),
+        },
+        ..config(None)
+    };
+
+    let discrepancies = reconciler.reconcile_all(&config).await;
+    let [discrepancy] = &discrepancies[..] else {
+        panic!("expected only the station to disagree");
+    };
+    assert_eq!(discrepancy.address, STATION);
+    assert_eq!(discrepancy.action, ReconcileAction::Adopted);
+    assert_eq!(state_manager.next_nonce(&STATION.to_string()).await, 2);
+}
+
+#[tokio::test]
+async fn test_from_config_requires_a_node() {
+    let temp_dir = TempDir::new().unwrap();
+    let state_manager = Arc::new(
+        StateManager::load(temp_dir.path().join("state.json"))
+            .await
+            .unwrap(),
+    );
+
+    assert!(
+        NonceReconciler::from_config(&config(None), state_manager.clone())
+            .unwrap()
+            .is_none()
+    );
+    assert!(
+        NonceReconciler::from_config(
+            &config(Some(NonceReconciliationConfig::default())),
+            state_manager.clone()
+        )
+        .is_err()
+    );
+
+    let reconciler = NonceReconciler::from_config(
+        &config(Some(NonceReconciliationConfig {
+            policy: NoncePolicy::Adopt,
+            rpc_url: Some("http://127.0.0.1:8545".to_string()),
+        })),
+        state_manager,
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(reconciler.policy(), NoncePolicy::Adopt);
+}
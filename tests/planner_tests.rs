@@ -0,0 +1,187 @@
+mod common;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use tempfile::TempDir;
+use treasury_sweeper::amount::{Amount, NATIVE_DECIMALS};
+use treasury_sweeper::balance_checker::DummyBalanceChecker;
+use treasury_sweeper::destinations::Destinations;
+use treasury_sweeper::planner::{PlannedAction, Planner};
+use treasury_sweeper::rules_engine::RulesEngine;
+use treasury_sweeper::state_manager::StateManager;
+use treasury_sweeper::sweep_caps::SweepCaps;
+use treasury_sweeper::types::{
+    CapAction, Config, GasConfig, HotWalletConfig, SplitDestination, SweepCap, SweepRule,
+};
+
+/// Config routing USDC to `0xEXCHANGE` and allowing `0xCOLD` as well
+fn test_config(hot_wallets: Vec<HotWalletConfig>) -> Config {
+    Config {
+        routes: HashMap::from([("USDC".to_string(), "0xEXCHANGE".to_string())]),
+        allowed_destinations: vec![
+            "0xTREASURY".to_string(),
+            "0xCOLD".to_string(),
+            "0xEXCHANGE".to_string(),
+        ],
+        ..common::test_config(hot_wallets)
+    }
+}
+
+#[tokio::test]
+async fn test_plan_resolves_destinations_and_fees_without_touching_state() {
+    let temp_dir = TempDir::new().unwrap();
+    let state_path = temp_dir.path().join("state.json");
+    let state_manager = Arc::new(StateManager::load(state_path.clone()).await.unwrap());
+    let rules_engine = Arc::new(
+        RulesEngine::new(Arc::new(DummyBalanceChecker::new(0.5, 1.0)))
+            .with_state_manager(state_manager.clone()),
+    );
+
+    let config = test_config(vec![HotWalletConfig {
+        address: "0x1234".to_string(),
+        label: "Test Wallet".to_string(),
+        rules: vec![
+            SweepRule::NativeBalance {
+                threshold: "0.1".parse().unwrap(),
+                asset: "ETH".to_string(),
+                decimals: 18,
+                cooldown_seconds: None,
+                destination: None,
+                destinations: vec![
+                    SplitDestination {
+                        address: "0xCOLD".to_string(),
+                        bps: 8000,
+                        primary: true,
+                    },
+                    SplitDestination {
+                        address: "0xTREASURY".to_string(),
+                        bps: 2000,
+                        primary: false,
+                    },
+                ],
+            },
+            SweepRule::TokenBalance {
+                threshold: "50".parse().unwrap(),
+                token_address: "0xTOKEN".to_string(),
+                asset: "USDC".to_string(),
+                decimals: 0,
+                cooldown_seconds: None,
+                destination: None,
+                destinations: vec![],
+            },
+        ],
+        cooldown_seconds: None,
+    }]);
+    let cap = SweepCap {
+        wallet: None,
+        asset: Some("USDC".to_string()),
+        max_per_sweep: Some("10".parse().unwrap()),
+        max_per_24h: None,
+        max_sweeps_per_day: None,
+        on_exceed: CapAction::Hold,
+    };
+    let gas = GasConfig::default();
+    let planner = Planner::new(rules_engine, Destinations::from_config(&config))
+        .with_fees(
+            gas.native_sweep_fee().unwrap(),
+            gas.token_sweep_fee().unwrap(),
+        )
+        .with_sweep_caps(SweepCaps::new(vec![cap], state_manager.clone()));
+
+    let plan = planner.plan(&config).await;
+
+    let [wallet] = &plan.wallets[..] else {
+        panic!("expected one wallet plan");
+    };
+    assert!(wallet.error.is_none());
+    let destinations: Vec<_> = wallet
+        .sweeps
+        .iter()
+        .map(|sweep| (sweep.asset.as_str(), sweep.destination.as_str()))
+        .collect();
+    assert_eq!(
+        destinations,
+        vec![
+            ("ETH", "0xCOLD"),
+            ("ETH", "0xTREASURY"),
+            ("USDC", "0xEXCHANGE")
+        ]
+    );
+    let native_fee = Amount::from_units(gas.native_sweep_fee().unwrap(), NATIVE_DECIMALS);
+    let token_fee = Amount::from_units(gas.token_sweep_fee().unwrap(), NATIVE_DECIMALS);
+    let fees: Vec<_> = wallet
+        .sweeps
+        .iter()
+        .map(|sweep| sweep.estimated_fee)
+        .collect();
+    assert_eq!(
+        fees,
+        vec![Some(native_fee), Some(native_fee), Some(token_fee)]
+    );
+
+    // Both split legs add up to the whole balance; the token sweep is held
+    let (cold, treasury) = (&wallet.sweeps[0], &wallet.sweeps[1]);
+    assert_eq!(cold.action, PlannedAction::Send);
+    assert_eq!(
+        cold.amount.checked_add(&treasury.amount),
+        Some(cold.split.unwrap().total)
+    );
+    assert!(matches!(
+        wallet.sweeps[2].action,
+        PlannedAction::Hold { .. }
+    ));
+    assert_eq!(plan.sweep_count, 2);
+
+    // Nothing was reserved or written
+    assert!(state_manager.fetch_snapshot().await.wallets.is_empty());
+    assert!(!state_path.exists());
+
+    let json = serde_json::to_value(&plan).unwrap();
+    let sweep = &json["wallets"][0]["sweeps"][2];
+    assert_eq!(sweep["action"], "hold");
+    assert_eq!(sweep["destination"], "0xEXCHANGE");
+    assert!(sweep["reason"].as_str().unwrap().contains("max_per_sweep"));
+}
+
+#[tokio::test]
+async fn test_plan_reports_refused_destination_as_wallet_error() {
+    let temp_dir = TempDir::new().unwrap();
+    let state_manager = Arc::new(
+        StateManager::load(temp_dir.path().join("state.json"))
+            .await
+            .unwrap(),
+    );
+    let rules_engine = Arc::new(
+        RulesEngine::new(Arc::new(DummyBalanceChecker::new(0.5, 1.0)))
+            .with_state_manager(state_manager),
+    );
+    let native_rule = |destination: &str| SweepRule::NativeBalance {
+        threshold: "0.1".parse().unwrap(),
+        asset: "ETH".to_string(),
+        decimals: 18,
+        cooldown_seconds: None,
+        destination: Some(destination.to_string()),
+        destinations: vec![],
+    };
+    let wallet = |address: &str, destination: &str| HotWalletConfig {
+        address: address.to_string(),
+        label: address.to_string(),
+        rules: vec![native_rule(destination)],
+        cooldown_seconds: None,
+    };
+    let config = test_config(vec![wallet("0xA", "0xELSEWHERE"), wallet("0xB", "0xCOLD")]);
+
+    // No fees without an on-chain emitter
+    let plan = Planner::new(rules_engine, Destinations::from_config(&config))
+        .plan(&config)
+        .await;
+
+    assert!(plan.wallets[0].error.is_some());
+    assert!(plan.wallets[0].sweeps.is_empty());
+    let [sweep] = &plan.wallets[1].sweeps[..] else {
+        panic!("expected one planned sweep");
+    };
+    assert_eq!(sweep.destination, "0xCOLD");
+    assert_eq!(sweep.estimated_fee, None);
+    assert_eq!(plan.sweep_count, 1);
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use chrono::{Duration, Utc};
+use common::MockHttpServer;
+use serde_json::json;
+use tempfile::TempDir;
+use treasury_sweeper::amount::Amount;
+use treasury_sweeper::price_oracle::{self, HttpPriceOracle, PriceOracle, StaticPriceOracle};
+use treasury_sweeper::types::PriceOracleConfig;
+
+#[tokio::test]
+async fn test_static_oracle_reads_price_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("prices.json");
+    std::fs::write(
+        &path,
+        r#"{"ETH": {"usd": "3012.55", "updated_at": "2026-10-17T09:00:00Z"}}"#,
+    )
+    .unwrap();
+    let oracle = StaticPriceOracle::new(path.clone());
+
+    let price = oracle.price("ETH").await.unwrap();
+    assert_eq!(price.usd, "3012.55".parse::<Amount>().unwrap());
+    assert_eq!(price.updated_at.to_rfc3339(), "2026-10-17T09:00:00+00:00");
+    assert!(oracle.price("BTC").await.is_err());
+
+    // Updates to the file are picked up without a restart
+    std::fs::write(
+        &path,
+        r#"{"ETH": {"usd": "2900", "updated_at": "2026-10-17T10:00:00Z"}}"#,
+    )
+    .unwrap();
+    let price = oracle.price("ETH").await.unwrap();
+    assert_eq!(price.usd, "2900".parse::<Amount>().unwrap());
+}
+
+#[tokio::test]
+async fn test_http_oracle_queries_asset_path() {
+    let server = MockHttpServer::start(|path| match path {
+        "/prices/ETH" => Some(json!({"usd": "3000", "updated_at": "2026-10-17T09:00:00Z"})),
+        "/prices/BAD" => Some(json!({"usd": 3000})),
+        _ => None,
+    })
+    .await;
+    let oracle = HttpPriceOracle::new(format!("{}/prices/", server.url)).unwrap();
+
+    let price = oracle.price("ETH").await.unwrap();
+    assert_eq!(price.usd, "3000".parse::<Amount>().unwrap());
+
+    let err = oracle.price("BTC").await.unwrap_err();
+    assert!(format!("{:#}", err).contains("404"));
+    let err = oracle.price("BAD").await.unwrap_err();
+    assert!(err.to_string().contains("Invalid price response"));
+}
+
+#[tokio::test]
+async fn test_price_staleness_and_config() {
+    let oracle_config: PriceOracleConfig =
+        serde_json::from_str(r#"{"type": "http", "url": "http://127.0.0.1:9"}"#).unwrap();
+    assert_eq!(oracle_config.max_age_seconds(), 300);
+    assert_eq!(
+        price_oracle::from_config(&oracle_config).unwrap().name(),
+        "http"
+    );
+
+    let now = Utc::now();
+    let price = treasury_sweeper::price_oracle::Price {
+        usd: "3000".parse().unwrap(),
+        updated_at: now - Duration::seconds(301),
+    };
+    assert!(price.is_stale(300, now));
+    assert!(!price.is_stale(301, now));
+}
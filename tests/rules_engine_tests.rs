@@ -1,23 +1,34 @@
-use treasury_sweeper::balance_checker::DummyBalanceChecker;
+use chrono::Utc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use treasury_sweeper::amount::{Amount, U256};
+use treasury_sweeper::balance_checker::{BalanceSource, DummyBalanceChecker, from_config};
+use treasury_sweeper::price_oracle::{Price, PriceOracle};
 use treasury_sweeper::rules_engine::RulesEngine;
-use treasury_sweeper::types::{HotWalletConfig, SweepRule};
+use treasury_sweeper::state_manager::StateManager;
+use treasury_sweeper::types::{BalanceSourceConfig, Config, GasConfig, HotWalletConfig, SweepRule};
 
 fn create_test_wallet(rules: Vec<SweepRule>) -> HotWalletConfig {
     HotWalletConfig {
         address: "0xTestWallet".to_string(),
         label: "Test Wallet".to_string(),
         rules,
+        cooldown_seconds: None,
     }
 }
 
 #[tokio::test]
 async fn test_native_balance_rule_triggers() {
     let checker = DummyBalanceChecker::new(0.5, 1.0);
-    let engine = RulesEngine::new(checker);
+    let engine = RulesEngine::new(Arc::new(checker));
 
     let wallet = create_test_wallet(vec![SweepRule::NativeBalance {
-        threshold: "0.1".to_string(),
+        threshold: "0.1".parse().unwrap(),
         asset: "ETH".to_string(),
+        decimals: 18,
+        cooldown_seconds: None,
+        destination: None,
+        destinations: vec![],
     }]);
 
     let decisions = engine.evaluate(&wallet).await.unwrap();
@@ -30,11 +41,15 @@ async fn test_native_balance_rule_triggers() {
 #[tokio::test]
 async fn test_native_balance_rule_no_trigger() {
     let checker = DummyBalanceChecker::new(0.01, 0.05);
-    let engine = RulesEngine::new(checker);
+    let engine = RulesEngine::new(Arc::new(checker));
 
     let wallet = create_test_wallet(vec![SweepRule::NativeBalance {
-        threshold: "0.1".to_string(),
+        threshold: "0.1".parse().unwrap(),
         asset: "ETH".to_string(),
+        decimals: 18,
+        cooldown_seconds: None,
+        destination: None,
+        destinations: vec![],
     }]);
 
     let decisions = engine.evaluate(&wallet).await.unwrap();
@@ -44,12 +59,16 @@ async fn test_native_balance_rule_no_trigger() {
 #[tokio::test]
 async fn test_token_balance_rule_triggers() {
     let checker = DummyBalanceChecker::new(0.0, 1.0);
-    let engine = RulesEngine::new(checker);
+    let engine = RulesEngine::new(Arc::new(checker));
 
     let wallet = create_test_wallet(vec![SweepRule::TokenBalance {
-        threshold: "50".to_string(),
+        threshold: "50".parse().unwrap(),
         token_address: "0xUSDC".to_string(),
         asset: "USDC".to_string(),
+        decimals: 0,
+        cooldown_seconds: None,
+        destination: None,
+        destinations: vec![],
     }]);
 
     let decisions = engine.evaluate(&wallet).await.unwrap();
@@ -65,22 +84,34 @@ async fn test_token_balance_rule_triggers() {
 #[tokio::test]
 async fn test_multiple_rules_all_trigger() {
     let checker = DummyBalanceChecker::new(0.5, 1.0);
-    let engine = RulesEngine::new(checker);
+    let engine = RulesEngine::new(Arc::new(checker));
 
     let wallet = create_test_wallet(vec![
         SweepRule::NativeBalance {
-            threshold: "0.1".to_string(),
+            threshold: "0.1".parse().unwrap(),
             asset: "ETH".to_string(),
+            decimals: 18,
+            cooldown_seconds: None,
+            destination: None,
+            destinations: vec![],
         },
         SweepRule::TokenBalance {
-            threshold: "50".to_string(),
+            threshold: "50".parse().unwrap(),
             token_address: "0xUSDC".to_string(),
             asset: "USDC".to_string(),
+            decimals: 0,
+            cooldown_seconds: None,
+            destination: None,
+            destinations: vec![],
         },
         SweepRule::TokenBalance {
-            threshold: "75".to_string(),
+            threshold: "75".parse().unwrap(),
             token_address: "0xDAI".to_string(),
             asset: "DAI".to_string(),
+            decimals: 0,
+            cooldown_seconds: None,
+            destination: None,
+            destinations: vec![],
         },
     ]);
 
@@ -94,17 +125,25 @@ async fn test_multiple_rules_all_trigger() {
 #[tokio::test]
 async fn test_multiple_rules_partial_trigger() {
     let checker = DummyBalanceChecker::new(0.01, 0.05);
-    let engine = RulesEngine::new(checker);
+    let engine = RulesEngine::new(Arc::new(checker));
 
     let wallet = create_test_wallet(vec![
         SweepRule::NativeBalance {
-            threshold: "0.1".to_string(),
+            threshold: "0.1".parse().unwrap(),
             asset: "ETH".to_string(),
+            decimals: 18,
+            cooldown_seconds: None,
+            destination: None,
+            destinations: vec![],
         },
         SweepRule::TokenBalance {
-            threshold: "50".to_string(),
+            threshold: "50".parse().unwrap(),
             token_address: "0xUSDC".to_string(),
             asset: "USDC".to_string(),
+            decimals: 0,
+            cooldown_seconds: None,
+            destination: None,
+            destinations: vec![],
         },
     ]);
 
@@ -112,3 +151,589 @@ async fn test_multiple_rules_partial_trigger() {
     assert_eq!(decisions.len(), 1);
     assert_eq!(decisions[0].rule_type, "token_balance");
 }
+
+struct FailingBalanceSource;
+
+#[async_trait::async_trait]
+impl BalanceSource for FailingBalanceSource {
+    fn name(&self) -> &str {
+        "failing"
+    }
+
+    async fn check_native_balance(&self, _address: &String) -> anyhow::Result<U256> {
+        anyhow::bail!("node unreachable")
+    }
+
+    async fn check_token_balance(&self, _address: &String, _token: &String) -> anyhow::Result<U256> {
+        Ok(U256::from(500))
+    }
+}
+
+#[tokio::test]
+async fn test_custom_balance_source_errors_are_reported() {
+    let engine = RulesEngine::new(Arc::new(FailingBalanceSource));
+
+    let wallet = create_test_wallet(vec![SweepRule::NativeBalance {
+        threshold: "0.1".parse().unwrap(),
+        asset: "ETH".to_string(),
+        decimals: 18,
+        cooldown_seconds: None,
+        destination: None,
+        destinations: vec![],
+    }]);
+
+    let err = engine.evaluate(&wallet).await.unwrap_err();
+    assert!(format!("{:#}", err).contains("failing balance source"));
+    assert!(format!("{:#}", err).contains("node unreachable"));
+}
+
+#[tokio::test]
+async fn test_balance_source_from_config() {
+    let source = from_config(&BalanceSourceConfig::Dummy { min: 0.5, max: 1.0 }).unwrap();
+    assert_eq!(source.name(), "dummy");
+
+    let engine = RulesEngine::new(source);
+    let wallet = create_test_wallet(vec![SweepRule::NativeBalance {
+        threshold: "0.1".parse().unwrap(),
+        asset: "ETH".to_string(),
+        decimals: 18,
+        cooldown_seconds: None,
+        destination: None,
+        destinations: vec![],
+    }]);
+    assert_eq!(engine.evaluate(&wallet).await.unwrap().len(), 1);
+
+    assert!(from_config(&BalanceSourceConfig::Dummy { min: 1.0, max: 1.0 }).is_err());
+}
+
+/// Balance source returning the same balances for every wallet
+struct FixedBalanceSource {
+    native: U256,
+    token: U256,
+}
+
+#[async_trait::async_trait]
+impl BalanceSource for FixedBalanceSource {
+    fn name(&self) -> &str {
+        "fixed"
+    }
+
+    async fn check_native_balance(&self, _address: &String) -> anyhow::Result<U256> {
+        Ok(self.native)
+    }
+
+    async fn check_token_balance(
+        &self,
+        _address: &String,
+        _token: &String,
+    ) -> anyhow::Result<U256> {
+        Ok(self.token)
+    }
+}
+
+#[tokio::test]
+async fn test_excess_rules_retain_a_float() {
+    let engine = RulesEngine::new(Arc::new(FixedBalanceSource {
+        native: U256::from(2_500_000_000_000_000_000u128),
+        token: U256::from(1_500_000u64),
+    }));
+
+    let wallet = create_test_wallet(vec![
+        SweepRule::NativeExcess {
+            trigger_threshold: "1".parse().unwrap(),
+            retain_amount: "0.5".parse().unwrap(),
+            asset: "ETH".to_string(),
+            decimals: 18,
+            cooldown_seconds: None,
+            destination: None,
+            destinations: vec![],
+        },
+        SweepRule::TokenExcess {
+            trigger_threshold: "1".parse().unwrap(),
+            retain_amount: "0.25".parse().unwrap(),
+            token_address: "0xUSDC".to_string(),
+            asset: "USDC".to_string(),
+            decimals: 6,
+            cooldown_seconds: None,
+            destination: None,
+            destinations: vec![],
+        },
+        // Not triggered: the balance is below the trigger
+        SweepRule::TokenExcess {
+            trigger_threshold: "2".parse().unwrap(),
+            retain_amount: "1".parse().unwrap(),
+            token_address: "0xUSDT".to_string(),
+            asset: "USDT".to_string(),
+            decimals: 6,
+            cooldown_seconds: None,
+            destination: None,
+            destinations: vec![],
+        },
+    ]);
+
+    let decisions = engine.evaluate(&wallet).await.unwrap();
+    assert_eq!(decisions.len(), 2);
+    assert_eq!(decisions[0].rule_type, "native_excess");
+    assert_eq!(decisions[0].amount, Amount::parse("2", 18).unwrap());
+    assert!(decisions[0].token_address.is_none());
+    assert_eq!(decisions[1].rule_type, "token_excess");
+    assert_eq!(decisions[1].amount, Amount::parse("1.25", 6).unwrap());
+    assert_eq!(decisions[1].amount.units(), U256::from(1_250_000u64));
+    assert_eq!(decisions[1].token_address, Some("0xUSDC".to_string()));
+}
+
+#[test]
+fn test_retain_above_trigger_is_rejected() {
+    let config = |retain: &str| {
+        format!(
+            r#"{{
+                "treasury_address": "0xTREASURY",
+                "sweep_interval_seconds": 60,
+                "hot_wallets": [{{
+                    "address": "0x1234",
+                    "label": "Float Wallet",
+                    "rules": [{{"native_excess": {{
+                        "trigger_threshold": "1",
+                        "retain_amount": "{}",
+                        "asset": "ETH"
+                    }}}}]
+                }}]
+            }}"#,
+            retain
+        )
+    };
+
+    assert!(Config::from_json(&config("1")).is_ok());
+    let err = Config::from_json(&config("1.5")).unwrap_err();
+    assert!(format!("{:#}", err).contains("retain_amount"));
+    assert!(format!("{:#}", err).contains("Float Wallet"));
+}
+
+#[tokio::test]
+async fn test_native_sweeps_pay_their_own_fee() {
+    let fee = GasConfig::default().native_sweep_fee().unwrap();
+    assert_eq!(fee, U256::from(630_000_000_000_000u64));
+
+    let rules = vec![
+        SweepRule::NativeBalance {
+            threshold: "0".parse().unwrap(),
+            asset: "ETH".to_string(),
+            decimals: 18,
+            cooldown_seconds: None,
+            destination: None,
+            destinations: vec![],
+        },
+        SweepRule::TokenBalance {
+            threshold: "0".parse().unwrap(),
+            token_address: "0xUSDC".to_string(),
+            asset: "USDC".to_string(),
+            decimals: 6,
+            cooldown_seconds: None,
+            destination: None,
+            destinations: vec![],
+        },
+    ];
+    let engine = |native: u128| {
+        RulesEngine::new(Arc::new(FixedBalanceSource {
+            native: U256::from(native),
+            token: U256::from(1_000_000u64),
+        }))
+        .with_native_fee(fee, 10)
+    };
+
+    let evaluation = engine(1_000_000_000_000_000_000)
+        .evaluate_detailed(&create_test_wallet(rules.clone()))
+        .await
+        .unwrap();
+    assert!(evaluation.skipped.is_empty());
+    assert_eq!(
+        evaluation.decisions[0].amount,
+        Amount::parse("0.99937", 18).unwrap()
+    );
+    // Token sweeps are paid for from the native balance, not the token
+    assert_eq!(evaluation.decisions[1].amount, Amount::parse("1", 6).unwrap());
+
+    // 0.001 ETH: the fee would take 63% of it
+    let evaluation = engine(1_000_000_000_000_000)
+        .evaluate_detailed(&create_test_wallet(rules))
+        .await
+        .unwrap();
+    assert_eq!(evaluation.decisions.len(), 1);
+    assert_eq!(evaluation.decisions[0].asset, "USDC");
+    assert_eq!(evaluation.skipped.len(), 1);
+    assert_eq!(evaluation.skipped[0].asset, "ETH");
+    assert!(evaluation.skipped[0].reason.contains("more than 10%"));
+}
+
+#[tokio::test]
+async fn test_native_fee_is_in_wei_whatever_the_rule_decimals() {
+    let fee = GasConfig::default().native_sweep_fee().unwrap();
+    let engine = RulesEngine::new(Arc::new(FixedBalanceSource {
+        native: U256::from(1_000_000_000u64),
+        token: U256::ZERO,
+    }))
+    .with_native_fee(fee, 10);
+
+    // 1.0 at 9 decimals still pays 0.00063
+    let decisions = engine
+        .evaluate(&create_test_wallet(vec![SweepRule::NativeBalance {
+            threshold: "0".parse().unwrap(),
+            asset: "ETH".to_string(),
+            decimals: 9,
+            cooldown_seconds: None,
+            destination: None,
+            destinations: vec![],
+        }]))
+        .await
+        .unwrap();
+    assert_eq!(decisions[0].amount, Amount::parse("0.99937", 9).unwrap());
+}
+
+#[test]
+fn test_evm_emitter_rejects_native_rules_below_wei() {
+    let config = |decimals: u8| {
+        format!(
+            r#"{{
+                "treasury_address": "0xTREASURY",
+                "sweep_interval_seconds": 60,
+                "emitter": {{
+                    "type": "evm",
+                    "rpc_url": "http://127.0.0.1:8545",
+                    "chain_id": 1,
+                    "keystore": "keys.json"
+                }},
+                "hot_wallets": [{{
+                    "address": "0x1234",
+                    "label": "Hot Wallet",
+                    "rules": [{{"native_balance": {{
+                        "threshold": "1",
+                        "asset": "ETH",
+                        "decimals": {}
+                    }}}}]
+                }}]
+            }}"#,
+            decimals
+        )
+    };
+
+    assert!(Config::from_json(&config(18)).is_ok());
+    let err = Config::from_json(&config(9)).unwrap_err();
+    assert!(err.to_string().contains("9 decimals"));
+}
+
+/// Counts the balance queries that reach the wrapped source
+struct CountingBalanceSource {
+    inner: FixedBalanceSource,
+    calls: AtomicUsize,
+}
+
+#[async_trait::async_trait]
+impl BalanceSource for CountingBalanceSource {
+    fn name(&self) -> &str {
+        "counting"
+    }
+
+    async fn check_native_balance(&self, address: &String) -> anyhow::Result<U256> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        self.inner.check_native_balance(address).await
+    }
+
+    async fn check_token_balance(&self, address: &String, token: &String) -> anyhow::Result<U256> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        self.inner.check_token_balance(address, token).await
+    }
+}
+
+#[tokio::test]
+async fn test_composite_rule_from_config() {
+    // Sweep USDC if USDC > 10k OR (USDC > 1k AND last sweep older than 24h),
+    // alongside a native rule, all from one balance query per asset
+    let rules: Vec<SweepRule> = serde_json::from_str(
+        r#"[
+            {"composite": {
+                "asset": "USDC",
+                "token_address": "0xUSDC",
+                "decimals": 6,
+                "condition": {"any": [
+                    {"balance": {"token_address": "0xUSDC", "decimals": 6, "above": "10000"}},
+                    {"all": [
+                        {"balance": {"token_address": "0xUSDC", "decimals": 6, "above": "1000"}},
+                        {"last_sweep_older_than": {"seconds": 86400}}
+                    ]}
+                ]}
+            }},
+            {"native_balance": {"threshold": "1", "asset": "ETH"}},
+            {"composite": {
+                "asset": "ETH",
+                "decimals": 18,
+                "retain_amount": "0.5",
+                "condition": {"not": {"balance": {"token_address": "0xUSDC", "decimals": 6, "below": "1"}}}
+            }}
+        ]"#,
+    )
+    .unwrap();
+    let wallet = create_test_wallet(rules);
+    for rule in &wallet.rules {
+        rule.validate().unwrap();
+    }
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let state_manager = Arc::new(
+        StateManager::load(temp_dir.path().join("state.json"))
+            .await
+            .unwrap(),
+    );
+    let source = Arc::new(CountingBalanceSource {
+        inner: FixedBalanceSource {
+            native: U256::from(2_000_000_000_000_000_000u128),
+            token: U256::from(5_000_000_000u64),
+        },
+        calls: AtomicUsize::new(0),
+    });
+    let engine = RulesEngine::new(source.clone()).with_state_manager(state_manager.clone());
+
+    // USDC never swept, so 5k is enough; a recent ETH sweep does not count
+    state_manager
+        .reserve_nonce(&"0xTestWallet".to_string())
+        .await
+        .unwrap()
+        .commit_sweep("ETH", Amount::parse("1", 18).unwrap())
+        .await
+        .unwrap();
+    let decisions = engine.evaluate(&wallet).await.unwrap();
+    assert_eq!(decisions.len(), 3);
+    assert_eq!(decisions[0].rule_type, "composite");
+    assert_eq!(decisions[0].amount, Amount::parse("5000", 6).unwrap());
+    assert_eq!(decisions[0].token_address, Some("0xUSDC".to_string()));
+    assert_eq!(decisions[2].asset, "ETH");
+    assert_eq!(decisions[2].amount, Amount::parse("1.5", 18).unwrap());
+    assert!(decisions[2].token_address.is_none());
+    assert_eq!(source.calls.load(Ordering::SeqCst), 2);
+
+    // Swept just now: 5k USDC is not enough on its own
+    let reservation = state_manager
+        .reserve_nonce(&"0xTestWallet".to_string())
+        .await
+        .unwrap();
+    reservation
+        .commit_sweep("USDC", Amount::parse("1", 6).unwrap())
+        .await
+        .unwrap();
+    let decisions = engine.evaluate(&wallet).await.unwrap();
+    assert_eq!(decisions.len(), 2);
+    assert!(decisions.iter().all(|decision| decision.asset == "ETH"));
+}
+
+#[tokio::test]
+async fn test_composite_rule_validation() {
+    let parse = |condition: &str| -> SweepRule {
+        serde_json::from_str(&format!(
+            r#"{{"composite": {{"asset": "ETH", "decimals": 18, "condition": {}}}}}"#,
+            condition
+        ))
+        .unwrap()
+    };
+
+    // Without a retain_amount nothing else would rescale to the rule's decimals
+    let too_precise: SweepRule = serde_json::from_str(
+        r#"{"composite": {"asset": "ETH", "decimals": 80, "condition": {"balance": {"decimals": 18, "above": "1"}}}}"#,
+    )
+    .unwrap();
+    let err = too_precise.validate().unwrap_err();
+    assert!(err.to_string().contains("80 decimals"));
+
+    assert!(parse(r#"{"any": []}"#).validate().is_err());
+    assert!(parse(r#"{"balance": {"decimals": 18}}"#).validate().is_err());
+    assert!(
+        parse(r#"{"balance": {"decimals": 0, "above": "0.5"}}"#)
+            .validate()
+            .is_err()
+    );
+
+    // Last-sweep conditions need the sweep history
+    let engine = RulesEngine::new(Arc::new(DummyBalanceChecker::new(0.5, 1.0)));
+    let wallet = create_test_wallet(vec![parse(r#"{"last_sweep_older_than": {"seconds": 60}}"#)]);
+    let err = engine.evaluate(&wallet).await.unwrap_err();
+    assert!(err.to_string().contains("sweep history"));
+}
+
+/// Oracle quoting every asset at the same price
+struct FixedPriceOracle {
+    price: Price,
+}
+
+#[async_trait::async_trait]
+impl PriceOracle for FixedPriceOracle {
+    fn name(&self) -> &str {
+        "fixed"
+    }
+
+    async fn price(&self, _asset: &str) -> anyhow::Result<Price> {
+        Ok(self.price)
+    }
+}
+
+fn usd_rules() -> Vec<SweepRule> {
+    serde_json::from_str(
+        r#"[
+            {"native_balance": {"threshold": {"usd": "5000"}, "asset": "ETH"}},
+            {"native_excess": {"trigger_threshold": {"usd": "7000"}, "retain_amount": "0.1", "asset": "ETH"}}
+        ]"#,
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_usd_thresholds_use_oracle_price() {
+    // 2 ETH at 3000 USD is worth 6000 USD
+    let oracle = FixedPriceOracle {
+        price: Price {
+            usd: "3000".parse().unwrap(),
+            updated_at: Utc::now(),
+        },
+    };
+    let engine = RulesEngine::new(Arc::new(FixedBalanceSource {
+        native: U256::from(2_000_000_000_000_000_000u128),
+        token: U256::ZERO,
+    }))
+    .with_price_oracle(Arc::new(oracle), 300);
+
+    let evaluation = engine
+        .evaluate_detailed(&create_test_wallet(usd_rules()))
+        .await
+        .unwrap();
+    assert_eq!(evaluation.decisions.len(), 1);
+    assert_eq!(evaluation.decisions[0].rule_type, "native_balance");
+    assert_eq!(
+        evaluation.decisions[0].amount,
+        Amount::parse("2", 18).unwrap()
+    );
+    assert!(evaluation.skipped.is_empty());
+}
+
+#[tokio::test]
+async fn test_stale_price_refuses_to_fire() {
+    let oracle = FixedPriceOracle {
+        price: Price {
+            usd: "3000".parse().unwrap(),
+            updated_at: Utc::now() - chrono::Duration::hours(1),
+        },
+    };
+    let engine = RulesEngine::new(Arc::new(FixedBalanceSource {
+        native: U256::from(2_000_000_000_000_000_000u128),
+        token: U256::ZERO,
+    }))
+    .with_price_oracle(Arc::new(oracle), 300);
+
+    let evaluation = engine
+        .evaluate_detailed(&create_test_wallet(usd_rules()))
+        .await
+        .unwrap();
+    assert!(evaluation.decisions.is_empty());
+    assert_eq!(evaluation.skipped.len(), 2);
+    assert!(evaluation.skipped[0].reason.contains("stale"));
+
+    // Without an oracle, USD thresholds cannot be evaluated at all
+    let engine = RulesEngine::new(Arc::new(DummyBalanceChecker::new(0.5, 1.0)));
+    let err = engine
+        .evaluate(&create_test_wallet(usd_rules()))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("price oracle"));
+}
+
+#[test]
+fn test_usd_threshold_requires_price_oracle_config() {
+    let config = |oracle: &str| {
+        format!(
+            r#"{{
+                "treasury_address": "0xTREASURY",
+                "hot_wallets": [{{"address": "0xW", "label": "W", "rules": [
+                    {{"native_balance": {{"threshold": {{"usd": "5000"}}, "asset": "ETH"}}}}
+                ]}}],
+                "sweep_interval_seconds": 60{}
+            }}"#,
+            oracle
+        )
+    };
+
+    let err = Config::from_json(&config("")).unwrap_err();
+    assert!(err.to_string().contains("no price_oracle"));
+    let config = Config::from_json(&config(
+        r#", "price_oracle": {"type": "static", "path": "prices.json"}"#,
+    ))
+    .unwrap();
+    assert!(config.hot_wallets[0].rules[0].threshold_units().unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_cooldown_skips_recently_swept_asset() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let state_manager = Arc::new(
+        StateManager::load(temp_dir.path().join("state.json"))
+            .await
+            .unwrap(),
+    );
+    let engine = RulesEngine::new(Arc::new(FixedBalanceSource {
+        native: U256::from(2_000_000_000_000_000_000u128),
+        token: U256::from(500u64),
+    }))
+    .with_state_manager(state_manager.clone());
+
+    // The wallet's cooldown applies to ETH; the USDC rule opts out
+    let mut wallet = create_test_wallet(vec![
+        SweepRule::NativeBalance {
+            threshold: "1".parse().unwrap(),
+            asset: "ETH".to_string(),
+            decimals: 18,
+            cooldown_seconds: None,
+            destination: None,
+            destinations: vec![],
+        },
+        SweepRule::TokenBalance {
+            threshold: "100".parse().unwrap(),
+            token_address: "0xUSDC".to_string(),
+            asset: "USDC".to_string(),
+            decimals: 0,
+            cooldown_seconds: Some(0),
+            destination: None,
+            destinations: vec![],
+        },
+    ]);
+    wallet.cooldown_seconds = Some(3600);
+    assert_eq!(engine.evaluate(&wallet).await.unwrap().len(), 2);
+
+    for asset in ["ETH", "USDC"] {
+        let reservation = state_manager.reserve_nonce(&wallet.address).await.unwrap();
+        reservation
+            .commit_sweep(asset, Amount::parse("1", 6).unwrap())
+            .await
+            .unwrap();
+    }
+    assert!(state_manager.last_asset_sweep(&wallet.address, "ETH").await.is_some());
+
+    let evaluation = engine.evaluate_detailed(&wallet).await.unwrap();
+    assert_eq!(evaluation.decisions.len(), 1);
+    assert_eq!(evaluation.decisions[0].asset, "USDC");
+    assert_eq!(evaluation.skipped.len(), 1);
+    assert_eq!(evaluation.skipped[0].asset, "ETH");
+    assert!(evaluation.skipped[0].reason.contains("cooldown_seconds 3600"));
+
+    // A sweep of another asset does not start the ETH cooldown
+    let other = HotWalletConfig {
+        address: "0xOther".to_string(),
+        ..wallet
+    };
+    let reservation = state_manager.reserve_nonce(&other.address).await.unwrap();
+    reservation
+        .commit_sweep("USDC", Amount::parse("1", 6).unwrap())
+        .await
+        .unwrap();
+    let evaluation = engine.evaluate_detailed(&other).await.unwrap();
+    assert_eq!(evaluation.decisions.len(), 2);
+
+    // Cooldowns need the sweep history
+    let engine = RulesEngine::new(Arc::new(DummyBalanceChecker::new(1.5, 2.0)));
+    let err = engine.evaluate(&other).await.unwrap_err();
+    assert!(err.to_string().contains("sweep history"));
+}
@@ -2,6 +2,12 @@ use std::sync::Arc;
 use tempfile::TempDir;
 use treasury_sweeper::state_manager::StateManager;
 
+/// Reserve and immediately commit the next nonce for `address`
+async fn commit_next(state_manager: &StateManager, address: &str) -> u64 {
+    let reservation = state_manager.reserve_nonce(&address.to_string()).await.unwrap();
+    reservation.commit().await.unwrap()
+}
+
 #[tokio::test]
 async fn test_state_persistence() {
     let temp_dir = TempDir::new().unwrap();
@@ -12,13 +18,13 @@ async fn test_state_persistence() {
         let state_manager = StateManager::load(state_path.clone()).await.unwrap();
 
         // Reserve some nonces
-        let nonce1 = state_manager.reserve_nonce(&"0xWallet1".to_string()).await.unwrap();
+        let nonce1 = commit_next(&state_manager, "0xWallet1").await;
         assert_eq!(nonce1, 0);
 
-        let nonce2 = state_manager.reserve_nonce(&"0xWallet1".to_string()).await.unwrap();
+        let nonce2 = commit_next(&state_manager, "0xWallet1").await;
         assert_eq!(nonce2, 1);
 
-        let nonce3 = state_manager.reserve_nonce(&"0xWallet2".to_string()).await.unwrap();
+        let nonce3 = commit_next(&state_manager, "0xWallet2").await;
         assert_eq!(nonce3, 0);
     }
 
@@ -26,10 +32,10 @@ async fn test_state_persistence() {
     {
         let state_manager = StateManager::load(state_path.clone()).await.unwrap();
 
-        let nonce4 = state_manager.reserve_nonce(&"0xWallet1".to_string()).await.unwrap();
+        let nonce4 = commit_next(&state_manager, "0xWallet1").await;
         assert_eq!(nonce4, 2);
 
-        let nonce5 = state_manager.reserve_nonce(&"0xWallet2".to_string()).await.unwrap();
+        let nonce5 = commit_next(&state_manager, "0xWallet2").await;
         assert_eq!(nonce5, 1);
     }
 }
@@ -46,7 +52,10 @@ async fn test_concurrent_nonce_reservation() {
     for _ in 0..10 {
         let sm = Arc::clone(&state_manager);
         let addr = wallet_address.clone();
-        let handle = tokio::spawn(async move { sm.reserve_nonce(&addr).await.unwrap() });
+        let handle = tokio::spawn(async move {
+            let reservation = sm.reserve_nonce(&addr).await.unwrap();
+            reservation.commit().await.unwrap()
+        });
         handles.push(handle);
     }
 
@@ -65,9 +74,9 @@ async fn test_state_snapshot() {
     let state_manager = StateManager::load(state_path).await.unwrap();
 
     // Reserve some nonces
-    state_manager.reserve_nonce(&"0xWallet1".to_string()).await.unwrap();
-    state_manager.reserve_nonce(&"0xWallet1".to_string()).await.unwrap();
-    state_manager.reserve_nonce(&"0xWallet2".to_string()).await.unwrap();
+    commit_next(&state_manager, "0xWallet1").await;
+    commit_next(&state_manager, "0xWallet1").await;
+    commit_next(&state_manager, "0xWallet2").await;
 
     // Get snapshot
     let snapshot = state_manager.fetch_snapshot().await;
@@ -79,3 +88,58 @@ async fn test_state_snapshot() {
     assert_eq!(snapshot.wallets.get("0xWallet2").unwrap().next_nonce, 1);
     assert_eq!(snapshot.wallets.get("0xWallet2").unwrap().total_sweeps, 1);
 }
+
+#[tokio::test]
+async fn test_dropped_reservation_returns_nonce() {
+    let temp_dir = TempDir::new().unwrap();
+    let state_path = temp_dir.path().join("state.json");
+    let state_manager = StateManager::load(state_path.clone()).await.unwrap();
+    let wallet = "0xWallet1".to_string();
+
+    {
+        let reservation = state_manager.reserve_nonce(&wallet).await.unwrap();
+        assert_eq!(reservation.nonce(), 0);
+        // Emitter fails before broadcasting: reservation goes out of scope
+    }
+    let reservation = state_manager.reserve_nonce(&wallet).await.unwrap();
+    assert_eq!(reservation.nonce(), 0);
+    reservation.release();
+
+    // Nothing was used, so nothing was counted or written
+    assert!(state_manager.fetch_snapshot().await.wallets.is_empty());
+    assert!(!state_path.exists());
+
+    let reservation = state_manager.reserve_nonce(&wallet).await.unwrap();
+    assert_eq!(reservation.commit().await.unwrap(), 0);
+    let reservation = state_manager.reserve_nonce(&wallet).await.unwrap();
+    assert_eq!(reservation.nonce(), 1);
+    drop(reservation);
+
+    let snapshot = state_manager.fetch_snapshot().await;
+    assert_eq!(snapshot.wallets["0xWallet1"].next_nonce, 1);
+    assert_eq!(snapshot.wallets["0xWallet1"].total_sweeps, 1);
+}
+
+#[tokio::test]
+async fn test_reservation_blocks_same_wallet_until_released() {
+    let temp_dir = TempDir::new().unwrap();
+    let state_path = temp_dir.path().join("state.json");
+    let state_manager = Arc::new(StateManager::load(state_path).await.unwrap());
+
+    let reservation = state_manager.reserve_nonce(&"0xWallet1".to_string()).await.unwrap();
+
+    // Another wallet is not blocked
+    let other = state_manager.reserve_nonce(&"0xWallet2".to_string()).await.unwrap();
+    assert_eq!(other.nonce(), 0);
+
+    let sm = Arc::clone(&state_manager);
+    let waiter = tokio::spawn(async move {
+        let reservation = sm.reserve_nonce(&"0xWallet1".to_string()).await.unwrap();
+        reservation.nonce()
+    });
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    assert!(!waiter.is_finished());
+
+    reservation.release();
+    assert_eq!(waiter.await.unwrap(), 0);
+}
@@ -0,0 +1,239 @@
+use std::sync::Arc;
+use tempfile::TempDir;
+use treasury_sweeper::amount::Amount;
+use treasury_sweeper::state_manager::StateManager;
+use treasury_sweeper::sweep_caps::{CapOutcome, SweepCaps};
+use treasury_sweeper::types::{CapAction, Config, SweepCap, SweepDecision, SweepSplit};
+
+const WALLET: &str = "0xWallet";
+
+async fn state_manager() -> (Arc<StateManager>, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let state_manager = Arc::new(
+        StateManager::load(temp_dir.path().join("state.json"))
+            .await
+            .unwrap(),
+    );
+    (state_manager, temp_dir)
+}
+
+async fn record_sweep(state_manager: &StateManager, wallet: &str, amount: &str) {
+    let reservation = state_manager
+        .reserve_nonce(&wallet.to_string())
+        .await
+        .unwrap();
+    reservation
+        .commit_sweep("ETH", Amount::parse(amount, 18).unwrap())
+        .await
+        .unwrap();
+}
+
+fn eth_sweep(amount: &str) -> SweepDecision {
+    SweepDecision {
+        amount: Amount::parse(amount, 18).unwrap(),
+        asset: "ETH".to_string(),
+        rule_type: "native_balance".to_string(),
+        token_address: None,
+        destination: None,
+        split: None,
+    }
+}
+
+fn eth_cap(on_exceed: CapAction) -> SweepCap {
+    SweepCap {
+        wallet: None,
+        asset: Some("ETH".to_string()),
+        max_per_sweep: Some("5".parse().unwrap()),
+        max_per_24h: Some("8".parse().unwrap()),
+        max_sweeps_per_day: None,
+        on_exceed,
+    }
+}
+
+#[tokio::test]
+async fn test_clip_to_per_sweep_and_rolling_total() {
+    let (state_manager, _temp_dir) = state_manager().await;
+    let caps = SweepCaps::new(vec![eth_cap(CapAction::Clip)], state_manager.clone());
+
+    // Under every cap: unchanged
+    let CapOutcome::Allow(legs) = caps
+        .check(&WALLET.to_string(), vec![eth_sweep("2")])
+        .await
+        .unwrap()
+    else {
+        panic!("expected the sweep to be allowed");
+    };
+    assert_eq!(legs[0].amount, Amount::parse("2", 18).unwrap());
+
+    // Clipped to max_per_sweep
+    let CapOutcome::Allow(legs) = caps
+        .check(&WALLET.to_string(), vec![eth_sweep("7")])
+        .await
+        .unwrap()
+    else {
+        panic!("expected the sweep to be clipped");
+    };
+    assert_eq!(legs[0].amount, Amount::parse("5", 18).unwrap());
+
+    // 6.5 ETH swept across wallets leaves 1.5 of the 24h total
+    record_sweep(&state_manager, WALLET, "4").await;
+    record_sweep(&state_manager, "0xOther", "2.5").await;
+    let CapOutcome::Allow(legs) = caps
+        .check(&WALLET.to_string(), vec![eth_sweep("3")])
+        .await
+        .unwrap()
+    else {
+        panic!("expected the sweep to be clipped");
+    };
+    assert_eq!(legs[0].amount, Amount::parse("1.5", 18).unwrap());
+
+    // Nothing left to sweep
+    record_sweep(&state_manager, WALLET, "1.5").await;
+    let outcome = caps
+        .check(&WALLET.to_string(), vec![eth_sweep("1")])
+        .await
+        .unwrap();
+    assert!(matches!(outcome, CapOutcome::Skip(reason) if reason.contains("max_per_24h 8")));
+
+    // Other assets are not covered
+    let usdc = SweepDecision {
+        asset: "USDC".to_string(),
+        ..eth_sweep("100")
+    };
+    assert!(matches!(
+        caps.check(&WALLET.to_string(), vec![usdc]).await.unwrap(),
+        CapOutcome::Allow(_)
+    ));
+}
+
+#[tokio::test]
+async fn test_hold_and_per_wallet_sweep_count() {
+    let (state_manager, _temp_dir) = state_manager().await;
+    let count_cap = SweepCap {
+        wallet: Some(WALLET.to_string()),
+        asset: None,
+        max_per_sweep: None,
+        max_per_24h: None,
+        max_sweeps_per_day: Some(2),
+        on_exceed: CapAction::Clip,
+    };
+    let caps = SweepCaps::new(
+        vec![eth_cap(CapAction::Hold), count_cap],
+        state_manager.clone(),
+    );
+
+    let outcome = caps
+        .check(&WALLET.to_string(), vec![eth_sweep("6")])
+        .await
+        .unwrap();
+    assert!(matches!(outcome, CapOutcome::Hold(reason) if reason.contains("max_per_sweep 5")));
+
+    record_sweep(&state_manager, WALLET, "1").await;
+    record_sweep(&state_manager, WALLET, "1").await;
+    let outcome = caps
+        .check(&WALLET.to_string(), vec![eth_sweep("1")])
+        .await
+        .unwrap();
+    assert!(matches!(outcome, CapOutcome::Skip(reason) if reason.contains("max_sweeps_per_day 2")));
+
+    // The count cap is per wallet
+    assert!(matches!(
+        caps.check(&"0xOther".to_string(), vec![eth_sweep("1")])
+            .await
+            .unwrap(),
+        CapOutcome::Allow(_)
+    ));
+}
+
+fn split_leg(amount: &str, total: &str, first: bool) -> SweepDecision {
+    SweepDecision {
+        split: Some(SweepSplit {
+            total: Amount::parse(total, 18).unwrap(),
+            bps: 5_000,
+            first,
+        }),
+        ..eth_sweep(amount)
+    }
+}
+
+#[tokio::test]
+async fn test_split_is_checked_as_one_sweep() {
+    let (state_manager, _temp_dir) = state_manager().await;
+    let cap = |on_exceed| SweepCap {
+        wallet: None,
+        asset: Some("ETH".to_string()),
+        max_per_sweep: Some("100".parse().unwrap()),
+        max_per_24h: None,
+        max_sweeps_per_day: Some(2),
+        on_exceed,
+    };
+    let legs = || vec![split_leg("75", "150", true), split_leg("75", "150", false)];
+
+    // Two 75 ETH legs are one 150 ETH sweep
+    let caps = SweepCaps::new(vec![cap(CapAction::Hold)], state_manager.clone());
+    let outcome = caps.check(&WALLET.to_string(), legs()).await.unwrap();
+    assert!(matches!(outcome, CapOutcome::Hold(reason) if reason.contains("max_per_sweep 100")));
+
+    // Clipping scales every leg alike
+    let caps = SweepCaps::new(vec![cap(CapAction::Clip)], state_manager.clone());
+    let CapOutcome::Allow(clipped) = caps.check(&WALLET.to_string(), legs()).await.unwrap() else {
+        panic!("expected the split to be clipped");
+    };
+    assert_eq!(clipped.len(), 2);
+    for (leg, first) in clipped.iter().zip([true, false]) {
+        assert_eq!(leg.amount, Amount::parse("50", 18).unwrap());
+        let split = leg.split.unwrap();
+        assert_eq!(split.total, Amount::parse("100", 18).unwrap());
+        assert_eq!(split.first, first);
+    }
+
+    // A recorded split counts once toward max_sweeps_per_day
+    for leg in clipped {
+        state_manager
+            .reserve_nonce(&WALLET.to_string())
+            .await
+            .unwrap()
+            .commit_sweep_to(&leg, &"0xTreasury".to_string())
+            .await
+            .unwrap();
+    }
+    assert!(matches!(
+        caps.check(&WALLET.to_string(), vec![eth_sweep("1")])
+            .await
+            .unwrap(),
+        CapOutcome::Allow(_)
+    ));
+    record_sweep(&state_manager, WALLET, "1").await;
+    let outcome = caps
+        .check(&WALLET.to_string(), vec![eth_sweep("1")])
+        .await
+        .unwrap();
+    assert!(matches!(outcome, CapOutcome::Skip(reason) if reason.contains("2 sweeps")));
+}
+
+#[test]
+fn test_cap_config_validation() {
+    let config = |caps: &str| {
+        Config::from_json(&format!(
+            r#"{{
+                "treasury_address": "0xTREASURY",
+                "hot_wallets": [{{"address": "0xW", "label": "W", "rules": [
+                    {{"token_balance": {{"threshold": "100", "token_address": "0xUSDC", "asset": "USDC", "decimals": 6}}}}
+                ]}}],
+                "sweep_interval_seconds": 60,
+                "caps": {}
+            }}"#,
+            caps
+        ))
+    };
+
+    let config_ok =
+        config(r#"[{"asset": "USDC", "max_per_sweep": "1000.5", "on_exceed": "clip"}]"#).unwrap();
+    assert_eq!(config_ok.caps[0].on_exceed, CapAction::Clip);
+    assert_eq!(
+        config(r#"[{"max_sweeps_per_day": 3}]"#).unwrap().caps[0].on_exceed,
+        CapAction::Hold
+    );
+    assert!(config(r#"[{"asset": "USDC", "max_per_24h": "0.0000001"}]"#).is_err());
+    assert!(config(r#"[{"max_per_sweep": "5"}]"#).is_err());
+}
@@ -1,8 +1,23 @@
-use std::sync::Arc;
+mod common;
+
+use common::{MockRpcServer, rpc_error};
+use serde_json::{Value, json};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tempfile::TempDir;
+use treasury_sweeper::amount::{Amount, U256};
+use treasury_sweeper::destinations::{Destinations, split_amount};
+use treasury_sweeper::evm_tx::{EvmTxBuilder, address_of, format_address, keccak256};
+use treasury_sweeper::keystore::Keystore;
+use treasury_sweeper::rpc::JsonRpcClient;
 use treasury_sweeper::state_manager::StateManager;
-use treasury_sweeper::tx_emitter::MockTxEmitter;
-use treasury_sweeper::types::SweepDecision;
+use treasury_sweeper::tx_emitter::{
+    DryRunTxEmitter, EvmTxEmitter, MockTxEmitter, ReceiptPolling, TxEmitter,
+};
+use treasury_sweeper::types::{
+    Config, GasConfig, ReplacementConfig, SplitDestination, SweepDecision, TxStatus,
+};
 
 async fn create_test_emitter() -> (MockTxEmitter, TempDir) {
     let temp_dir = TempDir::new().unwrap();
@@ -20,20 +35,22 @@ async fn test_nonce_increments_correctly() {
     let from_addr = "0x1234".to_string();
 
     let decision = SweepDecision {
-        amount: "0.5".to_string(),
+        amount: Amount::parse("0.5", 18).unwrap(),
         asset: "ETH".to_string(),
         rule_type: "native_balance".to_string(),
         token_address: None,
+        destination: None,
+        split: None,
     };
 
     let tx1 = emitter.emit_sweep(&from_addr, &decision).await.unwrap();
-    assert_eq!(tx1.nonce, 0);
+    assert_eq!(tx1.tx.nonce, 0);
 
     let tx2 = emitter.emit_sweep(&from_addr, &decision).await.unwrap();
-    assert_eq!(tx2.nonce, 1);
+    assert_eq!(tx2.tx.nonce, 1);
 
     let tx3 = emitter.emit_sweep(&from_addr, &decision).await.unwrap();
-    assert_eq!(tx3.nonce, 2);
+    assert_eq!(tx3.tx.nonce, 2);
 }
 
 #[tokio::test]
@@ -41,27 +58,29 @@ async fn test_different_wallets_independent_nonces() {
     let (emitter, _temp_dir) = create_test_emitter().await;
 
     let decision = SweepDecision {
-        amount: "1.0".to_string(),
+        amount: Amount::parse("1.0", 18).unwrap(),
         asset: "ETH".to_string(),
         rule_type: "native_balance".to_string(),
         token_address: None,
+        destination: None,
+        split: None,
     };
 
     // Wallet 1
     let tx1 = emitter.emit_sweep(&"0xWallet1".to_string(), &decision).await.unwrap();
-    assert_eq!(tx1.nonce, 0);
+    assert_eq!(tx1.tx.nonce, 0);
 
     // Wallet 2
     let tx2 = emitter.emit_sweep(&"0xWallet2".to_string(), &decision).await.unwrap();
-    assert_eq!(tx2.nonce, 0);
+    assert_eq!(tx2.tx.nonce, 0);
 
     // Wallet 1 again
     let tx3 = emitter.emit_sweep(&"0xWallet1".to_string(), &decision).await.unwrap();
-    assert_eq!(tx3.nonce, 1);
+    assert_eq!(tx3.tx.nonce, 1);
 
     // Wallet 2 again
     let tx4 = emitter.emit_sweep(&"0xWallet2".to_string(), &decision).await.unwrap();
-    assert_eq!(tx4.nonce, 1);
+    assert_eq!(tx4.tx.nonce, 1);
 }
 
 #[tokio::test]
@@ -70,20 +89,22 @@ async fn test_native_transaction_fields() {
     let from_addr = "0xABCD".to_string();
 
     let decision = SweepDecision {
-        amount: "2.5".to_string(),
+        amount: Amount::parse("2.5", 18).unwrap(),
         asset: "ETH".to_string(),
         rule_type: "native_balance".to_string(),
         token_address: None,
+        destination: None,
+        split: None,
     };
 
     let tx = emitter.emit_sweep(&from_addr, &decision).await.unwrap();
 
-    assert_eq!(tx.from, "0xABCD");
-    assert_eq!(tx.to, "0xTREASURY");
-    assert_eq!(tx.value, "2.5");
-    assert_eq!(tx.asset, "ETH");
-    assert_eq!(tx.nonce, 0);
-    assert!(tx.token_address.is_none());
+    assert_eq!(tx.tx.from, "0xABCD");
+    assert_eq!(tx.tx.to, "0xTREASURY");
+    assert_eq!(tx.tx.value, Amount::parse("2.5", 18).unwrap());
+    assert_eq!(tx.tx.asset, "ETH");
+    assert_eq!(tx.tx.nonce, 0);
+    assert!(tx.tx.token_address.is_none());
 }
 
 #[tokio::test]
@@ -92,18 +113,884 @@ async fn test_token_transaction_fields() {
     let from_addr = "0x5678".to_string();
 
     let decision = SweepDecision {
-        amount: "150".to_string(),
+        amount: Amount::parse("150", 6).unwrap(),
         asset: "USDC".to_string(),
         rule_type: "token_balance".to_string(),
         token_address: Some("0xUSDC_CONTRACT".to_string()),
+        destination: None,
+        split: None,
     };
 
     let tx = emitter.emit_sweep(&from_addr, &decision).await.unwrap();
 
-    assert_eq!(tx.from, "0x5678");
-    assert_eq!(tx.to, "0xTREASURY");
-    assert_eq!(tx.value, "150");
-    assert_eq!(tx.asset, "USDC");
-    assert_eq!(tx.nonce, 0);
-    assert_eq!(tx.token_address, Some("0xUSDC_CONTRACT".to_string()));
+    assert_eq!(tx.tx.from, "0x5678");
+    assert_eq!(tx.tx.to, "0xTREASURY");
+    assert_eq!(tx.tx.value.to_string(), "150");
+    assert_eq!(tx.tx.asset, "USDC");
+    assert_eq!(tx.tx.nonce, 0);
+    assert_eq!(tx.tx.token_address, Some("0xUSDC_CONTRACT".to_string()));
+}
+
+#[tokio::test]
+async fn test_mock_emitter_returns_no_hash() {
+    let (emitter, _temp_dir) = create_test_emitter().await;
+
+    let decision = SweepDecision {
+        amount: Amount::parse("1", 18).unwrap(),
+        asset: "ETH".to_string(),
+        rule_type: "native_balance".to_string(),
+        token_address: None,
+        destination: None,
+        split: None,
+    };
+
+    // The nonce is used, but nothing was submitted anywhere
+    let submitted = emitter.emit_sweep(&"0x1234".to_string(), &decision).await.unwrap();
+    assert_eq!(submitted.tx.nonce, 0);
+    assert!(submitted.hash.is_none());
+}
+
+#[tokio::test]
+async fn test_dry_run_does_not_reserve_nonce() {
+    let temp_dir = TempDir::new().unwrap();
+    let state_path = temp_dir.path().join("state.json");
+    let state_manager = Arc::new(StateManager::load(state_path.clone()).await.unwrap());
+    let emitter = DryRunTxEmitter::new(state_manager.clone(), "0xTREASURY".to_string());
+
+    let decision = SweepDecision {
+        amount: Amount::parse("1", 18).unwrap(),
+        asset: "ETH".to_string(),
+        rule_type: "native_balance".to_string(),
+        token_address: None,
+        destination: None,
+        split: None,
+    };
+
+    let tx1 = emitter.emit_sweep(&"0x1234".to_string(), &decision).await.unwrap();
+    let tx2 = emitter.emit_sweep(&"0x1234".to_string(), &decision).await.unwrap();
+    assert_eq!(tx1.tx.nonce, 0);
+    assert_eq!(tx2.tx.nonce, 0);
+    assert!(tx1.hash.is_none());
+
+    assert!(state_manager.fetch_snapshot().await.wallets.is_empty());
+    assert!(!state_path.exists());
+}
+
+fn routed_config(allowed: &str, rule_destination: &str) -> String {
+    format!(
+        r#"{{
+            "treasury_address": "0xTREASURY",
+            "hot_wallets": [{{"address": "0xW", "label": "W", "rules": [
+                {{"native_balance": {{"threshold": "1", "asset": "ETH"{}}}}}
+            ]}}],
+            "sweep_interval_seconds": 60,
+            "routes": {{"USDC": "0xUSDC_VAULT"}},
+            "allowed_destinations": [{}]
+        }}"#,
+        rule_destination, allowed
+    )
+}
+
+#[tokio::test]
+async fn test_mock_emitter_routes_by_rule_then_asset() {
+    let temp_dir = TempDir::new().unwrap();
+    let state_manager = Arc::new(
+        StateManager::load(temp_dir.path().join("state.json"))
+            .await
+            .unwrap(),
+    );
+    let config = Config::from_json(&routed_config(
+        r#""0xTREASURY", "0xusdc_vault", "0xCOLD""#,
+        r#", "destination": "0xCOLD""#,
+    ))
+    .unwrap();
+    let emitter = MockTxEmitter::new(state_manager, config.treasury_address.clone())
+        .with_destinations(Destinations::from_config(&config));
+
+    let decision = |asset: &str, destination: Option<&str>| SweepDecision {
+        amount: Amount::parse("1", 18).unwrap(),
+        asset: asset.to_string(),
+        rule_type: "native_balance".to_string(),
+        token_address: None,
+        destination: destination.map(str::to_string),
+        split: None,
+    };
+    let from = "0x1234".to_string();
+
+    let routed = emitter.emit_sweep(&from, &decision("USDC", None)).await.unwrap();
+    assert_eq!(routed.tx.to, "0xUSDC_VAULT");
+    let ruled = emitter
+        .emit_sweep(&from, &decision("USDC", Some("0xCOLD")))
+        .await
+        .unwrap();
+    assert_eq!(ruled.tx.to, "0xCOLD");
+    let default = emitter.emit_sweep(&from, &decision("ETH", None)).await.unwrap();
+    assert_eq!(default.tx.to, "0xTREASURY");
+
+    // A destination outside the allowlist is refused before a nonce is used
+    let err = emitter
+        .emit_sweep(&from, &decision("ETH", Some("0xATTACKER")))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("not in the allowlist"));
+    let next = emitter.emit_sweep(&from, &decision("ETH", None)).await.unwrap();
+    assert_eq!(next.tx.nonce, 3);
+}
+
+#[test]
+fn test_config_rejects_destinations_outside_allowlist() {
+    let err = Config::from_json(&routed_config(r#""0xTREASURY""#, "")).unwrap_err();
+    assert!(err.to_string().contains("0xUSDC_VAULT"));
+    let err = Config::from_json(&routed_config(
+        r#""0xTREASURY", "0xUSDC_VAULT""#,
+        r#", "destination": "0xCOLD""#,
+    ))
+    .unwrap_err();
+    assert!(err.to_string().contains("0xCOLD"));
+    let err = Config::from_json(&routed_config(r#""0xUSDC_VAULT""#, "")).unwrap_err();
+    assert!(err.to_string().contains("0xTREASURY"));
+}
+
+fn leg(address: &str, bps: u16, primary: bool) -> SplitDestination {
+    SplitDestination {
+        address: address.to_string(),
+        bps,
+        primary,
+    }
+}
+
+#[test]
+fn test_split_amount_gives_dust_to_primary() {
+    let amount = Amount::parse("10", 0).unwrap();
+    let shares = |legs: &[SplitDestination]| {
+        split_amount(amount, legs)
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        shares(&[leg("0xA", 3333, false), leg("0xB", 3333, true), leg("0xC", 3334, false)]),
+        ["3", "4", "3"]
+    );
+    assert_eq!(
+        shares(&[leg("0xA", 8000, false), leg("0xB", 2000, false)]),
+        ["8", "2"]
+    );
+    assert_eq!(
+        shares(&[leg("0xA", 1, false), leg("0xB", 9999, false)]),
+        ["1", "9"]
+    );
+}
+
+#[test]
+fn test_config_rejects_invalid_splits() {
+    let config = |legs: &str| {
+        routed_config(
+            r#""0xTREASURY", "0xUSDC_VAULT", "0xCOLD", "0xEXCHANGE""#,
+            &format!(r#", "destinations": [{}]"#, legs),
+        )
+    };
+    let split = r#"{"address": "0xCOLD", "bps": 8000}, {"address": "0xEXCHANGE", "bps": 2000, "primary": true}"#;
+    let parsed = Config::from_json(&config(split)).unwrap();
+    assert_eq!(parsed.hot_wallets[0].rules[0].split_destinations().len(), 2);
+
+    let err = Config::from_json(&config(
+        r#"{"address": "0xCOLD", "bps": 8000}, {"address": "0xEXCHANGE", "bps": 1000}"#,
+    ))
+    .unwrap_err();
+    assert!(format!("{:#}", err).contains("9000 bps"));
+    let err = Config::from_json(&config(
+        r#"{"address": "0xCOLD", "bps": 8000}, {"address": "0xELSEWHERE", "bps": 2000}"#,
+    ))
+    .unwrap_err();
+    assert!(err.to_string().contains("0xELSEWHERE"));
+    assert!(Config::from_json(&config(
+        r#"{"address": "0xCOLD", "bps": 5000, "primary": true}, {"address": "0xEXCHANGE", "bps": 5000, "primary": true}"#,
+    ))
+    .is_err());
+}
+
+const EVM_WALLET: &str = "0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f";
+const EVM_WALLET_KEY: &str = "0x4646464646464646464646464646464646464646464646464646464646464646";
+const EVM_TREASURY: &str = "0x3535353535353535353535353535353535353535";
+
+async fn create_evm_emitter(rpc_url: &str) -> (EvmTxEmitter, Arc<StateManager>, TempDir) {
+    create_evm_emitter_with_gas(rpc_url, GasConfig::default()).await
+}
+
+async fn create_evm_emitter_with_gas(
+    rpc_url: &str,
+    gas: GasConfig,
+) -> (EvmTxEmitter, Arc<StateManager>, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let state_manager = Arc::new(
+        StateManager::load(temp_dir.path().join("state.json"))
+            .await
+            .unwrap(),
+    );
+    let keystore =
+        Keystore::from_json(&format!(r#"{{"{}": "{}"}}"#, EVM_WALLET, EVM_WALLET_KEY)).unwrap();
+
+    let emitter = EvmTxEmitter::new(
+        state_manager.clone(),
+        EVM_TREASURY.to_string(),
+        Arc::new(JsonRpcClient::new(rpc_url).unwrap()),
+        EvmTxBuilder::new(1, gas),
+        keystore,
+        ReceiptPolling {
+            interval: Duration::from_millis(10),
+            timeout: Duration::from_millis(200),
+        },
+    );
+
+    (emitter, state_manager, temp_dir)
+}
+
+fn eth_decision() -> SweepDecision {
+    SweepDecision {
+        amount: Amount::parse("1.5", 18).unwrap(),
+        asset: "ETH".to_string(),
+        rule_type: "native_balance".to_string(),
+        token_address: None,
+        destination: None,
+        split: None,
+    }
+}
+
+/// Node that accepts every raw transaction and mines it after `polls` receipt queries
+async fn mining_node(polls: usize, status: &'static str) -> MockRpcServer {
+    let sent = Arc::new(Mutex::new(None::<String>));
+    let receipt_queries = AtomicUsize::new(0);
+    MockRpcServer::start(move |method, params| match method {
+        "eth_sendRawTransaction" => {
+            let raw = hex::decode(params[0].as_str().unwrap().trim_start_matches("0x")).unwrap();
+            let hash = format!("0x{}", hex::encode(keccak256(&raw)));
+            *sent.lock().unwrap() = Some(hash.clone());
+            Ok(json!(hash))
+        }
+        "eth_getTransactionReceipt" => {
+            if receipt_queries.fetch_add(1, Ordering::SeqCst) + 1 < polls {
+                return Ok(Value::Null);
+            }
+            Ok(json!({
+                "transactionHash": sent.lock().unwrap().clone().unwrap(),
+                "blockNumber": "0x10",
+                "gasUsed": "0x5208",
+                "status": status,
+            }))
+        }
+        _ => Err(rpc_error(-32601, "method not found")),
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_evm_emitter_broadcasts_and_records_receipt() {
+    let server = mining_node(3, "0x1").await;
+    let (emitter, state_manager, _temp_dir) = create_evm_emitter(&server.url).await;
+
+    let submitted = emitter
+        .emit_sweep(&EVM_WALLET.to_string(), &eth_decision())
+        .await
+        .unwrap();
+    let hash = submitted.hash.unwrap();
+
+    let sent = server.calls("eth_sendRawTransaction");
+    assert_eq!(sent.len(), 1);
+    let raw = hex::decode(sent[0][0].as_str().unwrap().trim_start_matches("0x")).unwrap();
+    assert_eq!(hash, format!("0x{}", hex::encode(keccak256(&raw))));
+    assert_eq!(server.calls("eth_getTransactionReceipt").len(), 3);
+
+    let snapshot = state_manager.fetch_snapshot().await;
+    let record = &snapshot.wallets[EVM_WALLET].transactions[0];
+    assert_eq!(record.hash, hash);
+    assert_eq!(record.nonce, 0);
+    assert_eq!(record.status, TxStatus::Confirmed);
+    assert_eq!(record.block_number, Some(16));
+    assert_eq!(record.gas_used, Some(21_000));
+}
+
+#[tokio::test]
+async fn test_evm_emitter_records_reverted_transaction() {
+    let server = mining_node(1, "0x0").await;
+    let (emitter, state_manager, _temp_dir) = create_evm_emitter(&server.url).await;
+
+    emitter
+        .emit_sweep(&EVM_WALLET.to_string(), &eth_decision())
+        .await
+        .unwrap();
+
+    let snapshot = state_manager.fetch_snapshot().await;
+    assert_eq!(
+        snapshot.wallets[EVM_WALLET].transactions[0].status,
+        TxStatus::Reverted
+    );
+}
+
+#[tokio::test]
+async fn test_evm_emitter_detects_dropped_transaction() {
+    let server = MockRpcServer::start(|method, params| match method {
+        "eth_sendRawTransaction" => {
+            let raw = hex::decode(params[0].as_str().unwrap().trim_start_matches("0x")).unwrap();
+            Ok(json!(format!("0x{}", hex::encode(keccak256(&raw)))))
+        }
+        "eth_getTransactionReceipt" | "eth_getTransactionByHash" => Ok(Value::Null),
+        _ => Err(rpc_error(-32601, "method not found")),
+    })
+    .await;
+    let (emitter, state_manager, _temp_dir) = create_evm_emitter(&server.url).await;
+
+    let err = emitter
+        .emit_sweep(&EVM_WALLET.to_string(), &eth_decision())
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("dropped"));
+
+    let snapshot = state_manager.fetch_snapshot().await;
+    assert_eq!(
+        snapshot.wallets[EVM_WALLET].transactions[0].status,
+        TxStatus::Dropped
+    );
+}
+
+#[tokio::test]
+async fn test_evm_emitter_reports_rejected_broadcast() {
+    let server = MockRpcServer::start(|method, _params| match method {
+        "eth_sendRawTransaction" => Err(rpc_error(
+            -32000,
+            "insufficient funds for gas * price + value",
+        )),
+        _ => Err(rpc_error(-32601, "method not found")),
+    })
+    .await;
+    let (emitter, state_manager, _temp_dir) = create_evm_emitter(&server.url).await;
+
+    let err = emitter
+        .emit_sweep(&EVM_WALLET.to_string(), &eth_decision())
+        .await
+        .unwrap_err();
+    assert!(format!("{:#}", err).contains("insufficient funds"));
+
+    // The rejected transaction did not use its nonce
+    assert_eq!(state_manager.next_nonce(&EVM_WALLET.to_string()).await, 0);
+    emitter
+        .emit_sweep(&EVM_WALLET.to_string(), &eth_decision())
+        .await
+        .unwrap_err();
+    for params in server.calls("eth_sendRawTransaction") {
+        let raw = hex::decode(params[0].as_str().unwrap().trim_start_matches("0x")).unwrap();
+        // Type byte, list header (2 bytes), chain id, then nonce 0 encoded as 0x80
+        assert_eq!(raw[4], 0x80);
+    }
+}
+
+/// Gas settings that treat every transaction as stuck right away
+fn eager_replacement(max_fee_gwei: &str) -> GasConfig {
+    GasConfig {
+        replacement: ReplacementConfig {
+            stuck_after_seconds: 0,
+            max_fee_per_gas_gwei: max_fee_gwei.parse().unwrap(),
+            ..ReplacementConfig::default()
+        },
+        ..GasConfig::default()
+    }
+}
+
+/// Gas limit, max fee per gas and value of a signed EIP-1559 transaction
+fn decode_eip1559(raw: &[u8]) -> (U256, U256, U256) {
+    assert_eq!(raw[0], 0x02);
+    // Read an RLP header: (header length, payload length)
+    let header = |item: &[u8]| -> (usize, usize) {
+        let long = |size: u8| {
+            let size = size as usize;
+            let length = item[1..=size]
+                .iter()
+                .fold(0usize, |length, byte| length << 8 | *byte as usize);
+            (1 + size, length)
+        };
+        match item[0] {
+            0x00..=0x7f => (0, 1),
+            byte @ 0x80..=0xb7 => (1, (byte - 0x80) as usize),
+            byte @ 0xb8..=0xbf => long(byte - 0xb7),
+            byte @ 0xc0..=0xf7 => (1, (byte - 0xc0) as usize),
+            byte => long(byte - 0xf7),
+        }
+    };
+    let (offset, _) = header(&raw[1..]);
+    let mut rest = &raw[1 + offset..];
+    let mut fields = Vec::new();
+    while !rest.is_empty() {
+        let (offset, length) = header(rest);
+        fields.push(&rest[offset..offset + length]);
+        rest = &rest[offset + length..];
+    }
+    let uint = |bytes: &[u8]| U256::from_be_slice(bytes);
+    (uint(fields[4]), uint(fields[3]), uint(fields[6]))
+}
+
+/// Node that accepts every raw transaction but only mines the `mined`-th one (0-based)
+async fn replacing_node(mined: usize) -> MockRpcServer {
+    funded_replacing_node(mined, U256::MAX).await
+}
+
+/// Like [`replacing_node`], but rejects transactions whose value and most
+/// fee are more than the wallet's `balance`
+async fn funded_replacing_node(mined: usize, balance: U256) -> MockRpcServer {
+    let sent = Arc::new(Mutex::new(Vec::<String>::new()));
+    MockRpcServer::start(move |method, params| match method {
+        "eth_sendRawTransaction" => {
+            let raw = hex::decode(params[0].as_str().unwrap().trim_start_matches("0x")).unwrap();
+            let (gas_limit, max_fee_per_gas, value) = decode_eip1559(&raw);
+            if value.saturating_add(gas_limit * max_fee_per_gas) > balance {
+                return Err(rpc_error(
+                    -32000,
+                    "insufficient funds for gas * price + value",
+                ));
+            }
+            let hash = format!("0x{}", hex::encode(keccak256(&raw)));
+            sent.lock().unwrap().push(hash.clone());
+            Ok(json!(hash))
+        }
+        "eth_getTransactionReceipt" => {
+            if sent.lock().unwrap().get(mined) != Some(&params[0].as_str().unwrap().to_string()) {
+                return Ok(Value::Null);
+            }
+            Ok(json!({
+                "transactionHash": params[0],
+                "blockNumber": "0x10",
+                "gasUsed": "0x5208",
+                "status": "0x1",
+            }))
+        }
+        "eth_getTransactionByHash" => Ok(json!({"hash": params[0]})),
+        _ => Err(rpc_error(-32601, "method not found")),
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_evm_emitter_replaces_stuck_transaction() {
+    let server = replacing_node(1).await;
+    let (emitter, state_manager, _temp_dir) =
+        create_evm_emitter_with_gas(&server.url, eager_replacement("100")).await;
+
+    let submitted = emitter
+        .emit_sweep(&EVM_WALLET.to_string(), &eth_decision())
+        .await
+        .unwrap();
+
+    let sent = server.calls("eth_sendRawTransaction");
+    assert_eq!(sent.len(), 2);
+    let hashes: Vec<String> = sent
+        .iter()
+        .map(|params| {
+            let raw = hex::decode(params[0].as_str().unwrap().trim_start_matches("0x")).unwrap();
+            format!("0x{}", hex::encode(keccak256(&raw)))
+        })
+        .collect();
+    assert_eq!(submitted.hash.as_ref(), Some(&hashes[1]));
+
+    let wallet = EVM_WALLET.to_string();
+    let snapshot = state_manager.fetch_snapshot().await;
+    let records = &snapshot.wallets[EVM_WALLET].transactions;
+    assert_eq!(records.len(), 2);
+    assert!(records.iter().all(|record| record.nonce == 0));
+    let status_of = |hash: &String| records.iter().find(|r| &r.hash == hash).unwrap().status;
+    assert_eq!(status_of(&hashes[0]), TxStatus::Replaced);
+    assert_eq!(status_of(&hashes[1]), TxStatus::Confirmed);
+    assert!(state_manager.pending_transactions(&wallet).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_evm_emitter_bumps_native_sweep_within_balance() {
+    // The wallet holds exactly the sweep and its fee at the base max fee
+    let gas = eager_replacement("100");
+    let balance = eth_decision().amount.units() + gas.native_sweep_fee().unwrap();
+    let server = funded_replacing_node(1, balance).await;
+    let (emitter, state_manager, _temp_dir) = create_evm_emitter_with_gas(&server.url, gas).await;
+
+    let submitted = emitter
+        .emit_sweep(&EVM_WALLET.to_string(), &eth_decision())
+        .await
+        .unwrap();
+
+    let sent = server.calls("eth_sendRawTransaction");
+    assert_eq!(sent.len(), 2);
+    let raw = hex::decode(sent[1][0].as_str().unwrap().trim_start_matches("0x")).unwrap();
+    let (_, max_fee_per_gas, value) = decode_eip1559(&raw);
+    assert_eq!(max_fee_per_gas, U256::from(33_000_000_000u64));
+    // The 3 gwei rise comes out of the swept value
+    assert_eq!(
+        value,
+        eth_decision().amount.units() - U256::from(21_000u64 * 3_000_000_000)
+    );
+    assert_eq!(
+        submitted.hash,
+        Some(format!("0x{}", hex::encode(keccak256(&raw))))
+    );
+    assert!(state_manager
+        .pending_transactions(&EVM_WALLET.to_string())
+        .await
+        .is_empty());
+}
+
+#[tokio::test]
+async fn test_evm_emitter_stops_replacing_at_fee_cap() {
+    let server = replacing_node(usize::MAX).await;
+    // The cap equals the initial max fee, so no replacement is allowed
+    let (emitter, state_manager, _temp_dir) =
+        create_evm_emitter_with_gas(&server.url, eager_replacement("30")).await;
+
+    let submitted = emitter
+        .emit_sweep(&EVM_WALLET.to_string(), &eth_decision())
+        .await
+        .unwrap();
+    assert_eq!(server.calls("eth_sendRawTransaction").len(), 1);
+
+    // Still unmined: the transaction stays tracked for a later cancel
+    let pending = state_manager
+        .pending_transactions(&EVM_WALLET.to_string())
+        .await;
+    assert_eq!(pending.len(), 1);
+    assert_eq!(Some(pending[0].latest_hash()), submitted.hash.as_deref());
+}
+
+#[tokio::test]
+async fn test_evm_emitter_resumes_bumping_earlier_pending_transaction() {
+    // Only the new sweep is mined; the earlier transaction stays stuck
+    let server = replacing_node(1).await;
+    let (emitter, state_manager, _temp_dir) =
+        create_evm_emitter_with_gas(&server.url, eager_replacement("100")).await;
+    let wallet = EVM_WALLET.to_string();
+
+    // A sweep at nonce 0 left pending by an earlier run
+    let stuck = EvmTxBuilder::new(1, GasConfig::default())
+        .build_transfer(
+            0,
+            &EVM_TREASURY.to_string(),
+            U256::from(1_000_000_000_000_000_000u64),
+        )
+        .unwrap();
+    state_manager
+        .track_pending(&wallet, stuck.to_pending("ETH", "0xstuck".to_string()))
+        .await
+        .unwrap();
+    state_manager.set_next_nonce(&wallet, 1).await.unwrap();
+
+    let submitted = emitter.emit_sweep(&wallet, &eth_decision()).await.unwrap();
+    assert_eq!(submitted.tx.nonce, 1);
+
+    let expected = stuck
+        .bumped(10, U256::MAX, true)
+        .unwrap()
+        .sign(&signing_key(EVM_WALLET_KEY))
+        .unwrap();
+    let sent = server.calls("eth_sendRawTransaction");
+    assert_eq!(sent.len(), 2);
+    assert_eq!(sent[0][0], expected.raw_hex());
+
+    let [pending] = &state_manager.pending_transactions(&wallet).await[..] else {
+        panic!("expected the earlier transaction to stay pending");
+    };
+    assert_eq!(pending.nonce, 0);
+    assert_eq!(
+        pending.hashes,
+        vec!["0xstuck".to_string(), expected.hash_hex()]
+    );
+}
+
+#[tokio::test]
+async fn test_evm_emitter_cancels_stuck_transaction() {
+    let server = replacing_node(0).await;
+    let (emitter, state_manager, _temp_dir) = create_evm_emitter(&server.url).await;
+    let wallet = EVM_WALLET.to_string();
+
+    // A sweep at nonce 3 left pending by an earlier run
+    let builder = EvmTxBuilder::new(1, GasConfig::default());
+    let stuck = builder
+        .build_transfer(3, &EVM_TREASURY.to_string(), U256::from(1_000u64))
+        .unwrap();
+    state_manager
+        .track_pending(&wallet, stuck.to_pending("ETH", "0xstuck".to_string()))
+        .await
+        .unwrap();
+    state_manager.set_next_nonce(&wallet, 4).await.unwrap();
+
+    // Nothing tracked at nonce 2 to outbid
+    assert!(emitter.cancel(&wallet, Some(2)).await.is_err());
+    assert!(server.calls("eth_sendRawTransaction").is_empty());
+
+    let outcome = emitter.cancel(&wallet, None).await.unwrap();
+    assert_eq!(outcome.status, TxStatus::Confirmed);
+
+    // The self-transfer outbids the stuck fees; the 1000 wei sweep could
+    // not have paid the rise itself
+    let expected = builder
+        .build_transfer(3, &wallet, U256::ZERO)
+        .unwrap()
+        .bumped(10, U256::MAX, false)
+        .unwrap();
+    assert!(stuck.bumped(10, U256::MAX, true).is_none());
+    let expected = expected.sign(&signing_key(EVM_WALLET_KEY)).unwrap();
+
+    let sent = server.calls("eth_sendRawTransaction");
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0][0], expected.raw_hex());
+    assert_eq!(outcome.hash, expected.hash_hex());
+
+    let snapshot = state_manager.fetch_snapshot().await;
+    let records = &snapshot.wallets[EVM_WALLET].transactions;
+    assert!(records.iter().any(|r| r.hash == "0xstuck" && r.status == TxStatus::Replaced));
+    assert!(state_manager.pending_transactions(&wallet).await.is_empty());
+    // Cancelling does not change which nonce the next sweep uses
+    assert_eq!(state_manager.next_nonce(&wallet).await, 4);
+
+    assert!(emitter.cancel(&wallet, None).await.is_err());
+}
+
+#[tokio::test]
+async fn test_evm_emitter_finds_and_fills_nonce_gaps() {
+    let sent = Arc::new(Mutex::new(Vec::<String>::new()));
+    let recorded = sent.clone();
+    let server = MockRpcServer::start(move |method, params| match method {
+        // Nonces 0 and 1 are mined
+        "eth_getTransactionCount" => {
+            assert_eq!(params[1], "latest");
+            Ok(json!("0x2"))
+        }
+        "eth_getTransactionByHash" => match params[0].as_str().unwrap() {
+            "0xlive" => Ok(json!({"hash": "0xlive"})),
+            _ => Ok(Value::Null),
+        },
+        "eth_sendRawTransaction" => {
+            let raw = hex::decode(params[0].as_str().unwrap().trim_start_matches("0x")).unwrap();
+            let hash = format!("0x{}", hex::encode(keccak256(&raw)));
+            recorded.lock().unwrap().push(hash.clone());
+            Ok(json!(hash))
+        }
+        "eth_getTransactionReceipt" => Ok(json!({
+            "transactionHash": params[0],
+            "blockNumber": "0x10",
+            "gasUsed": "0x5208",
+            "status": "0x1",
+        })),
+        _ => Err(rpc_error(-32601, "method not found")),
+    })
+    .await;
+    let (emitter, state_manager, _temp_dir) = create_evm_emitter(&server.url).await;
+    let wallet = EVM_WALLET.to_string();
+
+    // Nonces 0..5 were handed out: 3 is still in the mempool, 4 was dropped
+    // while the service was down, and nothing was tracked for 2
+    state_manager.set_next_nonce(&wallet, 5).await.unwrap();
+    let builder = EvmTxBuilder::new(1, GasConfig::default());
+    for (nonce, hash) in [(3, "0xlive"), (4, "0xgone")] {
+        let tx = builder
+            .build_transfer(nonce, &EVM_TREASURY.to_string(), U256::from(1u8))
+            .unwrap();
+        state_manager
+            .track_pending(&wallet, tx.to_pending("ETH", hash.to_string()))
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(state_manager.untracked_nonces(&wallet, 2).await, vec![2]);
+    assert_eq!(emitter.find_gaps(&wallet).await.unwrap(), vec![2, 4]);
+
+    // Nonce 5 was never handed out, so it is not a gap to fill
+    assert!(emitter.fill_gap(&wallet, 5).await.is_err());
+
+    let outcome = emitter.fill_gap(&wallet, 2).await.unwrap();
+    assert_eq!(outcome.status, TxStatus::Confirmed);
+    assert_eq!(*sent.lock().unwrap(), vec![outcome.hash.clone()]);
+
+    // A zero-value transfer from the wallet to itself at the missing nonce
+    let raw = server.calls("eth_sendRawTransaction")[0][0]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let expected = builder
+        .build_transfer(2, &wallet, U256::ZERO)
+        .unwrap()
+        .sign(&signing_key(EVM_WALLET_KEY))
+        .unwrap();
+    assert_eq!(raw, expected.raw_hex());
+
+    let snapshot = state_manager.fetch_snapshot().await;
+    let record = snapshot.wallets[EVM_WALLET]
+        .transactions
+        .iter()
+        .find(|record| record.nonce == 2)
+        .unwrap();
+    assert_eq!(record.asset, "gap_fill");
+    assert_eq!(snapshot.wallets[EVM_WALLET].next_nonce, 5);
+}
+
+const STATION_KEY: &str = "0x0101010101010101010101010101010101010101010101010101010101010101";
+
+fn signing_key(hex_key: &str) -> k256::ecdsa::SigningKey {
+    k256::ecdsa::SigningKey::from_slice(&hex::decode(&hex_key[2..]).unwrap()).unwrap()
+}
+
+fn usdc_decision() -> SweepDecision {
+    SweepDecision {
+        amount: Amount::parse("150", 6).unwrap(),
+        asset: "USDC".to_string(),
+        rule_type: "token_balance".to_string(),
+        token_address: Some("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48".to_string()),
+        destination: None,
+        split: None,
+    }
+}
+
+/// Node that confirms every transaction and reports `native_balance` for any address
+async fn funding_node(native_balance: u64) -> MockRpcServer {
+    MockRpcServer::start(move |method, params| match method {
+        "eth_getBalance" => {
+            assert_eq!(params[1], "pending");
+            Ok(json!(format!("{:#x}", native_balance)))
+        }
+        "eth_sendRawTransaction" => {
+            let raw = hex::decode(params[0].as_str().unwrap().trim_start_matches("0x")).unwrap();
+            Ok(json!(format!("0x{}", hex::encode(keccak256(&raw)))))
+        }
+        "eth_getTransactionReceipt" => Ok(json!({
+            "transactionHash": params[0],
+            "blockNumber": "0x10",
+            "gasUsed": "0x5208",
+            "status": "0x1",
+        })),
+        _ => Err(rpc_error(-32601, "method not found")),
+    })
+    .await
+}
+
+async fn create_funded_emitter(
+    rpc_url: &str,
+    gas: GasConfig,
+) -> (EvmTxEmitter, Arc<StateManager>, String, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let state_manager = Arc::new(
+        StateManager::load(temp_dir.path().join("state.json"))
+            .await
+            .unwrap(),
+    );
+    let station = format_address(&address_of(signing_key(STATION_KEY).verifying_key()));
+    let keystore = Keystore::from_json(&format!(
+        r#"{{"{}": "{}", "{}": "{}"}}"#,
+        EVM_WALLET, EVM_WALLET_KEY, station, STATION_KEY
+    ))
+    .unwrap();
+
+    let emitter = EvmTxEmitter::new(
+        state_manager.clone(),
+        EVM_TREASURY.to_string(),
+        Arc::new(JsonRpcClient::new(rpc_url).unwrap()),
+        EvmTxBuilder::new(1, gas),
+        keystore,
+        ReceiptPolling {
+            interval: Duration::from_millis(10),
+            timeout: Duration::from_millis(200),
+        },
+    )
+    .with_gas_station(station.clone(), None);
+
+    (emitter, state_manager, station, temp_dir)
+}
+
+#[tokio::test]
+async fn test_gas_station_tops_up_before_token_sweep() {
+    // 21000 gas × 30 gwei on hand, 65000 gas × 30 gwei needed
+    let server = funding_node(630_000_000_000_000).await;
+    let (emitter, state_manager, station, _temp_dir) = create_funded_emitter(&server.url, GasConfig::default()).await;
+
+    emitter
+        .emit_sweep(&EVM_WALLET.to_string(), &usdc_decision())
+        .await
+        .unwrap();
+
+    let sent = server.calls("eth_sendRawTransaction");
+    assert_eq!(sent.len(), 2);
+    let builder = EvmTxBuilder::new(1, GasConfig::default());
+    let top_up = builder
+        .build_transfer(0, &EVM_WALLET.to_string(), U256::from(1_320_000_000_000_000u64))
+        .unwrap()
+        .sign(&signing_key(STATION_KEY))
+        .unwrap();
+    assert_eq!(sent[0][0], top_up.raw_hex());
+
+    // The station keeps its own nonce and records
+    let snapshot = state_manager.fetch_snapshot().await;
+    assert_eq!(snapshot.wallets[&station].next_nonce, 1);
+    assert_eq!(snapshot.wallets[&station].transactions[0].asset, "gas_top_up");
+    // A top-up is not a sweep
+    assert_eq!(snapshot.wallets[&station].total_sweeps, 0);
+    assert_eq!(snapshot.wallets[EVM_WALLET].total_sweeps, 1);
+    assert_eq!(snapshot.wallets[EVM_WALLET].next_nonce, 1);
+    assert_eq!(snapshot.wallets[EVM_WALLET].transactions[0].asset, "USDC");
+}
+
+#[tokio::test]
+async fn test_gas_station_skips_funded_wallets_and_native_sweeps() {
+    let server = funding_node(2_000_000_000_000_000).await;
+    let (emitter, state_manager, station, _temp_dir) = create_funded_emitter(&server.url, GasConfig::default()).await;
+
+    emitter
+        .emit_sweep(&EVM_WALLET.to_string(), &usdc_decision())
+        .await
+        .unwrap();
+    emitter
+        .emit_sweep(&EVM_WALLET.to_string(), &eth_decision())
+        .await
+        .unwrap();
+
+    assert_eq!(server.calls("eth_getBalance").len(), 1);
+    assert_eq!(server.calls("eth_sendRawTransaction").len(), 2);
+    assert_eq!(state_manager.next_nonce(&station).await, 0);
+}
+
+#[tokio::test]
+async fn test_replaced_gas_top_up_still_covers_the_shortfall() {
+    // 21000 gas × 30 gwei on hand, 65000 gas × 30 gwei needed; the first
+    // transaction, the top-up, is never mined
+    let sent = Arc::new(Mutex::new(Vec::<String>::new()));
+    let server = MockRpcServer::start(move |method, params| match method {
+        "eth_getBalance" => Ok(json!(format!("{:#x}", 630_000_000_000_000u64))),
+        "eth_sendRawTransaction" => {
+            let raw = hex::decode(params[0].as_str().unwrap().trim_start_matches("0x")).unwrap();
+            let hash = format!("0x{}", hex::encode(keccak256(&raw)));
+            sent.lock().unwrap().push(hash.clone());
+            Ok(json!(hash))
+        }
+        "eth_getTransactionReceipt" => {
+            if sent.lock().unwrap().first() == Some(&params[0].as_str().unwrap().to_string()) {
+                return Ok(Value::Null);
+            }
+            Ok(json!({
+                "transactionHash": params[0],
+                "blockNumber": "0x10",
+                "gasUsed": "0x5208",
+                "status": "0x1",
+            }))
+        }
+        "eth_getTransactionByHash" => Ok(json!({"hash": params[0]})),
+        _ => Err(rpc_error(-32601, "method not found")),
+    })
+    .await;
+    let (emitter, _state_manager, _station, _temp_dir) =
+        create_funded_emitter(&server.url, eager_replacement("100")).await;
+
+    emitter
+        .emit_sweep(&EVM_WALLET.to_string(), &usdc_decision())
+        .await
+        .unwrap();
+
+    // Stuck top-up, its replacement, then the token sweep
+    let sent = server.calls("eth_sendRawTransaction");
+    assert_eq!(sent.len(), 3);
+    let raw = hex::decode(sent[1][0].as_str().unwrap().trim_start_matches("0x")).unwrap();
+    let (_, max_fee_per_gas, value) = decode_eip1559(&raw);
+    assert_eq!(max_fee_per_gas, U256::from(33_000_000_000u64));
+    assert_eq!(value, U256::from(1_320_000_000_000_000u64));
 }